@@ -23,6 +23,7 @@ use gtk::prelude::*;
 use gtk::{gio, glib};
 
 use crate::config::VERSION;
+use crate::tools::commands;
 use crate::GnoteWindow;
 
 mod imp {
@@ -43,7 +44,9 @@ mod imp {
             self.parent_constructed();
             let obj = self.instance();
             obj.setup_gactions();
-            obj.set_accels_for_action("app.quit", &["<primary>q"]);
+            for command in commands::COMMANDS {
+                obj.set_accels_for_action(command.action, command.accels);
+            }
         }
     }
 
@@ -0,0 +1,72 @@
+use crate::tools::attachments::{self, AttachmentLink, AttachmentStore};
+use std::{collections::BTreeSet, fs};
+
+fn hash_name(extension: &str) -> String {
+    attachments::content_name(b"picture", extension)
+}
+
+#[test]
+fn test_parse_and_format() {
+    let image = AttachmentLink {
+        is_image: true,
+        label: String::from("The [garden]"),
+        name: hash_name("png"),
+        size: Some(String::from("medium")),
+    };
+    let file = AttachmentLink {
+        is_image: false,
+        label: String::from("report.pdf"),
+        name: hash_name("pdf"),
+        size: None,
+    };
+    let text = format!(
+        "Before\n{}\nand {} after",
+        image.to_markup(),
+        file.to_markup()
+    );
+
+    let links = attachments::parse(&text);
+    assert_eq!(links.len(), 2);
+    assert_eq!(&text[links[0].0.clone()], image.to_markup());
+    assert_eq!(links[0].1.label, "The garden");
+    assert_eq!(links[0].1.size.as_deref(), Some("medium"));
+    assert_eq!(links[1].1, file);
+
+    assert!(attachments::parse("![cat](attachment:not-a-hash.png)").is_empty());
+    assert_eq!(
+        attachments::referenced([text.as_str(), "nothing here"]),
+        BTreeSet::from([hash_name("png"), hash_name("pdf")])
+    );
+}
+
+#[test]
+fn test_content_name() {
+    let name = attachments::content_name(b"abc", "PNG");
+    assert_eq!(
+        name,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad.png"
+    );
+    assert_eq!(attachments::content_name(b"abc", "").len(), 64);
+}
+
+#[test]
+fn test_store() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let store = AttachmentStore::new(dir.path().join("attachments"));
+
+    let first = store.add(b"same content", "txt").unwrap();
+    let second = store.add(b"same content", "txt").unwrap();
+    let other = store.add(b"other content", "txt").unwrap();
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+    assert_eq!(fs::read(store.path(&first)).unwrap(), b"same content");
+
+    fs::write(store.path("notes.txt"), "not the store's").unwrap();
+    let removed = store
+        .collect_garbage(&BTreeSet::from([first.clone()]))
+        .unwrap();
+    assert_eq!(removed, 1);
+    assert!(store.path(&first).exists());
+    assert!(!store.path(&other).exists());
+    assert!(store.path("notes.txt").exists());
+}
@@ -0,0 +1,37 @@
+use crate::tools::checklist::{self, ChecklistProgress};
+
+const LIST: [&str; 5] = ["☑ Milk", "☐ Eggs", "  ☑ Free range", "  ☐ Dozen", "☐ Bread"];
+
+#[test]
+fn test_progress() {
+    let text = format!("Shopping\n{}\nDone", LIST.join("\n"));
+    let progress = checklist::progress(&text);
+
+    assert_eq!(progress, ChecklistProgress { done: 2, total: 5 });
+    assert_eq!(progress.to_string(), "2/5 done");
+    assert_eq!(checklist::list_ranges(&text), vec![1..6]);
+}
+
+#[test]
+fn test_move_completed_to_bottom() {
+    assert_eq!(
+        checklist::move_completed_to_bottom(&LIST),
+        vec!["☐ Eggs", "  ☐ Dozen", "  ☑ Free range", "☐ Bread", "☑ Milk"]
+    );
+}
+
+#[test]
+fn test_clear_completed() {
+    assert_eq!(
+        checklist::clear_completed(&LIST),
+        vec!["☐ Eggs", "  ☐ Dozen", "☐ Bread"]
+    );
+}
+
+#[test]
+fn test_uncheck_all() {
+    assert_eq!(
+        checklist::uncheck_all(&LIST),
+        vec!["☐ Milk", "☐ Eggs", "  ☐ Free range", "  ☐ Dozen", "☐ Bread"]
+    );
+}
@@ -0,0 +1,120 @@
+use crate::tools::{
+    attachments::AttachmentLink,
+    clipboard::{self, PasteFormat},
+};
+
+#[test]
+fn test_negotiate() {
+    assert_eq!(
+        clipboard::negotiate(&["text/plain", "text/html", "image/png"]),
+        Some(PasteFormat::Image)
+    );
+    assert_eq!(
+        clipboard::negotiate(&["text/plain", "text/uri-list", "text/html"]),
+        Some(PasteFormat::UriList)
+    );
+    assert_eq!(
+        clipboard::negotiate(&["text/plain;charset=utf-8", "text/html"]),
+        Some(PasteFormat::Html)
+    );
+    assert_eq!(
+        clipboard::negotiate(&["UTF8_STRING"]),
+        Some(PasteFormat::Text)
+    );
+    assert_eq!(
+        clipboard::negotiate(&["text/html", "image/png", clipboard::NOTE_MIME_TYPE]),
+        Some(PasteFormat::Note)
+    );
+    assert_eq!(clipboard::negotiate(&["application/x-unknown"]), None);
+}
+
+#[test]
+fn test_parse_uri_list() {
+    assert_eq!(
+        clipboard::parse_uri_list("# copied\r\nfile:///tmp/a.pdf\r\n\r\nhttps://gnome.org\r\n"),
+        vec!["file:///tmp/a.pdf", "https://gnome.org"]
+    );
+}
+
+#[test]
+fn test_decode_html() {
+    assert_eq!(clipboard::decode_html(b"<b>hi</b>"), "<b>hi</b>");
+    assert_eq!(
+        clipboard::decode_html(&[0xff, 0xfe, b'<', 0, b'p', 0, b'>', 0, 0, 0]),
+        "<p>"
+    );
+}
+
+#[test]
+fn test_html_to_note() {
+    let html = "<html><head><style>p { color: red }</style></head><body>\
+        <p>Plan for <b>Monday </b>&amp; <a href=\"https://gnome.org\">GNOME</a></p>\
+        <ul><li>Milk</li><li><input type=\"checkbox\" checked> Bread\
+        <ul><li><input type=\"checkbox\">Eggs</li></ul></li></ul>\
+        <ol><li>First</li><li><i>Second</i></li></ol>\
+        <p>Mail <a href=\"mailto:bill@example.com\">bill@example.com</a><br>bye</p>\
+        </body></html>";
+
+    assert_eq!(
+        clipboard::html_to_note(html),
+        "Plan for **Monday** & [GNOME](https://gnome.org)\n\
+         • Milk\n\
+         ☑ Bread\n\
+         \x20 ☐ Eggs\n\
+         1. First\n\
+         2. *Second*\n\
+         Mail bill@example.com\n\
+         bye"
+    );
+
+    // A line break trimming the spaces before the link's text
+    assert_eq!(
+        clipboard::html_to_note("<pre>a  <a href=\"http://x\"><br></a></pre>"),
+        "a"
+    );
+}
+
+const NOTE: &str = "Shopping **today**\n\
+    • Milk\n\
+    \x20 ☑ Bread & butter\n\
+    \x20 ☐ See https://gnome.org\n\
+    \n\
+    ![Receipt](attachment:0000000000000000000000000000000000000000000000000000000000000000.png)";
+
+fn attachment_uri(link: &AttachmentLink) -> String {
+    format!("file:///attachments/{}", &link.name[60..])
+}
+
+#[test]
+fn test_note_to_markdown() {
+    assert_eq!(
+        clipboard::note_to_markdown(NOTE, &attachment_uri),
+        "Shopping **today**\n\
+         - Milk\n\
+         \x20 - [x] Bread & butter\n\
+         \x20 - [ ] See https://gnome.org\n\
+         \n\
+         ![Receipt](file:///attachments/0000.png)"
+    );
+}
+
+#[test]
+fn test_note_to_html() {
+    assert_eq!(
+        clipboard::note_to_html(NOTE, &attachment_uri),
+        "<div>Shopping <strong>today</strong></div>\
+         <ul><li>Milk\
+         <ul><li><input type=\"checkbox\" disabled checked> Bread &amp; butter</li>\
+         <li><input type=\"checkbox\" disabled> See <a href=\"https://gnome.org\">https://gnome.org</a>\
+         </li></ul></li></ul>\
+         <div><br></div>\
+         <div><img src=\"file:///attachments/0000.png\" alt=\"Receipt\"></div>"
+    );
+}
+
+#[test]
+fn test_html_round_trip() {
+    let note = "Shopping **today**\n• Milk\n  ☑ Bread\n  ☐ Eggs";
+    let html = clipboard::note_to_html(note, &attachment_uri);
+    assert_eq!(clipboard::html_to_note(&html), note);
+}
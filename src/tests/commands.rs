@@ -0,0 +1,60 @@
+use crate::tools::commands::{self, COMMANDS};
+
+#[test]
+fn test_commands_are_unique() {
+    for (index, command) in COMMANDS.iter().enumerate() {
+        assert!(
+            COMMANDS[index + 1..]
+                .iter()
+                .all(|other| other.action != command.action),
+            "{} is listed twice",
+            command.action
+        );
+        for accel in command.accels {
+            assert!(
+                COMMANDS[index + 1..]
+                    .iter()
+                    .all(|other| !other.accels.contains(accel)),
+                "{} is bound twice",
+                accel
+            );
+        }
+    }
+}
+
+#[test]
+fn test_every_action_is_listed() {
+    let sources = [
+        include_str!("../widgets/window.rs"),
+        include_str!("../widgets/gnote_editor.rs"),
+        include_str!("../widgets/gnote_image.rs"),
+        include_str!("../widgets/gnote_attachment.rs"),
+    ];
+    let installed = sources.iter().flat_map(|source| {
+        source
+            .split("install_action(\"")
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+    });
+    for action in installed {
+        assert!(
+            COMMANDS
+                .iter()
+                .any(|command| command.action.split("::").next() == Some(action)),
+            "{} isn't listed",
+            action
+        );
+    }
+}
+
+#[test]
+fn test_filter() {
+    assert_eq!(commands::filter("").len(), COMMANDS.len());
+
+    let actions: Vec<&str> = commands::filter("new note")
+        .into_iter()
+        .map(|command| command.action)
+        .collect();
+    assert_eq!(actions.first(), Some(&"notebook.add-note"));
+    assert!(commands::filter("zzz").is_empty());
+}
@@ -0,0 +1,147 @@
+use crate::tests::test_data::{self, dated_note};
+use crate::tools::{
+    crypto::{self, CryptoError, NotebookKey, SealedKey},
+    history::{History, Snapshot},
+    io::{NoteFile, NoteFileItem},
+    merge,
+    storage::{JsonStorage, Storage},
+};
+use std::fs;
+
+fn notebook() -> NoteFile {
+    test_data::notebook(vec![dated_note("a", "Server", "root password: hunter2")])
+}
+
+fn contains(data: &[u8], text: &str) -> bool {
+    data.windows(text.len())
+        .any(|window| window == text.as_bytes())
+}
+
+#[test]
+fn test_encrypted_notebook() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let notes_path = dir.path().join("notes.json").to_string_lossy().to_string();
+    let history_path = dir
+        .path()
+        .join("history.json")
+        .to_string_lossy()
+        .to_string();
+    let key = NotebookKey::generate();
+
+    let storage = JsonStorage::encrypted(&notes_path, key.clone());
+    storage.save(&notebook()).unwrap();
+    let mut history = History::default();
+    history.notes.insert(
+        String::from("a"),
+        vec![Snapshot {
+            time: 1_700_000_000,
+            title: String::from("Server"),
+            body: String::from("root password: hunter1"),
+        }],
+    );
+    history.save(&history_path, Some(&key)).unwrap();
+
+    let notes_data = fs::read(&notes_path).unwrap();
+    assert!(crypto::is_encrypted(&notes_data));
+    assert!(!contains(&notes_data, "hunter2"));
+    assert!(!contains(&fs::read(&history_path).unwrap(), "hunter1"));
+
+    assert!(merge::same_notes(&storage.load().unwrap(), &notebook()));
+    assert_eq!(History::load(&history_path, Some(&key)).unwrap(), history);
+    // Without the key, or with another one, there is nothing to read
+    assert!(JsonStorage::new(&notes_path).load().is_err());
+    assert!(History::load(&history_path, None).is_err());
+    assert!(JsonStorage::encrypted(&notes_path, NotebookKey::generate())
+        .load()
+        .is_err());
+
+    // Changed on disk, it no longer decrypts
+    let mut tampered = String::from_utf8(notes_data).unwrap();
+    let data_at = tampered.find("\"data\":\"").unwrap() + 8;
+    let flipped = if &tampered[data_at..data_at + 1] == "A" {
+        "B"
+    } else {
+        "A"
+    };
+    tampered.replace_range(data_at..data_at + 1, flipped);
+    fs::write(&notes_path, tampered).unwrap();
+    assert!(storage.load().is_err());
+}
+
+#[test]
+fn test_plain_notebook_is_read_with_key() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let notes_path = dir.path().join("notes.json").to_string_lossy().to_string();
+    JsonStorage::new(&notes_path).save(&notebook()).unwrap();
+
+    // Picked up as it is while the notebook is being encrypted, and encrypted on the next save
+    let storage = JsonStorage::encrypted(&notes_path, NotebookKey::generate());
+    let note_file = storage.load().unwrap();
+    assert!(merge::same_notes(&note_file, &notebook()));
+    storage.save(&note_file).unwrap();
+    assert!(crypto::is_encrypted(&fs::read(&notes_path).unwrap()));
+}
+
+#[test]
+fn test_change_passphrase() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let key_path = dir
+        .path()
+        .join("notebook_key.json")
+        .to_string_lossy()
+        .to_string();
+    let key = NotebookKey::generate();
+    let encrypted = key.encrypt(b"root password: hunter2").unwrap();
+
+    key.seal("correct horse").unwrap().save(&key_path).unwrap();
+    let sealed = SealedKey::load(&key_path).unwrap();
+    assert_eq!(
+        sealed.unseal("battery staple").unwrap_err(),
+        CryptoError::WrongPassphrase
+    );
+    let unsealed = sealed.unseal("correct horse").unwrap();
+
+    // Only the key file changes, and what was encrypted before still opens
+    unsealed
+        .seal("battery staple")
+        .unwrap()
+        .save(&key_path)
+        .unwrap();
+    let sealed = SealedKey::load(&key_path).unwrap();
+    assert_eq!(
+        sealed.unseal("correct horse").unwrap_err(),
+        CryptoError::WrongPassphrase
+    );
+    let unsealed = sealed.unseal("battery staple").unwrap();
+    assert_eq!(
+        unsealed.decrypt(&encrypted).unwrap().as_slice(),
+        b"root password: hunter2"
+    );
+    assert!(!dir.path().join("notebook_key.json.tmp").exists());
+}
+
+#[test]
+fn test_lock_and_unlock() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let notes_path = dir.path().join("notes.json").to_string_lossy().to_string();
+    let key_path = dir
+        .path()
+        .join("notebook_key.json")
+        .to_string_lossy()
+        .to_string();
+    let key = NotebookKey::generate();
+    key.seal("correct horse").unwrap().save(&key_path).unwrap();
+    JsonStorage::encrypted(&notes_path, key)
+        .save(&notebook())
+        .unwrap();
+
+    // Locked, the notebook can't be read
+    assert!(JsonStorage::new(&notes_path).load().is_err());
+
+    let key = SealedKey::load(&key_path)
+        .unwrap()
+        .unseal("correct horse")
+        .unwrap();
+    let unlocked = JsonStorage::encrypted(&notes_path, key).load().unwrap();
+    assert!(merge::same_notes(&unlocked, &notebook()));
+}
@@ -0,0 +1,63 @@
+use crate::tools::diff::{changed_range, changed_ranges, diff_lines, DiffLine};
+
+#[test]
+fn test_diff_lines() {
+    let old = "Groceries\nMilk\nBread\nEggs";
+    let new = "Groceries\nMilk\nButter\nEggs\nJam";
+    assert_eq!(
+        diff_lines(old, new),
+        vec![
+            DiffLine::Same("Groceries"),
+            DiffLine::Same("Milk"),
+            DiffLine::Removed("Bread"),
+            DiffLine::Added("Butter"),
+            DiffLine::Same("Eggs"),
+            DiffLine::Added("Jam"),
+        ]
+    );
+
+    assert_eq!(diff_lines(old, old).len(), 4);
+    assert_eq!(diff_lines("", "One"), vec![DiffLine::Added("One")]);
+    assert_eq!(
+        diff_lines("a\nb\nc", "c\na"),
+        vec![
+            DiffLine::Removed("a"),
+            DiffLine::Removed("b"),
+            DiffLine::Same("c"),
+            DiffLine::Added("a"),
+        ]
+    );
+}
+
+#[test]
+fn test_changed_range() {
+    assert_eq!(
+        changed_range("[ ] Pay due:2026-12-01 ￼", "[ ] Pay due:2026-12-24 ￼"),
+        (20..22, "24")
+    );
+    assert_eq!(
+        changed_range("Gifts", "Gifts due:2026-12-24"),
+        (5..5, " due:2026-12-24")
+    );
+    assert_eq!(changed_range("aaa", "aa"), (2..3, ""));
+    assert_eq!(changed_range("same", "same"), (4..4, ""));
+    assert_eq!(changed_range("é", "è"), (0..2, "è"));
+}
+
+#[test]
+fn test_changed_ranges() {
+    assert_eq!(
+        changed_ranges(
+            "See [[Old]] ￼ and [[Old]]\nKeep\n[[Old]]",
+            "See [[New]] ￼ and [[New]]\nKeep\n[[New]]"
+        ),
+        Some(vec![
+            (6..9, String::from("New")),
+            (22..25, String::from("New")),
+            (35..38, String::from("New")),
+        ])
+    );
+    assert_eq!(changed_ranges("same\n", "same\n"), Some(Vec::new()));
+    assert_eq!(changed_ranges("one line", "two\nlines"), None);
+    assert_eq!(changed_ranges("a\nb", "a b\n"), None);
+}
@@ -0,0 +1,60 @@
+use crate::tools::due_date::{self, DueDate, Urgency};
+
+#[test]
+fn test_find() {
+    let (range, due) = due_date::find("Pay rent due:2026-11-01 please").unwrap();
+    assert_eq!(range, 9..23);
+    assert_eq!(due, DueDate::new(2026, 11, 1));
+
+    let (_, due) = due_date::find("Call due:2026-11-01T14:05").unwrap();
+    assert_eq!(due, DueDate::new(2026, 11, 1).with_time(14, 5));
+    assert_eq!(due.to_string(), "2026-11-01T14:05");
+
+    assert!(due_date::find("overdue:2026-11-01").is_none());
+    assert!(due_date::find("due:2026-13-01").is_none());
+    // Digits of other scripts aren't dates
+    assert!(due_date::find("Pay due:٢٠٢٦-١١-٠١").is_none());
+    assert!(due_date::find("due:2026-02-29").is_none());
+    assert!(due_date::find("due:2026-04-31").is_none());
+    assert_eq!(
+        due_date::find("due:2028-02-29").unwrap().1,
+        DueDate::new(2028, 2, 29)
+    );
+
+    // A malformed token doesn't hide a valid one after it
+    let (range, due) = due_date::find("Pay due:2026-02-31 due:2026-03-02").unwrap();
+    assert_eq!(range, 19..33);
+    assert_eq!(due, DueDate::new(2026, 3, 2));
+    assert_eq!(
+        due_date::find("due:soon due:2026-03-02T25:00 due:2026-03-02")
+            .unwrap()
+            .0,
+        30..44
+    );
+}
+
+#[test]
+fn test_set() {
+    let due = DueDate::new(2026, 12, 24);
+    assert_eq!(due_date::set("Gifts", &due), "Gifts due:2026-12-24");
+    assert_eq!(
+        due_date::set("Gifts due:2026-12-01 soon", &due),
+        "Gifts due:2026-12-24 soon"
+    );
+}
+
+#[test]
+fn test_urgency_and_reminder() {
+    let today = DueDate::new(2026, 10, 19).with_time(12, 0);
+
+    assert_eq!(DueDate::new(2026, 10, 18).urgency(&today), Urgency::Overdue);
+    assert_eq!(DueDate::new(2026, 10, 19).urgency(&today), Urgency::Today);
+    assert_eq!(
+        DueDate::new(2026, 10, 20).urgency(&today),
+        Urgency::Upcoming
+    );
+    assert_eq!(
+        DueDate::new(2026, 10, 19).reminder(),
+        DueDate::new(2026, 10, 19).with_time(9, 0)
+    );
+}
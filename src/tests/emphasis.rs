@@ -0,0 +1,19 @@
+use crate::tools::emphasis::{self, Style};
+
+#[test]
+fn test_find() {
+    let text = "**Due** *soon*, but not 2 * 3 * 4 or a*b*c";
+    let found = emphasis::find(text);
+
+    assert_eq!(found.len(), 2);
+    assert_eq!(&text[found[0].range.clone()], "**Due**");
+    assert_eq!(found[0].style, Style::Strong);
+    assert_eq!(&text[found[1].range.clone()], "*soon*");
+    assert_eq!(found[1].style, Style::Emphasis);
+}
+
+#[test]
+fn test_find_ignores_list_markers() {
+    assert!(emphasis::find("* one\n* two").is_empty());
+    assert!(emphasis::find("**bold*").is_empty());
+}
@@ -0,0 +1,129 @@
+use crate::tools::{
+    find::{self, FindOptions},
+    io::{NoteFile, NoteFileItem},
+};
+
+#[test]
+fn test_find() {
+    let text = "Cat, cat and concatenate";
+    let options = FindOptions::default();
+    assert_eq!(find::find(text, "cat", &options).unwrap().len(), 3);
+
+    let case_sensitive = FindOptions {
+        case_sensitive: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        find::find(text, "cat", &case_sensitive).unwrap(),
+        vec![5..8, 16..19]
+    );
+
+    let whole_word = FindOptions {
+        whole_word: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        find::find(text, "cat", &whole_word).unwrap(),
+        vec![0..3, 5..8]
+    );
+
+    // Without regex mode the query is taken literally
+    assert_eq!(find::find("a.c abc", "a.c", &options).unwrap(), vec![0..3]);
+    let regex = FindOptions {
+        regex: true,
+        ..Default::default()
+    };
+    assert_eq!(find::find("a.c abc", "a.c", &regex).unwrap().len(), 2);
+    assert!(find::find("text", "(", &regex).is_err());
+    assert!(find::find("text", "", &options).unwrap().is_empty());
+}
+
+#[test]
+fn test_replace() {
+    let regex = FindOptions {
+        regex: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        find::replace_all(
+            "2024-01-31 and 2023-12-01",
+            r"(\d+)-(\d+)-(\d+)",
+            "$3/$2/$1",
+            &regex
+        )
+        .unwrap()
+        .as_deref(),
+        Some("31/01/2024 and 01/12/2023")
+    );
+
+    // $ is only special in regex mode
+    assert_eq!(
+        find::replace_all("price", "price", "$1", &FindOptions::default())
+            .unwrap()
+            .as_deref(),
+        Some("$1")
+    );
+    assert_eq!(
+        find::replace_all("text", "missing", "x", &FindOptions::default()).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_notebook_occurrences() {
+    let note_file = NoteFile {
+        children: Some(vec![
+            NoteFileItem {
+                title: String::from("Project Alpha"),
+                body: Some(String::from("Kickoff\nAlpha ships in May, alpha!")),
+                children: None,
+                is_folder: false,
+                modified: None,
+                id: String::new(),
+            },
+            NoteFileItem {
+                title: String::from("Other"),
+                body: None,
+                children: None,
+                is_folder: false,
+                modified: None,
+                id: String::new(),
+            },
+        ]),
+        smart_folders: Vec::new(),
+    };
+
+    let occurrences =
+        find::notebook_occurrences(&note_file, "alpha", "Beta", &FindOptions::default(), false)
+            .unwrap();
+    assert_eq!(occurrences.len(), 2);
+    assert_eq!(occurrences[0].line, "Alpha ships in May, alpha!");
+    assert_eq!(occurrences[0].line_range, 0..5);
+    assert!(occurrences.iter().all(|occurrence| !occurrence.in_title));
+
+    let with_titles =
+        find::notebook_occurrences(&note_file, "alpha", "Beta", &FindOptions::default(), true)
+            .unwrap();
+    assert_eq!(with_titles.len(), 3);
+    assert!(with_titles[0].in_title);
+
+    // Only the chosen occurrences are replaced
+    let body = note_file.children.as_ref().unwrap()[0]
+        .body
+        .as_deref()
+        .unwrap();
+    assert_eq!(
+        find::apply_occurrences(body, &[&occurrences[1]]),
+        "Kickoff\nAlpha ships in May, Beta!"
+    );
+}
+
+#[test]
+fn test_char_ranges() {
+    let text = "café ☕ café";
+    let matches = find::find(text, "café", &FindOptions::default()).unwrap();
+    assert_eq!(matches, vec![0..5, 10..15]);
+    assert_eq!(find::char_ranges(text, &matches), vec![0..4, 7..11]);
+    // Out of order is counted again from the start
+    assert_eq!(find::char_ranges(text, &[10..15, 0..5]), vec![7..11, 0..4]);
+}
@@ -0,0 +1,259 @@
+use crate::tests::test_data::{dated_note, folder, MODIFIED};
+use crate::tools::{
+    folder_storage::{self, FolderStorage},
+    io::{NoteFile, NoteFileItem},
+    merge,
+    search::{SearchQuery, SmartFolder},
+    storage::{Storage, StorageKind},
+};
+use std::{collections::BTreeSet, fs};
+
+fn notebook() -> NoteFile {
+    NoteFile {
+        children: Some(vec![
+            folder(
+                "f",
+                "Home",
+                vec![
+                    dated_note("b", "Shopping", "- [ ] Milk\n- [x] Bread"),
+                    dated_note("a", "Plans / Ideas", "Paint the fence"),
+                    folder("g", "Garden", vec![dated_note("c", ".hidden", "Weeds")]),
+                ],
+            ),
+            folder(
+                "w",
+                "Work",
+                vec![
+                    dated_note("d", "Shopping", "Pens"),
+                    dated_note("e", "shopping", "Ink"),
+                ],
+            ),
+        ]),
+        smart_folders: vec![SmartFolder {
+            title: String::from("Errands"),
+            query: SearchQuery {
+                text: Some(String::from("milk")),
+                ..Default::default()
+            },
+        }],
+    }
+}
+
+#[test]
+fn test_file_name() {
+    let taken = BTreeSet::from([String::from("groceries")]);
+    assert_eq!(folder_storage::file_name("Plans", &taken), "Plans");
+    assert_eq!(
+        folder_storage::file_name("Groceries", &taken),
+        "Groceries (2)"
+    );
+    assert_eq!(folder_storage::file_name("a/b: c?", &taken), "a-b- c-");
+    assert_eq!(folder_storage::file_name(".profile", &taken), "_profile");
+    assert_eq!(folder_storage::file_name("  ... ", &taken), "Untitled");
+    // Nor can it be the note file or sidecar of one
+    assert_eq!(
+        folder_storage::file_name("Groceries.md", &taken),
+        "Groceries.md"
+    );
+    let taken = BTreeSet::from([String::from("plans.md")]);
+    assert_eq!(folder_storage::file_name("Plans", &taken), "Plans (2)");
+}
+
+#[test]
+fn test_names_clash_with_note_files_and_sidecars() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    let note_file = NoteFile {
+        children: Some(vec![folder(
+            "f",
+            "Home",
+            vec![
+                dated_note("a", "Plans", "Paint"),
+                folder("g", "Plans.md", vec![dated_note("b", "Fence", "Wood")]),
+                folder("h", "Plans.json", vec![dated_note("c", "Gate", "Iron")]),
+            ],
+        )]),
+        smart_folders: Vec::new(),
+    };
+    storage.save(&note_file).unwrap();
+    assert!(merge::same_notes(&storage.load().unwrap(), &note_file));
+}
+
+#[test]
+fn test_load_takes_later_modified_time() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    storage.save(&notebook()).unwrap();
+    assert!(merge::same_notes(&storage.load().unwrap(), &notebook()));
+
+    // Edited elsewhere, which leaves the sidecar as it was
+    fs::write(dir.path().join("Home/Shopping.md"), "- [x] Milk").unwrap();
+    let loaded = storage.load().unwrap();
+    let shopping = &loaded.children.as_ref().unwrap()[0]
+        .children
+        .as_ref()
+        .unwrap()[0];
+    assert_eq!(shopping.title, "Shopping");
+    assert!(shopping.modified > Some(MODIFIED));
+}
+
+#[test]
+fn test_round_trip() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    assert!(!storage.exists());
+    storage.save(&notebook()).unwrap();
+    assert!(storage.exists());
+
+    // Plain files that grep and file syncing tools can make sense of
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Home/Shopping.md")).unwrap(),
+        "- [ ] Milk\n- [x] Bread"
+    );
+    assert!(dir.path().join("Home/Plans - Ideas.md").exists());
+    assert!(dir.path().join("Home/Garden/_hidden.md").exists());
+    assert!(dir.path().join("Work/shopping (2).md").exists());
+
+    let loaded = storage.load().unwrap();
+    assert!(merge::same_notes(&loaded, &notebook()));
+    assert_eq!(
+        storage.watched(),
+        vec![
+            dir.path().to_path_buf(),
+            dir.path().join("Home"),
+            dir.path().join("Home/Garden"),
+            dir.path().join("Work"),
+        ]
+    );
+}
+
+#[test]
+fn test_save_removes_what_is_gone() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    storage.save(&notebook()).unwrap();
+    fs::write(dir.path().join("Work/README.txt"), "Not a note").unwrap();
+    fs::write(
+        dir.path().join("Home/Draft.md"),
+        "Not saved by the notebook yet",
+    )
+    .unwrap();
+
+    let mut note_file = notebook();
+    let children = note_file.children.as_mut().unwrap();
+    children[0].children.as_mut().unwrap()[0].title = String::from("Groceries");
+    children[0].children.as_mut().unwrap().remove(2);
+    children[1].children = Some(Vec::new());
+    storage.save(&note_file).unwrap();
+
+    assert!(dir.path().join("Home/Groceries.md").exists());
+    assert!(!dir.path().join("Home/Shopping.md").exists());
+    assert!(!dir.path().join("Home/Shopping.json").exists());
+    assert!(!dir.path().join("Home/Garden").exists());
+    assert!(!dir.path().join("Work/Shopping.md").exists());
+    assert!(dir.path().join("Work/README.txt").exists());
+    assert!(dir.path().join("Home/Draft.md").exists());
+
+    fs::remove_file(dir.path().join("Home/Draft.md")).unwrap();
+    assert!(merge::same_notes(&storage.load().unwrap(), &note_file));
+}
+
+#[test]
+fn test_save_keeps_files_of_others() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    // A directory that already had other things in it
+    fs::write(dir.path().join("config.json"), r#"{"id": "app"}"#).unwrap();
+    fs::write(dir.path().join("README.md"), "Read me").unwrap();
+    fs::write(dir.path().join("README.json"), r#"{"id": "readme"}"#).unwrap();
+    fs::create_dir_all(dir.path().join("src/module")).unwrap();
+    fs::write(dir.path().join("src.json"), r#"{"id": "src"}"#).unwrap();
+    fs::write(dir.path().join("src/main.md"), "Code").unwrap();
+    fs::write(dir.path().join("src/main.json"), r#"{"id": "main"}"#).unwrap();
+
+    let storage = FolderStorage::new(dir.path());
+    storage.save(&notebook()).unwrap();
+    storage.save(&notebook()).unwrap();
+
+    for path in [
+        "config.json",
+        "README.md",
+        "README.json",
+        "src.json",
+        "src/main.md",
+        "src/main.json",
+        "src/module",
+    ] {
+        assert!(dir.path().join(path).exists(), "{} was removed", path);
+    }
+}
+
+#[test]
+fn test_empty_folder() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    // The tree keeps an empty row in a folder without notes
+    let placeholder = folder("", "", Vec::new());
+    let note_file = NoteFile {
+        children: Some(vec![folder("f", "Home", vec![placeholder])]),
+        smart_folders: Vec::new(),
+    };
+    storage.save(&note_file).unwrap();
+
+    assert!(dir.path().join("Home").is_dir());
+    assert!(!dir.path().join("Home/Untitled").exists());
+    assert!(!dir.path().join("Home/Untitled.json").exists());
+    let loaded = storage.load().unwrap();
+    let home = &loaded.children.as_ref().unwrap()[0];
+    assert!(home.children.as_ref().unwrap().is_empty());
+}
+
+#[test]
+fn test_load_notes_added_from_outside() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    storage.save(&notebook()).unwrap();
+    fs::write(dir.path().join("Work/Agenda.md"), "- Budget").unwrap();
+
+    let loaded = storage.load().unwrap();
+    let work = &loaded.children.as_ref().unwrap()[1];
+    let agenda = work.children.as_ref().unwrap().last().unwrap();
+    assert_eq!(agenda.title, "Agenda");
+    assert_eq!(agenda.body.as_deref(), Some("- Budget"));
+    assert!(!agenda.id.is_empty());
+    assert!(agenda.modified.is_some());
+}
+
+#[test]
+fn test_may_have_changed() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = FolderStorage::new(dir.path());
+    assert!(storage.may_have_changed());
+    storage.save(&notebook()).unwrap();
+    assert!(!storage.may_have_changed());
+
+    // Changed by another program
+    fs::write(dir.path().join("Work/Agenda.md"), "- Budget").unwrap();
+    assert!(storage.may_have_changed());
+    storage.load().unwrap();
+    assert!(!storage.may_have_changed());
+    fs::remove_file(dir.path().join("Work/Agenda.md")).unwrap();
+    assert!(storage.may_have_changed());
+}
+
+#[test]
+fn test_switch_storage() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let json_path = dir.path().join("notes.json").to_string_lossy().to_string();
+    let folder = dir.path().join("notes");
+
+    let database = dir.path().join("notes.sqlite");
+
+    let json = StorageKind::Json.open(&json_path, &folder, &database);
+    json.save(&notebook()).unwrap();
+    let files = StorageKind::Folder.open(&json_path, &folder, &database);
+    files.save(&json.load().unwrap()).unwrap();
+    assert!(merge::same_notes(&files.load().unwrap(), &notebook()));
+    let sqlite = StorageKind::Sqlite.open(&json_path, &folder, &database);
+    sqlite.save(&files.load().unwrap()).unwrap();
+    assert!(merge::same_notes(&sqlite.load().unwrap(), &notebook()));
+}
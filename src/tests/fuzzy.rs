@@ -0,0 +1,56 @@
+use crate::tools::fuzzy::{self, Candidate};
+
+fn candidate(title: &str, location: &str, modified: Option<i64>) -> Candidate {
+    Candidate {
+        note_path: vec![0],
+        title: String::from(title),
+        location: String::from(location),
+        modified,
+    }
+}
+
+#[test]
+fn test_score() {
+    assert_eq!(fuzzy::score("xyz", "Meeting notes"), None);
+    assert!(fuzzy::score("mn", "Meeting notes").is_some());
+
+    // Word starts and runs beat scattered letters
+    assert!(fuzzy::score("mn", "Meeting notes") > fuzzy::score("mn", "Summon"));
+    assert!(fuzzy::score("note", "Notes") > fuzzy::score("note", "No time left"));
+    assert!(fuzzy::score("notes", "notes") > fuzzy::score("notes", "Meeting notes"));
+}
+
+#[test]
+fn test_rank() {
+    let day = 24 * 60 * 60;
+    let now = 100 * day;
+    let candidates = vec![
+        candidate("Groceries", "Home", Some(now - 90 * day)),
+        candidate("Garden plan", "Home", Some(now - day)),
+        candidate("Game ideas", "Work / Fun", None),
+        candidate("Budget", "Home", Some(now)),
+    ];
+
+    let titles = |ranked: Vec<&Candidate>| -> Vec<String> {
+        ranked.into_iter().map(|c| c.title.clone()).collect()
+    };
+
+    assert_eq!(
+        titles(fuzzy::rank("gro", &candidates, now, 10)),
+        vec!["Groceries"]
+    );
+    // The folders are searched too
+    assert_eq!(
+        titles(fuzzy::rank("work game", &candidates, now, 10)),
+        vec!["Game ideas"]
+    );
+    // Equally good matches are ordered by recency
+    assert_eq!(
+        titles(fuzzy::rank("g", &candidates, now, 3)),
+        vec!["Garden plan", "Groceries", "Game ideas"]
+    );
+    assert_eq!(
+        titles(fuzzy::rank("", &candidates, now, 2)),
+        vec!["Budget", "Garden plan"]
+    );
+}
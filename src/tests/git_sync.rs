@@ -0,0 +1,214 @@
+use crate::tests::test_data::{self, folder, note};
+use crate::tools::{
+    git_sync::{self, GitRepo},
+    io::{NoteFile, NoteFileItem},
+};
+use std::{path::Path, process::Command};
+
+fn notebook(notes: Vec<NoteFileItem>) -> NoteFile {
+    test_data::notebook(vec![folder("root", "My Notes", notes)])
+}
+
+fn save(dir: &Path, note_file: &NoteFile) {
+    note_file
+        .save(&dir.join("notes.json").to_string_lossy())
+        .expect("Failed to save notebook");
+}
+
+fn bodies(note_file: &NoteFile) -> Vec<String> {
+    note_file
+        .notes()
+        .iter()
+        .map(|note| {
+            format!(
+                "{}: {}",
+                note.item.title,
+                note.item.body.as_deref().unwrap_or("")
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_commit_message() {
+    let before = notebook(vec![note("a", "Groceries", "Milk"), note("b", "Plans", "")]);
+    assert_eq!(
+        git_sync::commit_message(
+            Some(&before),
+            &notebook(vec![
+                note("a", "Groceries", "Milk\nTea"),
+                note("b", "Plans", "")
+            ])
+        ),
+        "Edit “Groceries”"
+    );
+    assert_eq!(
+        git_sync::commit_message(
+            Some(&before),
+            &notebook(vec![note("a", "Groceries", "Milk")])
+        ),
+        "Delete “Plans”"
+    );
+    assert_eq!(
+        git_sync::commit_message(
+            Some(&before),
+            &notebook(vec![note("a", "Shopping", "Milk"), note("c", "Ideas", "")])
+        ),
+        "Update 3 notes\n\nRename “Groceries” to “Shopping”\nAdd “Ideas”\nDelete “Plans”"
+    );
+}
+
+#[test]
+fn test_pull_and_push_through_bare_repository() {
+    let remote = tempfile::tempdir().expect("Failed to create temporary directory");
+    let status = Command::new("git")
+        .args(["init", "-q", "--bare"])
+        .arg(remote.path())
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success());
+    let remote_url = remote.path().to_string_lossy().to_string();
+
+    let dir_a = tempfile::tempdir().expect("Failed to create temporary directory");
+    let repo_a = GitRepo::new(dir_a.path(), "notes.json", "attachments");
+    repo_a.init(&remote_url).unwrap();
+    save(
+        dir_a.path(),
+        &notebook(vec![
+            note("a", "Groceries", "Milk"),
+            note("b", "Plans", "Paint"),
+        ]),
+    );
+    assert!(repo_a.commit("Add notes").unwrap());
+    assert!(!repo_a.commit("Nothing").unwrap());
+    repo_a.push().unwrap();
+
+    let dir_b = tempfile::tempdir().expect("Failed to create temporary directory");
+    let repo_b = GitRepo::new(dir_b.path(), "notes.json", "attachments");
+    repo_b.init(&remote_url).unwrap();
+    let pulled = repo_b.pull().unwrap().expect("Nothing was pulled");
+    assert_eq!(
+        bodies(&pulled.note_file),
+        vec!["Groceries: Milk", "Plans: Paint"]
+    );
+
+    // Different notes edited on each side merge cleanly
+    save(
+        dir_b.path(),
+        &notebook(vec![
+            note("a", "Groceries", "Milk\nTea"),
+            note("b", "Plans", "Paint"),
+        ]),
+    );
+    repo_b.commit("Edit “Groceries”").unwrap();
+    repo_b.push().unwrap();
+    save(
+        dir_a.path(),
+        &notebook(vec![
+            note("a", "Groceries", "Milk"),
+            note("b", "Plans", "Paint\nSand"),
+        ]),
+    );
+    repo_a.commit("Edit “Plans”").unwrap();
+    let merged = repo_a.pull().unwrap().expect("Nothing was merged");
+    assert!(merged.conflicts.is_empty());
+    assert_eq!(
+        bodies(&merged.note_file),
+        vec!["Groceries: Milk\nTea", "Plans: Paint\nSand"]
+    );
+    assert_eq!(
+        bodies(&NoteFile::load(&dir_a.path().join("notes.json").to_string_lossy()).unwrap()),
+        bodies(&merged.note_file)
+    );
+    repo_a.push().unwrap();
+    assert!(repo_a.pull().unwrap().is_none());
+    save(
+        dir_a.path(),
+        &notebook(vec![
+            note("a", "Groceries", "Milk\nJuice"),
+            note("b", "Plans", "Paint\nSand"),
+        ]),
+    );
+    repo_a.commit("Edit “Groceries”").unwrap();
+    repo_a.push().unwrap();
+
+    // The same note edited on both sides keeps the local version and reports it
+    save(
+        dir_b.path(),
+        &notebook(vec![
+            note("a", "Groceries", "Milk\nCoffee"),
+            note("b", "Plans", "Paint"),
+        ]),
+    );
+    repo_b.commit("Edit “Groceries”").unwrap();
+    let merged = repo_b.pull().unwrap().expect("Nothing was merged");
+    assert_eq!(merged.conflicts.len(), 1);
+    assert_eq!(merged.conflicts[0].remote.1, "Milk\nJuice");
+    assert_eq!(
+        bodies(&merged.note_file),
+        vec!["Groceries: Milk\nCoffee", "Plans: Paint\nSand"]
+    );
+}
+
+#[test]
+fn test_pull_keeps_uncommitted_notebook() {
+    let remote = tempfile::tempdir().expect("Failed to create temporary directory");
+    let status = Command::new("git")
+        .args(["init", "-q", "--bare"])
+        .arg(remote.path())
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success());
+    let remote_url = remote.path().to_string_lossy().to_string();
+
+    let dir_a = tempfile::tempdir().expect("Failed to create temporary directory");
+    let repo_a = GitRepo::new(dir_a.path(), "notes.json", "attachments");
+    repo_a.init(&remote_url).unwrap();
+    save(
+        dir_a.path(),
+        &notebook(vec![note("a", "Groceries", "Milk")]),
+    );
+    repo_a.commit("Add notes").unwrap();
+    repo_a.push().unwrap();
+
+    // The first commit never happened here, as when it failed while setting up sync
+    let dir_b = tempfile::tempdir().expect("Failed to create temporary directory");
+    let repo_b = GitRepo::new(dir_b.path(), "notes.json", "attachments");
+    repo_b.init(&remote_url).unwrap();
+    save(dir_b.path(), &notebook(vec![note("b", "Plans", "Paint")]));
+    let merged = repo_b.pull().unwrap().expect("Nothing was merged");
+    assert_eq!(
+        bodies(&merged.note_file),
+        vec!["Groceries: Milk", "Plans: Paint"]
+    );
+    assert_eq!(
+        bodies(&NoteFile::load(&dir_b.path().join("notes.json").to_string_lossy()).unwrap()),
+        bodies(&merged.note_file)
+    );
+}
+
+#[test]
+fn test_commit_notes_describes_saved_notebook() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let repo = GitRepo::new(dir.path(), "notes.json", "attachments");
+    repo.init("https://example.com/notes.git").unwrap();
+    save(dir.path(), &notebook(vec![note("a", "Groceries", "Milk")]));
+    assert!(repo.commit_notes().unwrap());
+    assert!(!repo.commit_notes().unwrap());
+
+    save(
+        dir.path(),
+        &notebook(vec![note("a", "Groceries", "Milk\nTea")]),
+    );
+    assert!(repo.commit_notes().unwrap());
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir.path())
+        .args(["log", "-1", "--format=%s"])
+        .output()
+        .expect("Failed to run git");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "Edit “Groceries”"
+    );
+}
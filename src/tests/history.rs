@@ -0,0 +1,56 @@
+use crate::tools::{
+    history::{History, MAX_SNAPSHOTS, SNAPSHOT_INTERVAL},
+    io::{NoteFile, NoteFileItem},
+};
+
+#[test]
+fn test_record() {
+    let mut history = History::default();
+    assert!(history.record("a", "Groceries", "Milk", 1_000));
+    // Nothing changed
+    assert!(!history.record("a", "Groceries", "Milk", 5_000));
+    // Too soon after the last one, which gets replaced
+    assert!(history.record("a", "Groceries", "Milk\nBread", 1_060));
+    assert_eq!(history.snapshots("a").len(), 1);
+    assert_eq!(history.snapshots("a")[0].body, "Milk\nBread");
+
+    assert!(history.record("a", "Shopping", "Milk\nBread", 1_060 + SNAPSHOT_INTERVAL));
+    let titles: Vec<&str> = history
+        .snapshots("a")
+        .iter()
+        .map(|snapshot| snapshot.title.as_str())
+        .collect();
+    assert_eq!(titles, vec!["Groceries", "Shopping"]);
+    assert!(history.snapshots("b").is_empty());
+
+    for index in 0..MAX_SNAPSHOTS as i64 {
+        history.record(
+            "a",
+            "Shopping",
+            &index.to_string(),
+            10_000 + index * SNAPSHOT_INTERVAL,
+        );
+    }
+    assert_eq!(history.snapshots("a").len(), MAX_SNAPSHOTS);
+    assert_eq!(history.snapshots("a")[0].body, "0");
+}
+
+#[test]
+fn test_record_notes() {
+    let note_file = NoteFile {
+        children: Some(vec![NoteFileItem {
+            title: String::from("Ideas"),
+            body: Some(String::from("Garden")),
+            children: None,
+            is_folder: false,
+            modified: Some(2_000),
+            id: String::from("ideas"),
+        }]),
+        smart_folders: Vec::new(),
+    };
+
+    let mut history = History::default();
+    assert!(history.record_notes(&note_file, 9_000));
+    assert!(!history.record_notes(&note_file, 9_500));
+    assert_eq!(history.snapshots("ideas")[0].time, 2_000);
+}
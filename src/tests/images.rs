@@ -0,0 +1,37 @@
+use crate::tools::images::{self, ImageSize};
+
+#[test]
+fn test_scaled_size() {
+    assert_eq!(
+        images::scaled_size(ImageSize::FitWidth, 2000, 1000, 600),
+        (600, 300)
+    );
+    assert_eq!(
+        images::scaled_size(ImageSize::Small, 1000, 500, 600),
+        (200, 100)
+    );
+    assert_eq!(
+        images::scaled_size(ImageSize::Original, 2000, 1000, 600),
+        (2000, 1000)
+    );
+    // Small images aren't blown up
+    assert_eq!(
+        images::scaled_size(ImageSize::Large, 300, 150, 600),
+        (300, 150)
+    );
+    assert_eq!(images::scaled_size(ImageSize::Medium, 0, 0, 600), (0, 0));
+}
+
+#[test]
+fn test_size_names() {
+    for size in [
+        ImageSize::Small,
+        ImageSize::Medium,
+        ImageSize::Large,
+        ImageSize::FitWidth,
+        ImageSize::Original,
+    ] {
+        assert_eq!(ImageSize::from_name(size.name()), Some(size));
+    }
+    assert_eq!(ImageSize::from_name("huge"), None);
+}
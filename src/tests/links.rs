@@ -0,0 +1,32 @@
+use crate::tools::links;
+
+#[test]
+fn test_find() {
+    let text = "See https://gnome.org/apps. Mail bill@example.com or (file:///tmp/a.txt)";
+    let found = links::find(text);
+    let uris: Vec<&str> = found.iter().map(|link| link.uri.as_str()).collect();
+
+    assert_eq!(
+        uris,
+        vec![
+            "https://gnome.org/apps",
+            "mailto:bill@example.com",
+            "file:///tmp/a.txt"
+        ]
+    );
+    assert_eq!(&text[found[0].range.clone()], "https://gnome.org/apps");
+    assert_eq!(&text[found[1].range.clone()], "bill@example.com");
+}
+
+#[test]
+fn test_uri_for() {
+    assert_eq!(
+        links::uri_for("mailto:bill@example.com").as_deref(),
+        Some("mailto:bill@example.com")
+    );
+    assert_eq!(
+        links::uri_for("bill@example.com").as_deref(),
+        Some("mailto:bill@example.com")
+    );
+    assert_eq!(links::uri_for("not a link"), None);
+}
@@ -0,0 +1,204 @@
+use crate::tests::test_data::{folder, note, notebook};
+use crate::tools::{
+    io::{NoteFile, NoteFileItem},
+    merge::{self, Conflict},
+};
+
+// Titles and bodies in tree order, folders marked with a slash
+fn outline(note_file: &NoteFile) -> Vec<String> {
+    fn walk(items: &[NoteFileItem], depth: usize, outline: &mut Vec<String>) {
+        for item in items {
+            if item.is_folder {
+                outline.push(format!("{}{}/", "  ".repeat(depth), item.title));
+                walk(item.children.as_deref().unwrap_or(&[]), depth + 1, outline);
+            } else {
+                outline.push(format!(
+                    "{}{}: {}",
+                    "  ".repeat(depth),
+                    item.title,
+                    item.body.as_deref().unwrap_or("")
+                ));
+            }
+        }
+    }
+    let mut lines = Vec::new();
+    walk(note_file.children.as_deref().unwrap_or(&[]), 0, &mut lines);
+    lines
+}
+
+fn base() -> NoteFile {
+    notebook(vec![folder(
+        "f",
+        "Home",
+        vec![note("a", "Groceries", "Milk"), note("b", "Plans", "Paint")],
+    )])
+}
+
+#[test]
+fn test_merge_changes_from_both_sides() {
+    let local = notebook(vec![folder(
+        "f",
+        "Home",
+        vec![
+            note("a", "Groceries", "Milk\nBread"),
+            note("b", "Plans", "Paint"),
+            note("c", "Ideas", "Garden"),
+        ],
+    )]);
+    let remote = notebook(vec![
+        folder("f", "Home", vec![note("a", "Groceries", "Milk")]),
+        folder("g", "Work", vec![note("b", "Weekend plans", "Paint")]),
+    ]);
+
+    let merged = merge::merge(Some(&base()), &local, &remote);
+    assert!(merged.conflicts.is_empty());
+    assert_eq!(
+        outline(&merged.note_file),
+        vec![
+            "Home/",
+            "  Groceries: Milk\nBread",
+            "  Ideas: Garden",
+            "Work/",
+            "  Weekend plans: Paint",
+        ]
+    );
+}
+
+#[test]
+fn test_merge_deletions() {
+    // Deleted remotely and left alone here, deleted here but edited remotely
+    let local = notebook(vec![folder(
+        "f",
+        "Home",
+        vec![note("a", "Groceries", "Milk")],
+    )]);
+    let remote = notebook(vec![folder(
+        "f",
+        "Home",
+        vec![note("b", "Plans", "Paint\nSand")],
+    )]);
+
+    let merged = merge::merge(Some(&base()), &local, &remote);
+    assert_eq!(
+        outline(&merged.note_file),
+        vec!["Home/", "  Plans: Paint\nSand"]
+    );
+}
+
+#[test]
+fn test_merge_conflict_keeps_local() {
+    let local = notebook(vec![folder(
+        "f",
+        "Home",
+        vec![
+            note("a", "Groceries", "Milk\nEggs"),
+            note("b", "Plans", "Paint"),
+        ],
+    )]);
+    let remote = notebook(vec![folder(
+        "f",
+        "Home",
+        vec![
+            note("a", "Groceries", "Milk\nTea"),
+            note("b", "Plans", "Paint"),
+        ],
+    )]);
+
+    let merged = merge::merge(Some(&base()), &local, &remote);
+    assert_eq!(
+        merged.conflicts,
+        vec![Conflict {
+            id: String::from("a"),
+            local: (String::from("Groceries"), String::from("Milk\nEggs")),
+            remote: (String::from("Groceries"), String::from("Milk\nTea")),
+            kept_remote: false,
+        }]
+    );
+    assert_eq!(outline(&merged.note_file)[1], "  Groceries: Milk\nEggs");
+}
+
+#[test]
+fn test_merge_conflict_keeps_latest() {
+    let edited = |body: &str, modified: i64| {
+        let mut item = note("a", "Groceries", body);
+        item.modified = Some(modified);
+        notebook(vec![folder("f", "Home", vec![item])])
+    };
+
+    let merged = merge::merge(
+        Some(&base()),
+        &edited("Milk\nEggs", 100),
+        &edited("Milk\nTea", 200),
+    );
+    assert!(merged.conflicts[0].kept_remote);
+    assert_eq!(outline(&merged.note_file)[1], "  Groceries: Milk\nTea");
+    assert!(merge::same_notes(
+        &merged.note_file,
+        &edited("Milk\nTea", 200)
+    ));
+
+    // Without a common version every difference is a conflict
+    let merged = merge::merge(None, &edited("Milk\nEggs", 300), &edited("Milk\nTea", 200));
+    assert!(!merged.conflicts[0].kept_remote);
+    assert_eq!(outline(&merged.note_file)[1], "  Groceries: Milk\nEggs");
+}
+
+#[test]
+fn test_merge_moves_into_each_other() {
+    let base = notebook(vec![folder("x", "X", vec![]), folder("y", "Y", vec![])]);
+    let local = notebook(vec![folder("y", "Y", vec![folder("x", "X", vec![])])]);
+    let remote = notebook(vec![folder("x", "X", vec![folder("y", "Y", vec![])])]);
+
+    // Neither folder is lost
+    let merged = merge::merge(Some(&base), &local, &remote);
+    assert_eq!(outline(&merged.note_file).len(), 2);
+}
+
+#[test]
+fn test_merge_empty_folders() {
+    // The tree keeps an empty row in each folder without notes
+    let empty_folder = |id: &str, title: &str| folder(id, title, vec![folder("", "", Vec::new())]);
+    let local = notebook(vec![empty_folder("f", "Home"), empty_folder("w", "Work")]);
+    let remote = notebook(vec![
+        empty_folder("f", "Home"),
+        empty_folder("w", "Work"),
+        empty_folder("g", "Garden"),
+    ]);
+    assert!(merge::entries(&local).keys().all(|id| !id.is_empty()));
+
+    let merged = merge::merge(Some(&local), &local, &remote);
+    assert_eq!(
+        outline(&merged.note_file),
+        vec!["Home/", "Work/", "Garden/"]
+    );
+}
+
+#[test]
+fn test_changes_on_disk() {
+    let known = base();
+    // Our own save, seen by the file monitor
+    assert!(merge::changes_on_disk(Some(&known), &known, &base()).is_none());
+
+    // Plans edited by another program, Groceries edited both here and there
+    let mut current = base();
+    current.children.as_mut().unwrap()[0]
+        .children
+        .as_mut()
+        .unwrap()[0]
+        .body = Some(String::from("Milk\nTea"));
+    let mut on_disk = base();
+    let children = on_disk.children.as_mut().unwrap()[0]
+        .children
+        .as_mut()
+        .unwrap();
+    children[0].body = Some(String::from("Milk\nBread"));
+    children[1].body = Some(String::from("Paint\nSand"));
+
+    let merged = merge::changes_on_disk(Some(&known), &current, &on_disk).unwrap();
+    assert_eq!(
+        outline(&merged.note_file),
+        vec!["Home/", "  Groceries: Milk\nTea", "  Plans: Paint\nSand"]
+    );
+    assert_eq!(merged.conflicts.len(), 1);
+    assert_eq!(merged.conflicts[0].id, "a");
+}
@@ -1,2 +1,52 @@
+#[cfg(test)]
+mod attachments;
+#[cfg(test)]
+mod checklist;
+#[cfg(test)]
+mod clipboard;
+#[cfg(test)]
+mod commands;
+#[cfg(test)]
+mod crypto;
+#[cfg(test)]
+mod diff;
+#[cfg(test)]
+mod due_date;
+#[cfg(test)]
+mod emphasis;
+#[cfg(test)]
+mod find;
+#[cfg(test)]
+mod folder_storage;
+#[cfg(test)]
+mod fuzzy;
+#[cfg(test)]
+mod git_sync;
 mod gnote_tree_view;
+#[cfg(test)]
+mod history;
+#[cfg(test)]
+mod images;
+#[cfg(test)]
+mod links;
+#[cfg(test)]
+mod merge;
+#[cfg(test)]
+mod search;
+#[cfg(test)]
+mod settings;
+#[cfg(test)]
+mod sqlite_storage;
+#[cfg(test)]
+mod storage;
+#[cfg(test)]
+mod tags;
+#[cfg(test)]
+mod task_export;
+#[cfg(test)]
+mod tasks;
 pub mod test_data;
+#[cfg(test)]
+mod webdav_sync;
+#[cfg(test)]
+mod wiki_links;
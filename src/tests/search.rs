@@ -0,0 +1,77 @@
+use crate::tests::test_data::note;
+use crate::tools::{
+    io::{NoteFile, NoteFileItem},
+    search::{self, SearchQuery},
+};
+
+#[test]
+fn test_matches() {
+    let item = NoteFileItem {
+        modified: Some(1_000),
+        ..note("", "Groceries", "☐ Milk\n☑ Bread #shopping")
+    };
+
+    let query = SearchQuery {
+        text: Some(String::from("MILK")),
+        tag: Some(String::from("#shopping")),
+        has_unchecked_tasks: true,
+        ..Default::default()
+    };
+    assert!(query.matches(&item));
+
+    let query = SearchQuery {
+        regex: Some(String::from(r"^☑ Bread")),
+        modified_since: Some(1_000),
+        ..Default::default()
+    };
+    assert!(query.matches(&item));
+
+    let query = SearchQuery {
+        modified_since: Some(1_001),
+        ..Default::default()
+    };
+    assert!(!query.matches(&item));
+    assert!(!query.matches(&note("", "Old", "")));
+
+    let query = SearchQuery {
+        regex: Some(String::from("(unclosed")),
+        ..Default::default()
+    };
+    assert!(!query.matches(&item));
+
+    let query = SearchQuery {
+        has_unchecked_tasks: true,
+        ..Default::default()
+    };
+    assert!(!query.matches(&note("", "Done", "☑ All of it")));
+    assert!(SearchQuery::default().is_empty());
+}
+
+#[test]
+fn test_matching_notes() {
+    let note_file = NoteFile {
+        children: Some(vec![NoteFileItem {
+            title: String::from("Home"),
+            body: None,
+            children: Some(vec![
+                note("", "Plan", "#work ideas"),
+                note("", "Garden", "weeding"),
+                note("", "Review", "#work notes"),
+            ]),
+            is_folder: true,
+            modified: None,
+            id: String::new(),
+        }]),
+        smart_folders: Vec::new(),
+    };
+
+    let query = SearchQuery {
+        tag: Some(String::from("work")),
+        ..Default::default()
+    };
+    let paths: Vec<Vec<i32>> = search::matching_notes(&note_file, &query)
+        .into_iter()
+        .map(|note| note.path)
+        .collect();
+    assert_eq!(paths, vec![vec![0, 0], vec![0, 2]]);
+}
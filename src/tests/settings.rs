@@ -0,0 +1,32 @@
+use crate::tools::settings::Settings;
+use std::fs;
+
+#[test]
+fn test_webdav_password_is_not_saved() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let path = dir
+        .path()
+        .join("settings.json")
+        .to_string_lossy()
+        .to_string();
+    let settings = Settings {
+        webdav_url: Some(String::from("https://example.com/dav/notes")),
+        webdav_username: Some(String::from("ann")),
+        webdav_password: Some(String::from("secret")),
+        ..Default::default()
+    };
+    settings.save(&path).unwrap();
+    assert!(!fs::read_to_string(&path).unwrap().contains("secret"));
+    let loaded = Settings::load(&path).unwrap();
+    assert_eq!(loaded.webdav_username.as_deref(), Some("ann"));
+    assert_eq!(loaded.webdav_password, None);
+
+    // Settings saved before the keyring was used still have it, to move it there
+    fs::write(
+        &path,
+        r#"{"webdav_url": "https://example.com/dav/notes", "webdav_password": "secret"}"#,
+    )
+    .unwrap();
+    let loaded = Settings::load(&path).unwrap();
+    assert_eq!(loaded.webdav_password.as_deref(), Some("secret"));
+}
@@ -0,0 +1,227 @@
+use crate::tests::test_data::{dated_note, folder};
+use crate::tools::{
+    attachments,
+    io::{NoteFile, NoteFileItem},
+    merge,
+    search::{SearchQuery, SmartFolder},
+    sqlite_storage::SqliteStorage,
+    storage::Storage,
+};
+use rusqlite::Connection;
+
+fn notebook() -> NoteFile {
+    NoteFile {
+        children: Some(vec![
+            folder(
+                "f",
+                "Home",
+                vec![
+                    dated_note("a", "Shopping", "- [ ] Milk\n- [x] Bread"),
+                    dated_note(
+                        "b",
+                        "Garden",
+                        &format!(
+                            "![Fence](attachment:{})",
+                            attachments::content_name(b"fence", "png")
+                        ),
+                    ),
+                ],
+            ),
+            folder("w", "Work", vec![dated_note("c", "Agenda", "Budget")]),
+        ]),
+        smart_folders: vec![SmartFolder {
+            title: String::from("Errands"),
+            query: SearchQuery {
+                text: Some(String::from("milk")),
+                ..Default::default()
+            },
+        }],
+    }
+}
+
+fn ids(connection: &Connection, sql: &str) -> Vec<String> {
+    let mut statement = connection.prepare(sql).unwrap();
+    let rows = statement.query_map([], |row| row.get(0)).unwrap();
+    rows.map(Result::unwrap).collect()
+}
+
+#[test]
+fn test_round_trip() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = SqliteStorage::new(dir.path().join("notes.sqlite"));
+    assert!(!storage.exists());
+    storage.save(&notebook()).unwrap();
+    assert!(storage.exists());
+    assert!(merge::same_notes(&storage.load().unwrap(), &notebook()));
+
+    let connection = Connection::open(dir.path().join("notes.sqlite")).unwrap();
+    assert_eq!(
+        ids(&connection, "SELECT note_id FROM attachments"),
+        vec!["b"]
+    );
+}
+
+#[test]
+fn test_save_writes_only_changes() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = SqliteStorage::new(dir.path().join("notes.sqlite"));
+    storage.save(&notebook()).unwrap();
+
+    // Every note written from here on is logged
+    let connection = Connection::open(dir.path().join("notes.sqlite")).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE written (id TEXT);
+            CREATE TRIGGER note_written AFTER UPDATE ON notes
+                BEGIN INSERT INTO written VALUES (new.id); END;
+            CREATE TRIGGER note_added AFTER INSERT ON notes
+                BEGIN INSERT INTO written VALUES (new.id); END;",
+        )
+        .unwrap();
+
+    let mut note_file = notebook();
+    let children = note_file.children.as_mut().unwrap();
+    children[0].children.as_mut().unwrap()[0].body = Some(String::from("- [x] Milk"));
+    // Moved into Work, and Agenda removed
+    let garden = children[0].children.as_mut().unwrap().remove(1);
+    children[1].children = Some(vec![garden]);
+    storage.save(&note_file).unwrap();
+
+    assert_eq!(ids(&connection, "SELECT id FROM written"), vec!["a"]);
+    assert_eq!(
+        ids(&connection, "SELECT id FROM notes ORDER BY id"),
+        vec!["a", "b"]
+    );
+    assert!(merge::same_notes(&storage.load().unwrap(), &note_file));
+}
+
+#[test]
+fn test_empty_folders() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = SqliteStorage::new(dir.path().join("notes.sqlite"));
+    // The tree keeps an empty row in each folder without notes
+    let empty_folder = |id: &str, title: &str| folder(id, title, vec![folder("", "", Vec::new())]);
+    let note_file = NoteFile {
+        children: Some(vec![empty_folder("f", "Home"), empty_folder("w", "Work")]),
+        smart_folders: Vec::new(),
+    };
+    storage.save(&note_file).unwrap();
+
+    let connection = Connection::open(dir.path().join("notes.sqlite")).unwrap();
+    assert_eq!(
+        ids(&connection, "SELECT id FROM tree ORDER BY id"),
+        vec!["f", "w"]
+    );
+    let loaded = storage.load().unwrap();
+    assert!(loaded
+        .children
+        .unwrap()
+        .iter()
+        .all(|item| item.children.as_ref().is_some_and(Vec::is_empty)));
+}
+
+#[test]
+fn test_save_notes() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = SqliteStorage::new(dir.path().join("notes.sqlite"));
+    storage.save(&notebook()).unwrap();
+
+    let connection = Connection::open(dir.path().join("notes.sqlite")).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE written (id TEXT);
+            CREATE TRIGGER note_written AFTER UPDATE ON notes
+                BEGIN INSERT INTO written VALUES (new.id); END;",
+        )
+        .unwrap();
+
+    let mut note_file = notebook();
+    let shopping = &mut note_file.children.as_mut().unwrap()[0]
+        .children
+        .as_mut()
+        .unwrap()[0];
+    shopping.title = String::from("Groceries");
+    shopping.body = Some(String::from("- [x] Milk"));
+    assert!(storage.save_notes(std::slice::from_ref(shopping)).unwrap());
+    assert_eq!(ids(&connection, "SELECT id FROM written"), vec!["a"]);
+    assert!(merge::same_notes(&storage.load().unwrap(), &note_file));
+
+    // Notes the database doesn't have yet take a whole save
+    assert!(!storage.save_notes(&[dated_note("n", "New", "")]).unwrap());
+    assert!(merge::same_notes(&storage.load().unwrap(), &note_file));
+}
+
+#[test]
+fn test_may_have_changed() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = SqliteStorage::new(dir.path().join("notes.sqlite"));
+    assert!(storage.may_have_changed());
+    storage.save(&notebook()).unwrap();
+    assert!(!storage.may_have_changed());
+    let mut note_file = notebook();
+    note_file.smart_folders.clear();
+    storage.save(&note_file).unwrap();
+    assert!(!storage.may_have_changed());
+
+    // Changed by another program
+    let connection = Connection::open(dir.path().join("notes.sqlite")).unwrap();
+    connection
+        .execute("UPDATE tree SET title = 'Chores' WHERE id = 'f'", [])
+        .unwrap();
+    assert!(storage.may_have_changed());
+    storage.load().unwrap();
+    assert!(!storage.may_have_changed());
+}
+
+#[test]
+fn test_folders_in_a_circle_move_to_top_level() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let storage = SqliteStorage::new(dir.path().join("notes.sqlite"));
+    storage.save(&notebook()).unwrap();
+    let connection = Connection::open(dir.path().join("notes.sqlite")).unwrap();
+    connection
+        .execute_batch(
+            "UPDATE tree SET parent_id = 'w' WHERE id = 'f';
+            UPDATE tree SET parent_id = 'f' WHERE id = 'w';",
+        )
+        .unwrap();
+
+    let loaded = storage.load().unwrap();
+    let top_level: Vec<&str> = loaded
+        .children
+        .iter()
+        .flatten()
+        .map(|item| item.id.as_str())
+        .collect();
+    assert_eq!(top_level, vec!["f"]);
+    let mut note_ids: Vec<String> = loaded
+        .notes()
+        .into_iter()
+        .map(|note| note.item.id.clone())
+        .collect();
+    note_ids.sort();
+    assert_eq!(note_ids, vec!["a", "b", "c"]);
+}
+
+#[test]
+fn test_full_text_index_is_dropped() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let path = dir.path().join("notes.sqlite");
+    SqliteStorage::new(&path).save(&notebook()).unwrap();
+    // As the first databases were made
+    let connection = Connection::open(&path).unwrap();
+    connection
+        .execute_batch(
+            "CREATE VIRTUAL TABLE notes_fts USING fts5 (id UNINDEXED, title, body);
+            PRAGMA user_version = 1;",
+        )
+        .unwrap();
+
+    let storage = SqliteStorage::new(&path);
+    assert!(merge::same_notes(&storage.load().unwrap(), &notebook()));
+    assert!(ids(
+        &connection,
+        "SELECT name FROM sqlite_master WHERE name = 'notes_fts'"
+    )
+    .is_empty());
+}
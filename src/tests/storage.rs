@@ -0,0 +1,29 @@
+use crate::tests::test_data;
+use crate::tools::{
+    io::NoteFile,
+    storage::{JsonStorage, Storage},
+};
+use std::fs;
+
+#[test]
+fn test_json_may_have_changed() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let notes_path = dir.path().join("notes.json").to_string_lossy().to_string();
+    let storage = JsonStorage::new(&notes_path);
+    assert!(storage.may_have_changed());
+    storage.save(&test_data::get()).unwrap();
+    assert!(!storage.may_have_changed());
+
+    // Changed by another program
+    JsonStorage::new(&notes_path)
+        .save(&NoteFile {
+            children: Some(Vec::new()),
+            smart_folders: Vec::new(),
+        })
+        .unwrap();
+    assert!(storage.may_have_changed());
+    storage.load().unwrap();
+    assert!(!storage.may_have_changed());
+    fs::remove_file(&notes_path).unwrap();
+    assert!(storage.may_have_changed());
+}
@@ -0,0 +1,55 @@
+use crate::tests::test_data::note;
+use crate::tools::{
+    io::{NoteFile, NoteFileItem},
+    tags,
+};
+
+#[test]
+fn test_find() {
+    let text = "#work plan for #home/garden, not #1 or https://a.org/#frag";
+    let found = tags::find(text);
+    let names: Vec<&str> = found.iter().map(|tag| tag.name.as_str()).collect();
+
+    assert_eq!(names, vec!["work", "home/garden"]);
+    assert_eq!(&text[found[1].range.clone()], "#home/garden");
+    assert!(tags::has_all(text, &[String::from("work")]));
+    assert!(!tags::has_all(
+        text,
+        &[String::from("work"), String::from("urgent")]
+    ));
+}
+
+#[test]
+fn test_index() {
+    let note_file = NoteFile {
+        children: Some(vec![
+            note("", "A", "#work #urgent #work"),
+            note("", "B", "#work"),
+            note("", "C", "untagged"),
+        ]),
+        smart_folders: Vec::new(),
+    };
+
+    let index = tags::index(&note_file);
+    assert_eq!(index.keys().collect::<Vec<_>>(), vec!["urgent", "work"]);
+    assert_eq!(index["work"], vec![vec![0], vec![1]]);
+}
+
+#[test]
+fn test_rename() {
+    assert_eq!(
+        tags::rename("#todo then #todos and #todo", "todo", "tasks").as_deref(),
+        Some("#tasks then #todos and #tasks")
+    );
+    assert_eq!(tags::rename("#other", "todo", "tasks"), None);
+}
+
+#[test]
+fn test_is_valid_name() {
+    assert!(tags::is_valid_name("work"));
+    assert!(tags::is_valid_name("work/urgent"));
+    assert!(!tags::is_valid_name("foo bar"));
+    assert!(!tags::is_valid_name("foo,bar"));
+    assert!(!tags::is_valid_name("1st"));
+    assert!(!tags::is_valid_name(""));
+}
@@ -0,0 +1,93 @@
+use crate::tools::{
+    due_date::DueDate,
+    io::{NoteFile, NoteFileItem},
+    task_export, tasks,
+};
+
+fn note_file() -> NoteFile {
+    NoteFile {
+        children: Some(vec![NoteFileItem {
+            title: String::from("Work"),
+            body: None,
+            children: Some(vec![NoteFileItem {
+                title: String::from("Release plan"),
+                body: Some(String::from(
+                    "☐ Tag v1.0 due:2026-10-21\n☑ Write changelog, notes",
+                )),
+                children: None,
+                is_folder: false,
+                modified: None,
+                id: String::from("release"),
+            }]),
+            is_folder: true,
+            modified: None,
+            id: String::new(),
+        }]),
+        smart_folders: Vec::new(),
+    }
+}
+
+#[test]
+fn test_to_todo_txt() {
+    let tasks = tasks::collect(&note_file());
+    assert_eq!(
+        task_export::to_todo_txt(&tasks),
+        "Tag v1.0 +Work @Release_plan due:2026-10-21\nx Write changelog, notes +Work @Release_plan\n"
+    );
+}
+
+#[test]
+fn test_to_ical() {
+    let tasks = tasks::collect(&note_file());
+    let ical = task_export::to_ical(&tasks, &DueDate::new(2026, 10, 19).with_time(8, 0));
+
+    assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ical.ends_with("END:VCALENDAR\r\n"));
+    assert!(ical.contains("SUMMARY:Tag v1.0\r\nDESCRIPTION:Work › Release plan\r\n"));
+    assert!(ical.contains("DUE;VALUE=DATE:20261021\r\nSTATUS:NEEDS-ACTION\r\n"));
+    assert!(ical.contains("SUMMARY:Write changelog\\, notes\r\n"));
+    assert!(ical.contains("STATUS:COMPLETED\r\n"));
+    assert!(ical.contains("DTSTAMP:20261019T080000Z\r\n"));
+}
+
+#[test]
+fn test_ical_uids_are_stable() {
+    let mut note_file = note_file();
+    let uids = |note_file: &NoteFile| -> Vec<String> {
+        task_export::to_ical(&tasks::collect(note_file), &DueDate::new(2026, 10, 19))
+            .lines()
+            .filter(|line| line.starts_with("UID:"))
+            .map(String::from)
+            .collect()
+    };
+    let before = uids(&note_file);
+
+    // Lines added above, the due date moved and the note moved to the top level
+    let mut folder = note_file.children.take().unwrap().remove(0);
+    let mut note = folder.children.take().unwrap().remove(0);
+    note.body = Some(String::from(
+        "Release\n☐ Tag v1.0 due:2026-10-22\n☑ Write changelog, notes\n☐ Tag v1.0",
+    ));
+    note_file.children = Some(vec![note]);
+    let after = uids(&note_file);
+
+    assert_eq!(before.len(), 2);
+    assert_eq!(after[..2], before[..]);
+    assert!(after[2].starts_with("UID:gnote-release-"));
+    assert!(!before.contains(&after[2]));
+}
+
+#[test]
+fn test_from_todo_txt() {
+    let todo_txt =
+        "(A) 2026-10-01 Call mom +Family\n\nx 2026-10-02 2026-10-01 Pay bills due:2026-10-05\n";
+    assert_eq!(
+        task_export::from_todo_txt(todo_txt),
+        "☐ Call mom +Family\n☑ Pay bills due:2026-10-05"
+    );
+    // Digits of other scripts aren't dates
+    assert_eq!(
+        task_export::from_todo_txt("٢٠٢٦-١٠-٠١ Call mom"),
+        "☐ ٢٠٢٦-١٠-٠١ Call mom"
+    );
+}
@@ -0,0 +1,62 @@
+use crate::tests::test_data::note;
+use crate::tools::{
+    io::{NoteFile, NoteFileItem},
+    tasks,
+};
+
+#[test]
+fn test_open_tasks() {
+    let note_file = NoteFile {
+        children: Some(vec![NoteFileItem {
+            title: String::from("Home"),
+            body: None,
+            children: Some(vec![
+                note("", "Plain", "No tasks here"),
+                note("", "Chores", "Today\n☑ Dishes\n  ☐ Laundry"),
+            ]),
+            is_folder: true,
+            modified: None,
+            id: String::new(),
+        }]),
+        smart_folders: Vec::new(),
+    };
+
+    let open = tasks::open_tasks(&note_file);
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].text, "Laundry");
+    assert_eq!(open[0].note_path, vec![0, 1]);
+    assert_eq!(open[0].line, 2);
+    assert_eq!(open[0].indent_level, 1);
+    assert_eq!(open[0].location(), "Home › Chores");
+}
+
+#[test]
+fn test_toggle_line() {
+    let body = "Today\n☑ Dishes\n☐ Laundry\n";
+
+    assert_eq!(
+        tasks::toggle_line(body, 2).as_deref(),
+        Some("Today\n☑ Dishes\n☑ Laundry\n")
+    );
+    assert_eq!(tasks::toggle_line(body, 0), None);
+    assert_eq!(tasks::toggle_line(body, 9), None);
+}
+
+#[test]
+fn test_upcoming() {
+    let note_file = NoteFile {
+        children: Some(vec![note(
+            "",
+            "Bills",
+            "☐ Rent due:2026-11-01\n☐ Phone\n☑ Gas due:2026-10-01\n☐ Water due:2026-10-20T08:30",
+        )]),
+        smart_folders: Vec::new(),
+    };
+
+    let upcoming = tasks::upcoming(&note_file);
+    let texts: Vec<&str> = upcoming.iter().map(|task| task.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        vec!["Water due:2026-10-20T08:30", "Rent due:2026-11-01"]
+    );
+}
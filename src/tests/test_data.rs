@@ -11,6 +11,8 @@ pub fn get() -> NoteFile {
                     body: Some(String::from("BODY: note 1.1")),
                     children: None,
                     is_folder: false,
+                    modified: None,
+                    id: String::new(),
                 },
                 NoteFileItem {
                     title: String::from("TITLE: Folder 2"),
@@ -21,6 +23,8 @@ pub fn get() -> NoteFile {
                             body: Some(String::from("BODY: note 2.2")),
                             children: None,
                             is_folder: false,
+                            modified: None,
+                            id: String::new(),
                         },
                         NoteFileItem {
                             title: String::from("TITLE: Folder 3"),
@@ -31,23 +35,78 @@ pub fn get() -> NoteFile {
                                     body: Some(String::from("BODY: note 3.3")),
                                     children: None,
                                     is_folder: false,
+                                    modified: None,
+                                    id: String::new(),
                                 },
                                 NoteFileItem {
                                     title: String::from("TITLE: Note 4"),
                                     body: Some(String::from("BODY: note 3.4")),
                                     children: None,
                                     is_folder: false,
+                                    modified: None,
+                                    id: String::new(),
                                 },
                             ]),
                             is_folder: true,
+                            modified: None,
+                            id: String::new(),
                         },
                     ]),
                     is_folder: true,
+                    modified: None,
+                    id: String::new(),
                 },
             ]),
             is_folder: true,
+            modified: None,
+            id: String::new(),
         }]),
+        smart_folders: Vec::new(),
     };
 
     note_file
 }
+
+// When the notes of the fixtures below were last edited, for storages that keep the time
+#[cfg(test)]
+pub const MODIFIED: i64 = 1_700_000_000;
+
+#[cfg(test)]
+pub fn note(id: &str, title: &str, body: &str) -> NoteFileItem {
+    NoteFileItem {
+        title: String::from(title),
+        body: Some(String::from(body)),
+        children: None,
+        is_folder: false,
+        modified: None,
+        id: String::from(id),
+    }
+}
+
+#[cfg(test)]
+pub fn dated_note(id: &str, title: &str, body: &str) -> NoteFileItem {
+    NoteFileItem {
+        modified: Some(MODIFIED),
+        ..note(id, title, body)
+    }
+}
+
+#[cfg(test)]
+pub fn folder(id: &str, title: &str, children: Vec<NoteFileItem>) -> NoteFileItem {
+    NoteFileItem {
+        title: String::from(title),
+        body: Some(String::new()),
+        children: Some(children),
+        is_folder: true,
+        modified: None,
+        id: String::from(id),
+    }
+}
+
+#[cfg(test)]
+pub fn notebook(children: Vec<NoteFileItem>) -> NoteFile {
+    NoteFile {
+        children: Some(children),
+        smart_folders: Vec::new(),
+    }
+}
@@ -0,0 +1,227 @@
+use crate::tests::test_data;
+use crate::tools::{
+    attachments::{AttachmentLink, AttachmentStore},
+    io::{NoteFile, NoteFileItem},
+    webdav_sync::{SyncError, SyncState, WebDav},
+};
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+use tiny_http::{Header, Response, Server};
+
+// Just enough of a WebDAV server for the sync: files kept in memory with a counter for ETags
+struct StandIn {
+    url: String,
+    uploads: Arc<AtomicUsize>,
+}
+
+impl StandIn {
+    fn start() -> StandIn {
+        let server = Server::http("127.0.0.1:0").expect("Failed to start server");
+        let url = format!("http://{}/notes", server.server_addr());
+        let uploads = Arc::new(AtomicUsize::new(0));
+        let upload_count = uploads.clone();
+        thread::spawn(move || {
+            let mut files: HashMap<String, (Vec<u8>, String)> = HashMap::new();
+            let mut next_etag = 0;
+            for mut request in server.incoming_requests() {
+                let header = |name: &'static str| {
+                    request
+                        .headers()
+                        .iter()
+                        .find(|header| header.field.equiv(name))
+                        .map(|header| header.value.to_string())
+                };
+                let if_match = header("If-Match");
+                let if_none_match = header("If-None-Match");
+                let path = request.url().to_string();
+                let existing = files.get(&path).cloned();
+
+                let response = match (request.method().as_str(), existing) {
+                    ("GET", Some((_, etag))) if if_none_match.as_ref() == Some(&etag) => {
+                        Response::empty(304).boxed()
+                    }
+                    ("GET", Some((content, etag))) | ("HEAD", Some((content, etag))) => {
+                        Response::from_data(content)
+                            .with_header(Header::from_bytes("ETag", etag).unwrap())
+                            .boxed()
+                    }
+                    ("PUT", existing) => {
+                        let etag = existing.map(|(_, etag)| etag);
+                        let precondition_failed = match (&if_match, &if_none_match) {
+                            (Some(if_match), _) => etag.as_ref() != Some(if_match),
+                            (None, Some(_)) => etag.is_some(),
+                            (None, None) => false,
+                        };
+                        if precondition_failed {
+                            Response::empty(412).boxed()
+                        } else {
+                            let mut content = Vec::new();
+                            request.as_reader().read_to_end(&mut content).unwrap();
+                            next_etag += 1;
+                            let etag = format!("\"{}\"", next_etag);
+                            files.insert(path, (content, etag.clone()));
+                            upload_count.fetch_add(1, Ordering::SeqCst);
+                            Response::empty(201)
+                                .with_header(Header::from_bytes("ETag", etag).unwrap())
+                                .boxed()
+                        }
+                    }
+                    ("MKCOL", _) => Response::empty(201).boxed(),
+                    _ => Response::empty(404).boxed(),
+                };
+                let _ = request.respond(response);
+            }
+        });
+        StandIn { url, uploads }
+    }
+
+    fn uploads(&self) -> usize {
+        self.uploads.load(Ordering::SeqCst)
+    }
+}
+
+// A machine with its own notebook, attachments and sync state
+struct Machine {
+    _dir: tempfile::TempDir,
+    store: AttachmentStore,
+    note_file: NoteFile,
+    state: SyncState,
+}
+
+impl Machine {
+    fn new(notes: Vec<NoteFileItem>) -> Machine {
+        let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let store = AttachmentStore::new(dir.path().join("attachments"));
+        Machine {
+            _dir: dir,
+            store,
+            note_file: NoteFile {
+                children: Some(notes),
+                smart_folders: Vec::new(),
+            },
+            state: SyncState::default(),
+        }
+    }
+
+    fn sync(&mut self, webdav: &WebDav) -> Result<usize, SyncError> {
+        let synced = webdav.sync(&self.note_file, &self.state, &self.store)?;
+        self.note_file = synced.note_file;
+        self.state = synced.state;
+        Ok(synced.conflicts.len())
+    }
+
+    fn edit(&mut self, id: &str, body: &str, modified: i64) {
+        let item = self
+            .note_file
+            .children
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|item| item.id == id)
+            .unwrap();
+        item.body = Some(body.to_string());
+        item.modified = Some(modified);
+    }
+
+    fn bodies(&self) -> Vec<String> {
+        self.note_file
+            .notes()
+            .iter()
+            .map(|note| note.item.body.clone().unwrap_or_default())
+            .collect()
+    }
+}
+
+fn note(id: &str, body: &str) -> NoteFileItem {
+    test_data::note(id, &id.to_uppercase(), body)
+}
+
+#[test]
+fn test_sync_between_machines() {
+    let server = StandIn::start();
+    let webdav = WebDav::new(&server.url, Some("user"), Some("secret"));
+
+    let mut a = Machine::new(vec![note("a", "Milk"), note("b", "Paint")]);
+    let mut b = Machine::new(Vec::new());
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(b.sync(&webdav), Ok(0));
+    assert_eq!(b.bodies(), vec!["Milk", "Paint"]);
+    assert!(!b.state.has_pending(&b.note_file));
+
+    // Nothing changed on either side, so nothing is uploaded
+    let uploads = server.uploads();
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(server.uploads(), uploads);
+
+    // Different notes edited on each side
+    a.edit("a", "Milk\nTea", 10);
+    b.edit("b", "Paint\nSand", 20);
+    assert!(a.state.has_pending(&a.note_file));
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(b.sync(&webdav), Ok(0));
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(a.bodies(), vec!["Milk\nTea", "Paint\nSand"]);
+    assert_eq!(b.bodies(), a.bodies());
+
+    // The same note edited on both sides keeps the later edit
+    a.edit("a", "Milk\nCoffee", 30);
+    b.edit("a", "Milk\nJuice", 40);
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(b.sync(&webdav), Ok(1));
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(a.bodies()[0], "Milk\nJuice");
+    assert_eq!(b.bodies()[0], "Milk\nJuice");
+}
+
+#[test]
+fn test_sync_offline_keeps_changes_queued() {
+    let server = StandIn::start();
+    let webdav = WebDav::new(&server.url, None, None);
+    let mut a = Machine::new(vec![note("a", "Milk")]);
+    assert_eq!(a.sync(&webdav), Ok(0));
+
+    // Nothing listens on the port of a server that was just closed
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let offline = WebDav::new(&format!("http://127.0.0.1:{}/notes", port), None, None);
+    a.edit("a", "Milk\nTea", 10);
+    assert!(matches!(a.sync(&offline), Err(SyncError::Offline(_))));
+    assert!(a.state.has_pending(&a.note_file));
+
+    let mut b = Machine::new(Vec::new());
+    assert_eq!(a.sync(&webdav), Ok(0));
+    assert_eq!(b.sync(&webdav), Ok(0));
+    assert_eq!(b.bodies(), vec!["Milk\nTea"]);
+}
+
+#[test]
+fn test_sync_attachments() {
+    let server = StandIn::start();
+    let webdav = WebDav::new(&server.url, None, None);
+
+    let mut a = Machine::new(Vec::new());
+    let name = a.store.add(b"picture", "png").unwrap();
+    let link = AttachmentLink {
+        is_image: true,
+        label: String::from("Garden"),
+        name: name.clone(),
+        size: None,
+    };
+    a.note_file.children = Some(vec![note("a", &link.to_markup())]);
+    assert_eq!(a.sync(&webdav), Ok(0));
+
+    let mut b = Machine::new(Vec::new());
+    assert_eq!(b.sync(&webdav), Ok(0));
+    assert_eq!(std::fs::read(b.store.path(&name)).unwrap(), b"picture");
+    assert!(!b.state.has_pending(&b.note_file));
+}
@@ -0,0 +1,70 @@
+use crate::tests::test_data::note;
+use crate::tools::{
+    io::{NoteFile, NoteFileItem},
+    wiki_links::{self, Backlink},
+};
+
+#[test]
+fn test_find_and_partial_title() {
+    let links = wiki_links::find("See [[Meeting notes]] and [[ Roadmap ]].");
+    let titles: Vec<&str> = links.iter().map(|link| link.title.as_str()).collect();
+    assert_eq!(titles, vec!["Meeting notes", "Roadmap"]);
+    assert_eq!(links[0].range, 4..21);
+
+    assert_eq!(wiki_links::partial_title("See [[Meet"), Some("Meet"));
+    assert_eq!(wiki_links::partial_title("See [[Meeting]] now"), None);
+    assert_eq!(
+        wiki_links::title_for("[[Roadmap]]").as_deref(),
+        Some("Roadmap")
+    );
+}
+
+#[test]
+fn test_completions() {
+    let titles = vec![
+        String::from("Team meeting"),
+        String::from("Meeting notes"),
+        String::from("Groceries"),
+    ];
+    assert_eq!(
+        wiki_links::completions("meet", &titles, 8),
+        vec!["Meeting notes", "Team meeting"]
+    );
+    assert_eq!(wiki_links::completions("meet", &titles, 1).len(), 1);
+}
+
+#[test]
+fn test_rename() {
+    assert_eq!(
+        wiki_links::rename("[[Old]] and [[Other]] and [[Old]]", "Old", "New").as_deref(),
+        Some("[[New]] and [[Other]] and [[New]]")
+    );
+    assert_eq!(wiki_links::rename("No links", "Old", "New"), None);
+}
+
+#[test]
+fn test_backlinks() {
+    let note_file = NoteFile {
+        children: Some(vec![NoteFileItem {
+            title: String::from("Work"),
+            body: None,
+            children: Some(vec![
+                note("", "Roadmap", "Links to itself [[Roadmap]]"),
+                note("", "Standup", "Discussed [[Roadmap]]"),
+                note("", "Lunch", "Nothing"),
+            ]),
+            is_folder: true,
+            modified: None,
+            id: String::new(),
+        }]),
+        smart_folders: Vec::new(),
+    };
+
+    assert_eq!(
+        wiki_links::backlinks(&note_file, "Roadmap"),
+        vec![Backlink {
+            note_path: vec![0, 1],
+            title: String::from("Standup"),
+        }]
+    );
+}
@@ -0,0 +1,179 @@
+use crate::tools::io::NoteFile;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeSet,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+// An image or file kept in the attachment store, as referenced from the body of a note:
+// ![caption](attachment:<name> "size") for images and [file name](attachment:<name>) for files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachmentLink {
+    pub is_image: bool,
+    // The caption of an image, the name a file was attached with
+    pub label: String,
+    // The hash of the content and the original extension, which is the file's name in the store
+    pub name: String,
+    // How large an image is shown, as named by ImageSize
+    pub size: Option<String>,
+}
+
+impl AttachmentLink {
+    pub fn to_markup(&self) -> String {
+        // Brackets and line breaks would end the link early
+        let label: String = self
+            .label
+            .chars()
+            .filter(|c| !matches!(c, '[' | ']' | '\n' | '\r'))
+            .collect();
+        let size = match &self.size {
+            Some(size) => format!(" \"{}\"", size),
+            None => String::new(),
+        };
+        format!(
+            "{}[{}](attachment:{}{})",
+            if self.is_image { "!" } else { "" },
+            label,
+            self.name,
+            size
+        )
+    }
+}
+
+// Every attachment link in the text, along with its byte range
+pub fn parse(text: &str) -> Vec<(Range<usize>, AttachmentLink)> {
+    let link_re = Regex::new(
+        r#"(!?)\[([^\[\]\n]*)\]\(attachment:([0-9a-f]{64}(?:\.[a-z0-9]{1,10})?)(?: "([a-z-]+)")?\)"#,
+    )
+    .unwrap();
+    link_re
+        .captures_iter(text)
+        .map(|captures| {
+            let link = AttachmentLink {
+                is_image: !captures[1].is_empty(),
+                label: captures[2].to_string(),
+                name: captures[3].to_string(),
+                size: captures.get(4).map(|size| size.as_str().to_string()),
+            };
+            (captures.get(0).unwrap().range(), link)
+        })
+        .collect()
+}
+
+// Names of the attachments the texts refer to
+pub fn referenced<'a, I: IntoIterator<Item = &'a str>>(texts: I) -> BTreeSet<String> {
+    texts
+        .into_iter()
+        .flat_map(parse)
+        .map(|(_, link)| link.name)
+        .collect()
+}
+
+pub fn referenced_by_notes(note_file: &NoteFile) -> BTreeSet<String> {
+    let notes = note_file.notes();
+    referenced(
+        notes
+            .iter()
+            .map(|note| note.item.body.as_deref().unwrap_or("")),
+    )
+}
+
+// The same content always gets the same name, which is what makes the store deduplicate
+pub fn content_name(content: &[u8], extension: &str) -> String {
+    let hash: String = Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let extension: String = extension
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(10)
+        .collect();
+    if extension.is_empty() {
+        hash
+    } else {
+        format!("{}.{}", hash, extension)
+    }
+}
+
+fn is_content_name(name: &str) -> bool {
+    let hash = name.split('.').next().unwrap_or("");
+    hash.len() == 64 && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+// Images and files attached to notes, one file per distinct content
+#[derive(Debug, Clone)]
+pub struct AttachmentStore {
+    dir: PathBuf,
+}
+
+impl AttachmentStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> AttachmentStore {
+        AttachmentStore { dir: dir.into() }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    // Returns the name to link to, which is already there when another note has the same content
+    pub fn add(
+        &self,
+        content: &[u8],
+        extension: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let name = content_name(content, extension);
+        let path = self.path(&name);
+        if path.exists() {
+            return Ok(name);
+        }
+
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let temp_path = self.path(&format!("{}.tmp", name));
+        fs::write(&temp_path, content)
+            .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+        fs::rename(&temp_path, &path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(name)
+    }
+
+    pub fn add_file(&self, path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+        let content =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.add(&content, &extension)
+    }
+
+    // Removes whatever nothing refers to anymore, returning how many files went
+    pub fn collect_garbage(
+        &self,
+        referenced: &BTreeSet<String>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        let entries = fs::read_dir(&self.dir)
+            .map_err(|e| format!("Failed to read {}: {}", self.dir.display(), e))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Leave alone anything the store didn't put there
+            if !is_content_name(&name) || referenced.contains(&name) {
+                continue;
+            }
+            fs::remove_file(entry.path())
+                .map_err(|e| format!("Failed to remove {}: {}", entry.path().display(), e))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,174 @@
+use crate::widgets::gnote_text_buffer::{
+    CHECK_BOX_CHECKED, CHECK_BOX_UNCHECKED, INDENT, SPECIAL_CHAR_PADDING,
+};
+use std::{fmt, ops::Range};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChecklistProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for ChecklistProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} done", self.done, self.total)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckBoxLine<'a> {
+    pub indent_level: usize,
+    pub checked: bool,
+    pub text: &'a str,
+}
+
+// Splits a line such as "    ☑ Buy milk" into its indent level, state and text
+pub fn parse_check_box_line(line: &str) -> Option<CheckBoxLine<'_>> {
+    let mut rest = line;
+    let mut indent_level = 0;
+    while let Some(stripped) = rest.strip_prefix(INDENT) {
+        rest = stripped;
+        indent_level += 1;
+    }
+
+    let mut chars = rest.chars();
+    let checked = match chars.next() {
+        Some(CHECK_BOX_CHECKED) => true,
+        Some(CHECK_BOX_UNCHECKED) => false,
+        _ => return None,
+    };
+    let text = chars.as_str();
+    let text = text.strip_prefix(SPECIAL_CHAR_PADDING).unwrap_or(text);
+
+    Some(CheckBoxLine {
+        indent_level,
+        checked,
+        text,
+    })
+}
+
+pub fn format_check_box_line(indent_level: usize, checked: bool, text: &str) -> String {
+    let check_box = if checked {
+        CHECK_BOX_CHECKED
+    } else {
+        CHECK_BOX_UNCHECKED
+    };
+    format!(
+        "{}{}{}{}",
+        INDENT.repeat(indent_level),
+        check_box,
+        SPECIAL_CHAR_PADDING,
+        text
+    )
+}
+
+pub fn progress(text: &str) -> ChecklistProgress {
+    let mut progress = ChecklistProgress::default();
+    for line in text.lines().filter_map(parse_check_box_line) {
+        progress.total += 1;
+        if line.checked {
+            progress.done += 1;
+        }
+    }
+    progress
+}
+
+// Line ranges of every run of consecutive check box lines, i.e. every list in the note
+pub fn list_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+
+    for (index, line) in text.lines().enumerate() {
+        match (parse_check_box_line(line).is_some(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(list_start)) => {
+                ranges.push(list_start..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(list_start) = start {
+        ranges.push(list_start..text.lines().count());
+    }
+
+    ranges
+}
+
+// A check box line together with the more indented lines nested beneath it
+struct ListItem<'a> {
+    checked: bool,
+    lines: Vec<&'a str>,
+}
+
+fn group_items<'a>(lines: &[&'a str]) -> Vec<ListItem<'a>> {
+    let base_level = lines
+        .iter()
+        .filter_map(|line| parse_check_box_line(line))
+        .map(|line| line.indent_level)
+        .min()
+        .unwrap_or(0);
+
+    let mut items: Vec<ListItem> = Vec::new();
+    for line in lines {
+        let parsed = parse_check_box_line(line);
+        match parsed {
+            Some(parsed) if parsed.indent_level <= base_level || items.is_empty() => {
+                items.push(ListItem {
+                    checked: parsed.checked,
+                    lines: vec![line],
+                });
+            }
+            _ => items.last_mut().unwrap().lines.push(line),
+        }
+    }
+    items
+}
+
+pub fn move_completed_to_bottom(lines: &[&str]) -> Vec<String> {
+    if lines.is_empty() {
+        return Vec::new();
+    }
+
+    let items = group_items(lines);
+    let (open, completed): (Vec<_>, Vec<_>) = items.into_iter().partition(|item| !item.checked);
+
+    open.into_iter()
+        .chain(completed)
+        .flat_map(|item| {
+            let (head, nested) = item.lines.split_at(1);
+            let mut sorted = vec![head[0].to_string()];
+            sorted.extend(move_completed_to_bottom(nested));
+            sorted
+        })
+        .collect()
+}
+
+pub fn clear_completed(lines: &[&str]) -> Vec<String> {
+    if lines.is_empty() {
+        return Vec::new();
+    }
+
+    group_items(lines)
+        .into_iter()
+        .filter(|item| !item.checked)
+        .flat_map(|item| {
+            let (head, nested) = item.lines.split_at(1);
+            let mut kept = vec![head[0].to_string()];
+            kept.extend(clear_completed(nested));
+            kept
+        })
+        .collect()
+}
+
+pub fn uncheck_all(lines: &[&str]) -> Vec<String> {
+    lines
+        .iter()
+        .map(|line| match parse_check_box_line(line) {
+            Some(parsed) if parsed.checked => {
+                format_check_box_line(parsed.indent_level, false, parsed.text)
+            }
+            _ => line.to_string(),
+        })
+        .collect()
+}
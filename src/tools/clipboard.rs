@@ -0,0 +1,516 @@
+use crate::{
+    tools::{
+        attachments::{self, AttachmentLink},
+        emphasis::{self, Style},
+        links,
+    },
+    widgets::gnote_text_buffer::{
+        BULLET, CHECK_BOX_CHECKED, CHECK_BOX_UNCHECKED, INDENT, SPECIAL_CHAR_PADDING,
+    },
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+// Tags, comments and the text between them
+static TOKEN_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|<(/?)([a-zA-Z][a-zA-Z0-9]*)([^>]*)>|([^<]+)|<").unwrap()
+});
+static ATTRIBUTE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"([a-zA-Z_:-]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#).unwrap()
+});
+static ENTITY_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]+);").unwrap());
+
+// Note text as saved, attachment links and all, for copying between notes
+pub const NOTE_MIME_TYPE: &str = "application/x-gnote-note";
+
+const TEXT_MIME_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+];
+
+// What pasted or dropped content is taken as, richest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteFormat {
+    Note,
+    Image,
+    UriList,
+    Html,
+    Text,
+}
+
+// The richest format on offer that a note can take in
+pub fn negotiate<S: AsRef<str>>(mime_types: &[S]) -> Option<PasteFormat> {
+    let offers = |wanted: &dyn Fn(&str) -> bool| {
+        mime_types
+            .iter()
+            .any(|mime_type| wanted(&mime_type.as_ref().to_lowercase()))
+    };
+    if offers(&|mime_type| mime_type == NOTE_MIME_TYPE) {
+        Some(PasteFormat::Note)
+    } else if offers(&|mime_type| mime_type.starts_with("image/")) {
+        Some(PasteFormat::Image)
+    } else if offers(&|mime_type| mime_type == "text/uri-list") {
+        Some(PasteFormat::UriList)
+    } else if offers(&|mime_type| mime_type == "text/html") {
+        Some(PasteFormat::Html)
+    } else if offers(&|mime_type| {
+        TEXT_MIME_TYPES
+            .iter()
+            .any(|text| text.eq_ignore_ascii_case(mime_type))
+    }) {
+        Some(PasteFormat::Text)
+    } else {
+        None
+    }
+}
+
+// URIs in a text/uri-list, which may have comment lines
+pub fn parse_uri_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// Browsers hand out text/html as UTF-16 when it starts with a byte order mark
+pub fn decode_html(bytes: &[u8]) -> String {
+    let utf16 = |to_u16: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|pair| to_u16([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xff, 0xfe, ..] => utf16(u16::from_le_bytes),
+        [0xfe, 0xff, ..] => utf16(u16::from_be_bytes),
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+    .trim_end_matches('\0')
+    .to_string()
+}
+
+struct List {
+    ordered: bool,
+    next_number: usize,
+}
+
+// Turns HTML into note text: lists become bullets or check boxes, bold and italic text
+// become **strong** and *emphasis*, and links keep where they go
+pub fn html_to_note(html: &str) -> String {
+    let mut note = String::new();
+    let mut lists: Vec<List> = Vec::new();
+    // Where each open link goes and where its text starts in the note
+    let mut links: Vec<Option<(String, usize)>> = Vec::new();
+    let mut hidden_depth: usize = 0;
+    let mut pre_depth: usize = 0;
+
+    for captures in TOKEN_RE.captures_iter(html) {
+        if let Some(text) = captures.get(4) {
+            if hidden_depth == 0 {
+                let text = decode_entities(text.as_str());
+                if pre_depth > 0 {
+                    note.push_str(&text);
+                } else {
+                    push_collapsed(&mut note, &text);
+                }
+            }
+            continue;
+        }
+        let name = match captures.get(2) {
+            Some(name) => name.as_str().to_lowercase(),
+            None => {
+                // A '<' that doesn't start a tag
+                if &captures[0] == "<" && hidden_depth == 0 {
+                    note.push('<');
+                }
+                continue;
+            }
+        };
+        let closing = !captures[1].is_empty();
+        let attributes = &captures[3];
+
+        match (name.as_str(), closing) {
+            ("head" | "script" | "style" | "title", false) => hidden_depth += 1,
+            ("head" | "script" | "style" | "title", true) => {
+                hidden_depth = hidden_depth.saturating_sub(1)
+            }
+            _ if hidden_depth > 0 => {}
+            ("br", _) => {
+                trim_trailing_spaces(&mut note);
+                note.push('\n');
+            }
+            ("pre", false) => {
+                start_line(&mut note);
+                pre_depth += 1;
+            }
+            ("pre", true) => {
+                pre_depth = pre_depth.saturating_sub(1);
+                start_line(&mut note);
+            }
+            (
+                "p" | "div" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" | "tr"
+                | "table" | "section" | "article" | "header" | "footer" | "dt" | "dd",
+                _,
+            ) => start_line(&mut note),
+            ("ul" | "ol", false) => {
+                start_line(&mut note);
+                lists.push(List {
+                    ordered: name == "ol",
+                    next_number: 1,
+                });
+            }
+            ("ul" | "ol", true) => {
+                lists.pop();
+                start_line(&mut note);
+            }
+            ("li", false) => {
+                start_line(&mut note);
+                note.push_str(&INDENT.repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(list) if list.ordered => {
+                        note.push_str(&format!("{}. ", list.next_number));
+                        list.next_number += 1;
+                    }
+                    _ => {
+                        note.push(BULLET);
+                        note.push_str(SPECIAL_CHAR_PADDING);
+                    }
+                }
+            }
+            ("li", true) => start_line(&mut note),
+            ("input", false) if attribute(attributes, "type").as_deref() == Some("checkbox") => {
+                // Task lists come as a bullet followed by a check box, which takes its place
+                let bullet = format!("{}{}", BULLET, SPECIAL_CHAR_PADDING);
+                if note.ends_with(&bullet) {
+                    note.truncate(note.len() - bullet.len());
+                }
+                if attribute(attributes, "checked").is_some() {
+                    note.push(CHECK_BOX_CHECKED);
+                } else {
+                    note.push(CHECK_BOX_UNCHECKED);
+                }
+                note.push_str(SPECIAL_CHAR_PADDING);
+            }
+            ("b" | "strong", _) => push_marker(&mut note, "**", closing),
+            ("i" | "em", _) => push_marker(&mut note, "*", closing),
+            ("a", false) => {
+                links.push(attribute(attributes, "href").map(|href| (href, note.len())))
+            }
+            ("a", true) => {
+                if let Some(Some((href, mut start))) = links.pop() {
+                    // Line breaks inside the link may have trimmed away where it started
+                    start = start.min(note.len());
+                    while !note.is_char_boundary(start) {
+                        start -= 1;
+                    }
+                    let text = note[start..].trim().to_string();
+                    let goes_elsewhere = !href.starts_with('#')
+                        && !href.starts_with("javascript:")
+                        && href != text
+                        && href.trim_start_matches("mailto:") != text;
+                    if !text.is_empty() && goes_elsewhere {
+                        note.truncate(start);
+                        note.push_str(&format!("[{}]({})", text, href));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    tidy(&note)
+}
+
+fn decode_entities(text: &str) -> String {
+    ENTITY_RE
+        .replace_all(text, |captures: &regex::Captures| {
+            let entity = &captures[1];
+            let decoded = if let Some(hex) = entity.strip_prefix("#x").or(entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => None,
+                }
+            };
+            decoded.map_or(captures[0].to_string(), |c| c.to_string())
+        })
+        .to_string()
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    ATTRIBUTE_RE
+        .captures_iter(attributes)
+        .find(|captures| captures[1].eq_ignore_ascii_case(name))
+        .map(|captures| {
+            let value = captures
+                .get(2)
+                .or(captures.get(3))
+                .or(captures.get(4))
+                .map_or("", |value| value.as_str());
+            decode_entities(value)
+        })
+}
+
+// Runs of whitespace show as a single space, and not at all at the start of a line
+fn push_collapsed(note: &mut String, text: &str) {
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !note.is_empty() && !note.ends_with([' ', '\n']) {
+                note.push(' ');
+            }
+        } else {
+            note.push(c);
+        }
+    }
+}
+
+fn trim_trailing_spaces(note: &mut String) {
+    let trimmed_len = note.trim_end_matches(' ').len();
+    note.truncate(trimmed_len);
+}
+
+fn start_line(note: &mut String) {
+    trim_trailing_spaces(note);
+    if !note.is_empty() && !note.ends_with('\n') {
+        note.push('\n');
+    }
+}
+
+// Emphasis markers go right against the text they are around
+fn push_marker(note: &mut String, marker: &str, closing: bool) {
+    if closing && note.ends_with(' ') {
+        trim_trailing_spaces(note);
+        note.push_str(marker);
+        note.push(' ');
+    } else {
+        note.push_str(marker);
+    }
+}
+
+fn tidy(note: &str) -> String {
+    let mut tidied = String::new();
+    let mut blank_lines = 0;
+    for line in note.trim_matches('\n').lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        tidied.push_str(line);
+        tidied.push('\n');
+    }
+    tidied.trim_end_matches('\n').to_string()
+}
+
+// A line of a note split into its indent level, list marker and text
+struct NoteLine<'a> {
+    indent_level: usize,
+    marker: Option<ListMarker>,
+    text: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListMarker {
+    Bullet,
+    CheckBox(bool),
+}
+
+fn parse_line(line: &str) -> NoteLine<'_> {
+    let mut text = line;
+    let mut indent_level = 0;
+    while let Some(stripped) = text.strip_prefix(INDENT) {
+        text = stripped;
+        indent_level += 1;
+    }
+    let markers = [
+        (BULLET, ListMarker::Bullet),
+        (CHECK_BOX_UNCHECKED, ListMarker::CheckBox(false)),
+        (CHECK_BOX_CHECKED, ListMarker::CheckBox(true)),
+    ];
+    for (marker_char, marker) in markers {
+        if let Some(stripped) = text.strip_prefix(marker_char) {
+            return NoteLine {
+                indent_level,
+                marker: Some(marker),
+                text: stripped
+                    .strip_prefix(SPECIAL_CHAR_PADDING)
+                    .unwrap_or(stripped),
+            };
+        }
+    }
+    NoteLine {
+        indent_level,
+        marker: None,
+        text,
+    }
+}
+
+// The note as Markdown, with attachments linked to wherever attachment_uri says they are
+pub fn note_to_markdown(note: &str, attachment_uri: &dyn Fn(&AttachmentLink) -> String) -> String {
+    let mut markdown = Vec::new();
+    for line in note.lines() {
+        let line = parse_line(line);
+        let mut text = String::new();
+        let mut last_end = 0;
+        for (range, link) in attachments::parse(line.text) {
+            text.push_str(&line.text[last_end..range.start]);
+            text.push_str(&format!(
+                "{}[{}]({})",
+                if link.is_image { "!" } else { "" },
+                link.label,
+                attachment_uri(&link)
+            ));
+            last_end = range.end;
+        }
+        text.push_str(&line.text[last_end..]);
+
+        let prefix = match line.marker {
+            Some(ListMarker::Bullet) => "- ",
+            Some(ListMarker::CheckBox(false)) => "- [ ] ",
+            Some(ListMarker::CheckBox(true)) => "- [x] ",
+            // Indented paragraphs would read as code blocks
+            None => {
+                markdown.push(text);
+                continue;
+            }
+        };
+        markdown.push(format!(
+            "{}{}{}",
+            "  ".repeat(line.indent_level),
+            prefix,
+            text
+        ));
+    }
+    markdown.join("\n")
+}
+
+// The note as HTML, with lists nested by indent level
+pub fn note_to_html(note: &str, attachment_uri: &dyn Fn(&AttachmentLink) -> String) -> String {
+    let mut html = String::new();
+    // How many lists are open, each with an item still open in it
+    let mut open_lists = 0;
+    for line in note.lines() {
+        let line = parse_line(line);
+        let marker = match line.marker {
+            Some(marker) => marker,
+            None => {
+                html.push_str(&"</li></ul>".repeat(open_lists));
+                open_lists = 0;
+                if line.text.trim().is_empty() {
+                    html.push_str("<div><br></div>");
+                } else {
+                    html.push_str(&format!(
+                        "<div>{}</div>",
+                        inline_html(line.text, attachment_uri)
+                    ));
+                }
+                continue;
+            }
+        };
+
+        let depth = line.indent_level + 1;
+        if open_lists >= depth {
+            html.push_str(&"</li></ul>".repeat(open_lists - depth));
+            html.push_str("</li>");
+        } else {
+            html.push_str(&"<ul>".repeat(depth - open_lists));
+        }
+        open_lists = depth;
+
+        html.push_str("<li>");
+        if let ListMarker::CheckBox(checked) = marker {
+            html.push_str(if checked {
+                "<input type=\"checkbox\" disabled checked> "
+            } else {
+                "<input type=\"checkbox\" disabled> "
+            });
+        }
+        html.push_str(&inline_html(line.text, attachment_uri));
+    }
+    html.push_str(&"</li></ul>".repeat(open_lists));
+    html
+}
+
+fn inline_html(text: &str, attachment_uri: &dyn Fn(&AttachmentLink) -> String) -> String {
+    let mut html = String::new();
+    let mut last_end = 0;
+    for (range, link) in attachments::parse(text) {
+        html.push_str(&links_html(&text[last_end..range.start]));
+        let uri = escape_html(&attachment_uri(&link));
+        if link.is_image {
+            html.push_str(&format!(
+                "<img src=\"{}\" alt=\"{}\">",
+                uri,
+                escape_html(&link.label)
+            ));
+        } else {
+            html.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                uri,
+                escape_html(&link.label)
+            ));
+        }
+        last_end = range.end;
+    }
+    html.push_str(&links_html(&text[last_end..]));
+    html
+}
+
+fn links_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut last_end = 0;
+    for link in links::find(text) {
+        html.push_str(&emphasis_html(&text[last_end..link.range.start]));
+        html.push_str(&format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(&link.uri),
+            escape_html(&text[link.range.clone()])
+        ));
+        last_end = link.range.end;
+    }
+    html.push_str(&emphasis_html(&text[last_end..]));
+    html
+}
+
+fn emphasis_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut last_end = 0;
+    for span in emphasis::find(text) {
+        html.push_str(&escape_html(&text[last_end..span.range.start]));
+        let (element, marker_len) = match span.style {
+            Style::Strong => ("strong", 2),
+            Style::Emphasis => ("em", 1),
+        };
+        let inner = &text[span.range.start + marker_len..span.range.end - marker_len];
+        html.push_str(&format!("<{0}>{1}</{0}>", element, escape_html(inner)));
+        last_end = span.range.end;
+    }
+    html.push_str(&escape_html(&text[last_end..]));
+    html
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,136 @@
+use crate::tools::fuzzy;
+
+// An operation of the application as offered by the command palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command {
+    // Detailed action name, activatable from any widget in the window. Image and attachment
+    // actions go to the one next to the cursor.
+    pub action: &'static str,
+    pub label: &'static str,
+    pub accels: &'static [&'static str],
+}
+
+const fn command(
+    action: &'static str,
+    label: &'static str,
+    accels: &'static [&'static str],
+) -> Command {
+    Command {
+        action,
+        label,
+        accels,
+    }
+}
+
+// Every action the application installs, along with the keys bound to it
+pub const COMMANDS: &[Command] = &[
+    command("notebook.add-note", "New Note", &["<primary>n"]),
+    command("notebook.add-folder", "New Folder", &["<primary><shift>n"]),
+    command("notebook.remove-item", "Delete Selected Item", &[]),
+    command("notebook.new-smart-folder", "New Smart Folder", &[]),
+    command("notebook.quick-switch", "Go to Note", &["<primary>p"]),
+    command(
+        "notebook.command-palette",
+        "Show Command Palette",
+        &["<primary><shift>p"],
+    ),
+    command("notebook.show-tasks", "Show Tasks", &["<primary>t"]),
+    command(
+        "notebook.replace",
+        "Replace in Notebook",
+        &["<primary><shift>f"],
+    ),
+    command("notebook.undo-replace", "Undo Replace in Notebook", &[]),
+    command(
+        "notebook.show-history",
+        "Show Note History",
+        &["<primary><shift>h"],
+    ),
+    command("notebook.set-up-git-sync", "Sync with Git", &[]),
+    command("notebook.git-pull", "Pull Notes", &[]),
+    command("notebook.git-push", "Push Notes", &[]),
+    command("notebook.show-conflicts", "Review Conflicts", &[]),
+    command("notebook.set-up-webdav-sync", "Sync with WebDAV", &[]),
+    command("notebook.webdav-sync", "Sync Now", &[]),
+    command("notebook.choose-storage", "Choose Notebook Storage", &[]),
+    command(
+        "notebook.set-up-encryption",
+        "Encrypt Notebook or Change Passphrase",
+        &[],
+    ),
+    command("notebook.lock", "Lock Notebook", &[]),
+    command("notebook.export-json", "Export Notebook as JSON", &[]),
+    command("notebook.export-ical", "Export Tasks as iCalendar", &[]),
+    command("notebook.export-todo-txt", "Export Tasks as todo.txt", &[]),
+    command("notebook.import-todo-txt", "Import todo.txt", &[]),
+    // The note view binds Ctrl+Z and Ctrl+Shift+Z itself
+    command("editor.undo", "Undo", &[]),
+    command("editor.redo", "Redo", &[]),
+    command(
+        "editor.insert-image",
+        "Insert Image",
+        &["<primary><shift>i"],
+    ),
+    command("editor.attach-file", "Attach File", &[]),
+    command("editor.copy-note-markdown", "Copy Note as Markdown", &[]),
+    command("editor.copy-note-html", "Copy Note as HTML", &[]),
+    command(
+        "editor.insert-check-box",
+        "Insert Check Box",
+        &["<primary><shift>l"],
+    ),
+    command(
+        "editor.insert-bullet",
+        "Insert Bullet",
+        &["<primary><shift>8"],
+    ),
+    command(
+        "editor.indent-less",
+        "Indent Less",
+        &["<primary>bracketleft"],
+    ),
+    command(
+        "editor.indent-more",
+        "Indent More",
+        &["<primary>bracketright"],
+    ),
+    command("editor.find", "Find and Replace", &["<primary>f"]),
+    command("editor.find-next", "Find Next", &["<primary>g"]),
+    command(
+        "editor.find-previous",
+        "Find Previous",
+        &["<primary><shift>g"],
+    ),
+    command("editor.replace", "Replace", &[]),
+    command("editor.replace-all", "Replace All", &[]),
+    command(
+        "editor.move-completed-to-bottom",
+        "Move Completed Items to Bottom",
+        &[],
+    ),
+    command("editor.uncheck-all", "Uncheck All Items", &[]),
+    command("editor.clear-completed", "Clear Completed Items", &[]),
+    command("image.size::small", "Make Image Small", &[]),
+    command("image.size::medium", "Make Image Medium", &[]),
+    command("image.size::large", "Make Image Large", &[]),
+    command("image.size::fit-width", "Fit Image to Width", &[]),
+    command("image.size::original", "Show Image at Original Size", &[]),
+    command("image.edit-caption", "Edit Image Caption", &[]),
+    command("image.copy", "Copy Image", &[]),
+    command("image.save-as", "Save Image As", &[]),
+    command("attachment.open", "Open Attachment", &[]),
+    command("attachment.save-as", "Save Attachment As", &[]),
+    command("app.about", "About Gnote", &[]),
+    command("app.quit", "Quit", &["<primary>q"]),
+];
+
+// Commands whose label matches the query, best first
+pub fn filter(query: &str) -> Vec<&'static Command> {
+    let mut matches: Vec<(i64, &Command)> = COMMANDS
+        .iter()
+        .filter_map(|command| Some((fuzzy::score(query.trim(), command.label)?, command)))
+        .collect();
+    // Stable, so equally good matches keep their order in the list
+    matches.sort_by(|(a_score, _), (b_score, _)| b_score.cmp(a_score));
+    matches.into_iter().map(|(_, command)| command).collect()
+}
@@ -0,0 +1,249 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use std::{fmt, fs};
+use zeroize::Zeroizing;
+
+const FORMAT: &str = "gnote-encrypted";
+const FORMAT_VERSION: u32 = 1;
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+// Argon2id with the memory and passes OWASP recommends, about a tenth of a second to try one
+// passphrase
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CryptoError {
+    WrongPassphrase,
+    Failed(String),
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::WrongPassphrase => write!(f, "The passphrase is wrong"),
+            CryptoError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+// How the key is derived from the passphrase, kept with the key so the settings can be raised
+// later without locking anyone out
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct KdfParams {
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl KdfParams {
+    fn generate() -> KdfParams {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            salt: general_purpose::STANDARD.encode(salt),
+            memory_kib: KDF_MEMORY_KIB,
+            iterations: KDF_ITERATIONS,
+            parallelism: KDF_PARALLELISM,
+        }
+    }
+
+    fn derive(&self, passphrase: &str) -> Result<Zeroizing<[u8; KEY_LENGTH]>, CryptoError> {
+        let salt = general_purpose::STANDARD
+            .decode(&self.salt)
+            .map_err(|e| CryptoError::Failed(format!("Failed to read the key salt: {}", e)))?;
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LENGTH),
+        )
+        .map_err(|e| CryptoError::Failed(format!("Failed to derive the key: {}", e)))?;
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| CryptoError::Failed(format!("Failed to derive the key: {}", e)))?;
+        Ok(key)
+    }
+}
+
+// Encrypted content with what it takes to decrypt it, other than the key
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct Envelope {
+    format: String,
+    version: u32,
+    nonce: String,
+    data: String,
+}
+
+impl Envelope {
+    fn seal(key: &[u8; KEY_LENGTH], plaintext: &[u8]) -> Result<Envelope, CryptoError> {
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+        // Random 192-bit nonces never repeat in practice, however often the notebook is saved
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let data = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| CryptoError::Failed(String::from("Failed to encrypt")))?;
+        Ok(Envelope {
+            format: String::from(FORMAT),
+            version: FORMAT_VERSION,
+            nonce: general_purpose::STANDARD.encode(nonce),
+            data: general_purpose::STANDARD.encode(data),
+        })
+    }
+
+    // Nothing when the key is the wrong one, or the content was changed
+    fn open(&self, key: &[u8; KEY_LENGTH]) -> Option<Zeroizing<Vec<u8>>> {
+        if self.format != FORMAT || self.version != FORMAT_VERSION {
+            return None;
+        }
+        let nonce = general_purpose::STANDARD.decode(&self.nonce).ok()?;
+        let data = general_purpose::STANDARD.decode(&self.data).ok()?;
+        if nonce.len() != XNonce::default().len() {
+            return None;
+        }
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+        cipher
+            .decrypt(XNonce::from_slice(&nonce), data.as_slice())
+            .ok()
+            .map(Zeroizing::new)
+    }
+}
+
+// The random key the notes and their history are encrypted with
+#[derive(Clone)]
+pub struct NotebookKey {
+    key: Zeroizing<[u8; KEY_LENGTH]>,
+}
+
+// Never shows the key itself
+impl fmt::Debug for NotebookKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NotebookKey")
+    }
+}
+
+impl NotebookKey {
+    pub fn generate() -> NotebookKey {
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        OsRng.fill_bytes(key.as_mut());
+        NotebookKey { key }
+    }
+
+    // Encrypts the key with the passphrase, under a new salt
+    pub fn seal(&self, passphrase: &str) -> Result<SealedKey, CryptoError> {
+        let kdf = KdfParams::generate();
+        let passphrase_key = kdf.derive(passphrase)?;
+        Ok(SealedKey {
+            kdf,
+            key: Envelope::seal(&passphrase_key, self.key.as_ref())?,
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let envelope = Envelope::seal(&self.key, plaintext)?;
+        serde_json::to_vec(&envelope)
+            .map_err(|e| CryptoError::Failed(format!("Failed to serialize: {}", e)))
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        let envelope: Envelope = serde_json::from_slice(data)
+            .map_err(|e| CryptoError::Failed(format!("Not an encrypted file: {}", e)))?;
+        envelope.open(&self.key).ok_or_else(|| {
+            CryptoError::Failed(String::from(
+                "The file was encrypted with another key, or has been damaged",
+            ))
+        })
+    }
+}
+
+// The notebook key encrypted with the passphrase. Changing the passphrase only rewrites this,
+// so the notes never have to be encrypted again.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SealedKey {
+    kdf: KdfParams,
+    key: Envelope,
+}
+
+impl SealedKey {
+    pub fn load(path: &str) -> Result<SealedKey, Box<dyn std::error::Error>> {
+        let data =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+        let sealed_key = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to deserialize file {}: {}", path, e))?;
+        Ok(sealed_key)
+    }
+
+    // Written next to the old key and moved over it, so the old passphrase works until the new
+    // one does
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = serde_json::to_string_pretty(&self)
+            .map_err(|e| format!("Failed to serialize key: {}", e))?;
+        write_atomically(path, data.as_bytes())
+    }
+
+    pub fn unseal(&self, passphrase: &str) -> Result<NotebookKey, CryptoError> {
+        let passphrase_key = self.kdf.derive(passphrase)?;
+        let key = self
+            .key
+            .open(&passphrase_key)
+            .ok_or(CryptoError::WrongPassphrase)?;
+        let key: [u8; KEY_LENGTH] = key
+            .as_slice()
+            .try_into()
+            .map_err(|_| CryptoError::Failed(String::from("The key file is damaged")))?;
+        Ok(NotebookKey {
+            key: Zeroizing::new(key),
+        })
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    serde_json::from_slice::<Envelope>(data).is_ok_and(|envelope| envelope.format == FORMAT)
+}
+
+// Reads a file of the notebook, decrypting it when it is encrypted. Files still in plain text
+// are read as they are, so a notebook can be opened halfway through being encrypted.
+pub fn read_file(
+    path: &str,
+    key: Option<&NotebookKey>,
+) -> Result<Zeroizing<Vec<u8>>, Box<dyn std::error::Error>> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+    if !is_encrypted(&data) {
+        return Ok(Zeroizing::new(data));
+    }
+    match key {
+        Some(key) => key
+            .decrypt(&data)
+            .map_err(|e| format!("Failed to decrypt file {}: {}", path, e).into()),
+        None => Err(format!("File {} is encrypted and the notebook is locked", path).into()),
+    }
+}
+
+// Writes a file of the notebook, encrypted when there is a key
+pub fn write_file(
+    path: &str,
+    data: &[u8],
+    key: Option<&NotebookKey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match key {
+        Some(key) => write_atomically(path, &key.encrypt(data)?),
+        None => write_atomically(path, data),
+    }
+}
+
+fn write_atomically(path: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let temp_path = format!("{}.tmp", path);
+    fs::write(&temp_path, data)
+        .map_err(|e| format!("Failed to write file {}: {}", temp_path, e))?;
+    fs::rename(&temp_path, path)
+        .map_err(|e| format!("Failed to replace file {}: {}", path, e).into())
+}
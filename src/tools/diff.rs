@@ -0,0 +1,121 @@
+use std::ops::Range;
+
+const OBJECT_REPLACEMENT_CHAR: char = '\u{FFFC}';
+
+// A line of the difference between two texts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// Line by line, keeping the longest run of lines the two texts have in common
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Edits are usually in one spot, so the ends are matched up without the table
+    let prefix = old_lines
+        .iter()
+        .zip(new_lines.iter())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+
+    // common[i][j] is the length of the longest common run of old_middle[i..] and new_middle[j..]
+    let mut common = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            common[i][j] = if old_middle[i] == new_middle[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old_lines[..prefix]
+        .iter()
+        .map(|line| DiffLine::Same(line))
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            lines.push(DiffLine::Same(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if j < new_middle.len()
+            && (i == old_middle.len() || common[i][j + 1] > common[i + 1][j])
+        {
+            lines.push(DiffLine::Added(new_middle[j]));
+            j += 1;
+        } else {
+            lines.push(DiffLine::Removed(old_middle[i]));
+            i += 1;
+        }
+    }
+    lines.extend(
+        old_lines[old_lines.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Same(line)),
+    );
+    lines
+}
+
+// The byte range of old to replace, and what with, to turn it into new. Only what lies between
+// the parts the two have in common at either end is changed.
+pub fn changed_range<'a>(old: &str, new: &'a str) -> (Range<usize>, &'a str) {
+    let prefix: usize = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(old_char, new_char)| old_char == new_char)
+        .map(|(old_char, _)| old_char.len_utf8())
+        .sum();
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(old_char, new_char)| old_char == new_char)
+        .map(|(old_char, _)| old_char.len_utf8())
+        .sum();
+    (prefix..old.len() - suffix, &new[prefix..new.len() - suffix])
+}
+
+// Like changed_range, but one range for every line that changed, so whatever lies between them is
+// left alone. Object replacement characters, which stand in for attachments, are never part of a
+// range. None when old and new don't have the same lines and attachments to line them up by.
+pub fn changed_ranges(old: &str, new: &str) -> Option<Vec<(Range<usize>, String)>> {
+    let is_break = |c: char| c == '\n' || c == OBJECT_REPLACEMENT_CHAR;
+    let old_pieces: Vec<&str> = old.split_inclusive(is_break).collect();
+    let new_pieces: Vec<&str> = new.split_inclusive(is_break).collect();
+    if old_pieces.len() != new_pieces.len() {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    for (old_piece, new_piece) in old_pieces.iter().zip(new_pieces) {
+        if old_piece.chars().last().filter(|c| is_break(*c))
+            != new_piece.chars().last().filter(|c| is_break(*c))
+        {
+            return None;
+        }
+        if *old_piece != new_piece {
+            let (range, replacement) = changed_range(old_piece, new_piece);
+            ranges.push((
+                offset + range.start..offset + range.end,
+                replacement.to_string(),
+            ));
+        }
+        offset += old_piece.len();
+    }
+    Some(ranges)
+}
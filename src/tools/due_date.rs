@@ -0,0 +1,151 @@
+use gtk::glib;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{fmt, ops::Range};
+
+// Same key as todo.txt so the dates survive an export unchanged
+pub const DUE_PREFIX: &str = "due:";
+// Reminders for dates without a time go off at the start of the working day
+pub const DEFAULT_REMINDER_TIME: (u32, u32) = (9, 0);
+
+// Matched from the start of each "due:" in the line
+static DUE_DATE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^due:([0-9]{4})-([0-9]{2})-([0-9]{2})(?:T([0-9]{2}):([0-9]{2}))?(?:\s|$)").unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DueDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub time: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Overdue,
+    Today,
+    Upcoming,
+}
+
+impl DueDate {
+    pub fn new(year: i32, month: u32, day: u32) -> Self {
+        DueDate {
+            year,
+            month,
+            day,
+            time: None,
+        }
+    }
+
+    pub fn with_time(self, hour: u32, minute: u32) -> Self {
+        DueDate {
+            time: Some((hour, minute)),
+            ..self
+        }
+    }
+
+    pub fn date(&self) -> DueDate {
+        DueDate {
+            time: None,
+            ..*self
+        }
+    }
+
+    // The moment the reminder should fire, always carrying a time
+    pub fn reminder(&self) -> DueDate {
+        DueDate {
+            time: Some(self.time.unwrap_or(DEFAULT_REMINDER_TIME)),
+            ..*self
+        }
+    }
+
+    pub fn urgency(&self, today: &DueDate) -> Urgency {
+        match self.date().cmp(&today.date()) {
+            std::cmp::Ordering::Less => Urgency::Overdue,
+            std::cmp::Ordering::Equal => Urgency::Today,
+            std::cmp::Ordering::Greater => Urgency::Upcoming,
+        }
+    }
+}
+
+impl fmt::Display for DueDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)?;
+        if let Some((hour, minute)) = self.time {
+            write!(f, "T{:02}:{:02}", hour, minute)?;
+        }
+        Ok(())
+    }
+}
+
+// Byte range of the first valid "due:..." token in the line along with the parsed date
+pub fn find(line: &str) -> Option<(Range<usize>, DueDate)> {
+    line.match_indices(DUE_PREFIX)
+        // Only whole words, so "overdue:" isn't one
+        .filter(|(start, _)| !line[..*start].ends_with(|c: char| !c.is_whitespace()))
+        .find_map(|(start, _)| {
+            let due_date = parse(&line[start..])?;
+            let end = start + DUE_PREFIX.len() + due_date.to_string().len();
+            Some((start..end, due_date))
+        })
+}
+
+fn parse(token: &str) -> Option<DueDate> {
+    let captures = DUE_DATE_RE.captures(token)?;
+    let number = |index: usize| captures.get(index)?.as_str().parse::<u32>().ok();
+
+    let (year, month, day) = (number(1)? as i32, number(2)?, number(3)?);
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+
+    let due_date = DueDate::new(year, month, day);
+    match (number(4), number(5)) {
+        (Some(hour), Some(minute)) if hour > 23 || minute > 59 => None,
+        (Some(hour), Some(minute)) => Some(due_date.with_time(hour, minute)),
+        _ => Some(due_date),
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Replaces the line's due date, or appends one when it has none
+pub fn set(line: &str, due_date: &DueDate) -> String {
+    let token = format!("{}{}", DUE_PREFIX, due_date);
+    match find(line) {
+        Some((range, _)) => format!("{}{}{}", &line[..range.start], token, &line[range.end..]),
+        None if line.ends_with(' ') => format!("{}{}", line, token),
+        None => format!("{} {}", line, token),
+    }
+}
+
+// The line without its due date token
+pub fn strip(line: &str) -> String {
+    match find(line) {
+        Some((range, _)) => format!("{}{}", line[..range.start].trim_end(), &line[range.end..])
+            .trim()
+            .to_string(),
+        None => line.to_string(),
+    }
+}
+
+pub fn now_local() -> DueDate {
+    from_date_time(&glib::DateTime::now_local().expect("Couldn't get the local time"))
+}
+
+pub fn now_utc() -> DueDate {
+    from_date_time(&glib::DateTime::now_utc().expect("Couldn't get the time"))
+}
+
+fn from_date_time(now: &glib::DateTime) -> DueDate {
+    DueDate::new(now.year(), now.month() as u32, now.day_of_month() as u32)
+        .with_time(now.hour() as u32, now.minute() as u32)
+}
@@ -0,0 +1,44 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+
+// **strong** and *emphasised* text, which must not start or end with a space so
+// "* item" lines and "2 * 3 * 4" aren't picked up
+static EMPHASIS_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\*\*?)([^\s*](?:[^*\n]*?[^\s*])?)(\*\*?)").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Strong,
+    Emphasis,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    // Covers the asterisks around the text
+    pub range: Range<usize>,
+    pub style: Style,
+}
+
+pub fn find(text: &str) -> Vec<Span> {
+    EMPHASIS_RE
+        .captures_iter(text)
+        .filter(|captures| captures[1] == captures[3])
+        .filter_map(|captures| {
+            let range = captures.get(0).unwrap().range();
+            // Asterisks inside a word, as in "a*b*c", are left alone
+            let before = text[..range.start].chars().next_back();
+            let after = text[range.end..].chars().next();
+            if before.is_some_and(char::is_alphanumeric) || after.is_some_and(char::is_alphanumeric)
+            {
+                return None;
+            }
+            let style = if captures[1].len() == 2 {
+                Style::Strong
+            } else {
+                Style::Emphasis
+            };
+            Some(Span { range, style })
+        })
+        .collect()
+}
@@ -0,0 +1,179 @@
+use crate::tools::io::NoteFile;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    // Treats the query as a regular expression, with $1 and ${name} in replacements
+    pub regex: bool,
+}
+
+fn build(query: &str, options: &FindOptions) -> Result<Regex, regex::Error> {
+    let mut pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+}
+
+// The byte ranges of the text as character offsets, which is what text buffers count in. Ranges in
+// order are counted in a single pass over the text.
+pub fn char_ranges(text: &str, ranges: &[Range<usize>]) -> Vec<Range<i32>> {
+    let (mut byte, mut offset) = (0, 0);
+    let mut char_offset = |to: usize| {
+        if to < byte {
+            (byte, offset) = (0, 0);
+        }
+        offset += text[byte..to].chars().count() as i32;
+        byte = to;
+        offset
+    };
+    ranges
+        .iter()
+        .map(|range| {
+            let start = char_offset(range.start);
+            start..char_offset(range.end)
+        })
+        .collect()
+}
+
+// Byte ranges of every match, an error when the query is a broken regular expression
+pub fn find(text: &str, query: &str, options: &FindOptions) -> Result<Vec<Range<usize>>, String> {
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let regex = build(query, options).map_err(|e| e.to_string())?;
+    Ok(regex
+        .find_iter(text)
+        .map(|found| found.range())
+        .filter(|range| !range.is_empty())
+        .collect())
+}
+
+// Every match along with the text it would be replaced by
+pub fn replacements(
+    text: &str,
+    query: &str,
+    replacement: &str,
+    options: &FindOptions,
+) -> Result<Vec<(Range<usize>, String)>, String> {
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let regex = build(query, options).map_err(|e| e.to_string())?;
+    Ok(regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let range = captures.get(0)?.range();
+            if range.is_empty() {
+                return None;
+            }
+            let mut replaced = String::new();
+            if options.regex {
+                captures.expand(replacement, &mut replaced);
+            } else {
+                replaced.push_str(replacement);
+            }
+            Some((range, replaced))
+        })
+        .collect())
+}
+
+pub fn replace_all(
+    text: &str,
+    query: &str,
+    replacement: &str,
+    options: &FindOptions,
+) -> Result<Option<String>, String> {
+    let replacements = replacements(text, query, replacement, options)?;
+    if replacements.is_empty() {
+        return Ok(None);
+    }
+
+    let mut replaced = String::new();
+    let mut last_end = 0;
+    for (range, replacement) in replacements {
+        replaced.push_str(&text[last_end..range.start]);
+        replaced.push_str(&replacement);
+        last_end = range.end;
+    }
+    replaced.push_str(&text[last_end..]);
+    Ok(Some(replaced))
+}
+
+// A match somewhere in the notebook, as previewed before replacing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub note_path: Vec<i32>,
+    pub note_title: String,
+    // Whether the match is in the title rather than the body
+    pub in_title: bool,
+    // Byte range in the title or body
+    pub range: Range<usize>,
+    pub replacement: String,
+    // The line the match is on, and where on it the match is
+    pub line: String,
+    pub line_range: Range<usize>,
+}
+
+pub fn notebook_occurrences(
+    note_file: &NoteFile,
+    query: &str,
+    replacement: &str,
+    options: &FindOptions,
+    include_titles: bool,
+) -> Result<Vec<Occurrence>, String> {
+    let mut occurrences = Vec::new();
+    for note in note_file.notes() {
+        let mut texts = Vec::new();
+        if include_titles {
+            texts.push((true, note.item.title.as_str()));
+        }
+        texts.push((false, note.item.body.as_deref().unwrap_or("")));
+
+        for (in_title, text) in texts {
+            for (range, replaced) in replacements(text, query, replacement, options)? {
+                let line_start = text[..range.start].rfind('\n').map_or(0, |index| index + 1);
+                let line_end = text[range.start..]
+                    .find('\n')
+                    .map_or(text.len(), |index| range.start + index)
+                    .max(range.end);
+                occurrences.push(Occurrence {
+                    note_path: note.path.clone(),
+                    note_title: note.item.title.clone(),
+                    in_title,
+                    range: range.clone(),
+                    replacement: replaced,
+                    line: text[line_start..line_end].to_string(),
+                    line_range: range.start - line_start..range.end - line_start,
+                });
+            }
+        }
+    }
+    Ok(occurrences)
+}
+
+// Replaces the occurrences, which all have to be from the same text
+pub fn apply_occurrences(text: &str, occurrences: &[&Occurrence]) -> String {
+    let mut occurrences = occurrences.to_vec();
+    occurrences.sort_by_key(|occurrence| occurrence.range.start);
+
+    let mut replaced = String::new();
+    let mut last_end = 0;
+    for occurrence in occurrences {
+        replaced.push_str(&text[last_end..occurrence.range.start]);
+        replaced.push_str(&occurrence.replacement);
+        last_end = occurrence.range.end;
+    }
+    replaced.push_str(&text[last_end..]);
+    replaced
+}
@@ -0,0 +1,388 @@
+use crate::tools::{
+    io::{self, NoteFile, NoteFileItem},
+    search::SmartFolder,
+    storage::{self, FileStamps, Storage},
+};
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+const NOTE_EXTENSION: &str = "md";
+const SIDECAR_EXTENSION: &str = "json";
+// Set in every sidecar, so JSON files that happen to be in the directory are never taken for one
+const SIDECAR_FORMAT: &str = "gnote-note";
+// Smart folders, which belong to the notebook rather than to any one directory
+const NOTEBOOK_FILE: &str = ".notebook.json";
+// Longest file name made from a title, leaving room for the extension and a number
+const MAX_NAME_LENGTH: usize = 100;
+
+// What a note or folder can't say through its file, kept in a sidecar next to it
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+struct Metadata {
+    // Missing from the sidecars of the first notebooks kept as a folder
+    #[serde(default)]
+    format: String,
+    id: String,
+    // Only there when the title couldn't be the file name as it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<i64>,
+    #[serde(default)]
+    position: usize,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+struct NotebookMetadata {
+    #[serde(default)]
+    smart_folders: Vec<SmartFolder>,
+}
+
+// The notebook mirrored to a directory: a directory per folder and a Markdown file per note,
+// each with a small JSON sidecar of the same name. Only files that changed are written, so
+// file syncing tools see just the notes that were edited.
+#[derive(Debug, Clone)]
+pub struct FolderStorage {
+    dir: PathBuf,
+    // The files as last loaded or saved here
+    stamps: RefCell<Option<FileStamps>>,
+}
+
+impl FolderStorage {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FolderStorage {
+        FolderStorage {
+            dir: dir.into(),
+            stamps: RefCell::new(None),
+        }
+    }
+
+    // Every file in the notebook's directories, its own or not
+    fn file_stamps(&self) -> FileStamps {
+        let mut files: Vec<PathBuf> = self
+            .watched()
+            .iter()
+            .flat_map(|dir| fs::read_dir(dir).into_iter().flatten().flatten())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        storage::file_stamps(files)
+    }
+}
+
+impl Storage for FolderStorage {
+    fn exists(&self) -> bool {
+        self.dir.join(NOTEBOOK_FILE).exists()
+    }
+
+    fn load(&self) -> Result<NoteFile, Box<dyn std::error::Error>> {
+        // Taken first, so a write that comes in while reading is still seen as a change
+        let stamps = self.file_stamps();
+        let notebook_path = self.dir.join(NOTEBOOK_FILE);
+        let notebook: NotebookMetadata = match fs::read_to_string(&notebook_path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| {
+                format!(
+                    "Failed to deserialize file {}: {}",
+                    notebook_path.display(),
+                    e
+                )
+            })?,
+            Err(_) => NotebookMetadata::default(),
+        };
+        let note_file = NoteFile {
+            children: Some(load_dir(&self.dir)?),
+            smart_folders: notebook.smart_folders,
+        };
+        self.stamps.replace(Some(stamps));
+        Ok(note_file)
+    }
+
+    fn save(&self, note_file: &NoteFile) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        save_dir(&self.dir, note_file.children.as_deref().unwrap_or(&[]))?;
+        let notebook = NotebookMetadata {
+            smart_folders: note_file.smart_folders.clone(),
+        };
+        let data = serde_json::to_string_pretty(&notebook)
+            .map_err(|e| format!("Failed to serialize notebook: {}", e))?;
+        write_if_changed(&self.dir.join(NOTEBOOK_FILE), data.as_bytes())?;
+        self.stamps.replace(Some(self.file_stamps()));
+        Ok(())
+    }
+
+    // Directory monitors don't see into subdirectories, so every folder is watched
+    fn watched(&self) -> Vec<PathBuf> {
+        fn add_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) {
+            dirs.push(dir.to_path_buf());
+            for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+                    add_dirs(&path, dirs);
+                }
+            }
+        }
+        let mut dirs = Vec::new();
+        add_dirs(&self.dir, &mut dirs);
+        dirs.sort();
+        dirs
+    }
+
+    fn may_have_changed(&self) -> bool {
+        self.stamps.borrow().as_ref() != Some(&self.file_stamps())
+    }
+}
+
+// A name for the title that any file system takes, unique among the (lowercase) names taken
+pub fn file_name(title: &str, taken: &BTreeSet<String>) -> String {
+    let mut name: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    name = name.trim().trim_end_matches('.').to_string();
+    // Hidden files are left out of the notebook
+    if name.starts_with('.') {
+        name.replace_range(..1, "_");
+    }
+    if name.is_empty() {
+        name = String::from("Untitled");
+    }
+
+    let mut unique = name.clone();
+    let mut number = 2;
+    while reserved_names(&unique)
+        .iter()
+        .any(|reserved| taken.contains(reserved))
+    {
+        unique = format!("{} ({})", name, number);
+        number += 1;
+    }
+    unique
+}
+
+// A note or folder takes its name along with its note file and sidecar, compared without case
+fn reserved_names(name: &str) -> [String; 3] {
+    let name = name.to_lowercase();
+    [
+        format!("{}.{}", name, NOTE_EXTENSION),
+        format!("{}.{}", name, SIDECAR_EXTENSION),
+        name,
+    ]
+}
+
+fn sidecar_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, SIDECAR_EXTENSION))
+}
+
+fn read_metadata(path: &Path) -> Option<Metadata> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+// Whether the notebook wrote the sidecar, and so the note or folder next to it
+fn is_sidecar(path: &Path) -> bool {
+    read_metadata(path).is_some_and(|metadata| metadata.format == SIDECAR_FORMAT)
+}
+
+fn load_dir(dir: &Path) -> Result<Vec<NoteFileItem>, Box<dyn std::error::Error>> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut items = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        let (name, item) = if path.is_dir() {
+            let item = NoteFileItem {
+                title: file_name.clone(),
+                body: Some(String::new()),
+                children: Some(load_dir(&path)?),
+                is_folder: true,
+                modified: None,
+                id: String::new(),
+            };
+            (file_name, item)
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == NOTE_EXTENSION)
+        {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let body = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let item = NoteFileItem {
+                title: name.clone(),
+                body: Some(body),
+                children: None,
+                is_folder: false,
+                modified: modified_time(&path),
+                id: String::new(),
+            };
+            (name, item)
+        } else {
+            // Sidecars, and whatever else was put there
+            continue;
+        };
+        items.push((read_metadata(&sidecar_path(dir, &name)), name, item));
+    }
+
+    // Files without a sidecar were added from outside, and go after the rest
+    items.sort_by_key(|(metadata, name, _)| {
+        let position = metadata
+            .as_ref()
+            .map_or(usize::MAX, |metadata| metadata.position);
+        (position, name.clone())
+    });
+    Ok(items
+        .into_iter()
+        .map(|(metadata, _, mut item)| {
+            match metadata {
+                Some(metadata) => {
+                    item.id = metadata.id;
+                    if let Some(title) = metadata.title {
+                        item.title = title;
+                    }
+                    // A note edited elsewhere has a file newer than its sidecar
+                    if !item.is_folder {
+                        item.modified = metadata.modified.max(item.modified);
+                    }
+                }
+                None => item.id = io::new_note_id(),
+            }
+            item
+        })
+        .collect())
+}
+
+fn save_dir(dir: &Path, items: &[NoteFileItem]) -> Result<(), Box<dyn std::error::Error>> {
+    // Names are compared without case, as some file systems do
+    let mut taken = BTreeSet::new();
+    let mut expected = BTreeSet::new();
+    let items = items.iter().filter(|item| !item.is_placeholder());
+    for (position, item) in items.enumerate() {
+        let name = file_name(&item.title, &taken);
+        taken.extend(reserved_names(&name));
+
+        let metadata = Metadata {
+            format: String::from(SIDECAR_FORMAT),
+            id: item.id.clone(),
+            title: Some(item.title.clone()).filter(|title| *title != name),
+            modified: if item.is_folder { None } else { item.modified },
+            position,
+        };
+        let data = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| format!("Failed to serialize {}: {}", item.title, e))?;
+        let sidecar = sidecar_path(dir, &name);
+        write_if_changed(&sidecar, data.as_bytes())?;
+        expected.insert(format!("{}.{}", name, SIDECAR_EXTENSION).to_lowercase());
+
+        if item.is_folder {
+            let folder_dir = dir.join(&name);
+            fs::create_dir_all(&folder_dir)
+                .map_err(|e| format!("Failed to create {}: {}", folder_dir.display(), e))?;
+            save_dir(&folder_dir, item.children.as_deref().unwrap_or(&[]))?;
+            expected.insert(name.to_lowercase());
+        } else {
+            let note_path = dir.join(format!("{}.{}", name, NOTE_EXTENSION));
+            write_if_changed(&note_path, item.body.as_deref().unwrap_or("").as_bytes())?;
+            if let Some(modified) = item.modified {
+                set_modified_time(&note_path, modified)?;
+            }
+            expected.insert(format!("{}.{}", name, NOTE_EXTENSION).to_lowercase());
+        }
+    }
+    remove_stale(dir, &expected)
+}
+
+// Removes the notes, sidecars and folders of whatever was deleted, moved or renamed. Only what
+// has a sidecar written by the notebook was ever the notebook's, so other files and directories
+// are left alone.
+fn remove_stale(dir: &Path, expected: &BTreeSet<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    // Worked out before anything goes, as a note or folder is only known by its sidecar
+    let mut stale_dirs = Vec::new();
+    let mut stale_files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('.') || expected.contains(&file_name.to_lowercase()) {
+            continue;
+        }
+
+        if path.is_dir() {
+            if is_sidecar(&sidecar_path(dir, &file_name)) {
+                stale_dirs.push(path);
+            }
+            continue;
+        }
+        let sidecar = match path.extension() {
+            Some(extension) if extension == NOTE_EXTENSION => {
+                path.with_extension(SIDECAR_EXTENSION)
+            }
+            Some(extension) if extension == SIDECAR_EXTENSION => path.clone(),
+            _ => continue,
+        };
+        if is_sidecar(&sidecar) {
+            stale_files.push(path);
+        }
+    }
+
+    for path in stale_dirs {
+        remove_stale(&path, &BTreeSet::new())?;
+        // Still holding files of its own, so it stays
+        let _ = fs::remove_dir(&path);
+    }
+    for path in stale_files {
+        fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn write_if_changed(path: &Path, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    if fs::read(path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    // Written next to it first, so a crash never leaves half a note behind
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    fs::write(&temp_path, content)
+        .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e).into())
+}
+
+fn modified_time(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
+
+// Dates the file as the note, so only an edit made elsewhere leaves it newer than the sidecar
+fn set_modified_time(path: &Path, modified: i64) -> Result<(), Box<dyn std::error::Error>> {
+    if modified_time(path) == Some(modified) {
+        return Ok(());
+    }
+    let time = UNIX_EPOCH + Duration::from_secs(modified.max(0) as u64);
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(time))
+        .map_err(|e| format!("Failed to date {}: {}", path.display(), e).into())
+}
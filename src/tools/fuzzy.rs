@@ -0,0 +1,123 @@
+use crate::tools::io::NoteFile;
+
+// Recent edits lift a note up the results for about a month, by no more than a word start is worth
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+const RECENCY_DAYS: i64 = 30;
+const RECENCY_DAYS_PER_POINT: i64 = 3;
+
+// A note as offered by the quick switcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub note_path: Vec<i32>,
+    pub title: String,
+    // The folders leading to the note, e.g. "Work / Projects"
+    pub location: String,
+    pub modified: Option<i64>,
+}
+
+impl Candidate {
+    pub fn full_path(&self) -> String {
+        if self.location.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} / {}", self.location, self.title)
+        }
+    }
+}
+
+pub fn candidates(note_file: &NoteFile) -> Vec<Candidate> {
+    note_file
+        .notes()
+        .into_iter()
+        .map(|note| Candidate {
+            note_path: note.path,
+            title: note.item.title.clone(),
+            location: note.folders.join(" / "),
+            modified: note.item.modified,
+        })
+        .collect()
+}
+
+// How well the query matches the text when its characters appear in order, None when they don't.
+// Runs of characters and matches at the start of words count for the most
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text_lowercase = text.to_lowercase();
+    let text: Vec<char> = text_lowercase.chars().collect();
+
+    let mut score = 0;
+    let mut query_index = 0;
+    let mut last_match: Option<usize> = None;
+    for (index, &character) in text.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if character != query[query_index] {
+            continue;
+        }
+
+        score += 1;
+        if last_match.is_some_and(|last_match| last_match + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || matches!(text[index - 1], ' ' | '/' | '-' | '_' | '.') {
+            score += 8;
+        }
+        if last_match.is_none() {
+            // Matches further into the text are worth a little less
+            score -= index.min(10) as i64;
+        }
+        last_match = Some(index);
+        query_index += 1;
+    }
+
+    if query_index < query.len() {
+        return None;
+    }
+
+    let query: String = query.into_iter().collect();
+    if text_lowercase == query {
+        score += 50;
+    } else if text_lowercase.contains(&query) {
+        score += 20;
+    }
+    Some(score)
+}
+
+// The best matches first, with recently edited notes ahead of others that match as well.
+// Without a query that is simply the most recently edited notes
+pub fn rank<'a>(
+    query: &str,
+    candidates: &'a [Candidate],
+    now: i64,
+    limit: usize,
+) -> Vec<&'a Candidate> {
+    let query = query.trim();
+    let mut ranked: Vec<(i64, &Candidate)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            // The title is what people remember, the folders only help narrow things down
+            let title_score = score(query, &candidate.title).map(|score| score * 2);
+            let path_score = score(query, &candidate.full_path());
+            let match_score = title_score.max(path_score)?;
+
+            let recency = candidate.modified.map_or(0, |modified| {
+                let days = (now - modified).max(0) / SECONDS_PER_DAY;
+                (RECENCY_DAYS - days).max(0) / RECENCY_DAYS_PER_POINT
+            });
+            Some((match_score + recency, candidate))
+        })
+        .collect();
+
+    ranked.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| b.modified.cmp(&a.modified))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    ranked.truncate(limit);
+    ranked.into_iter().map(|(_, candidate)| candidate).collect()
+}
@@ -0,0 +1,235 @@
+use crate::tools::{
+    io::NoteFile,
+    merge::{self, Merged},
+};
+use std::{fs, path::PathBuf, process::Command};
+
+// What stays on this machine when the notebook directory is under git
+const GITIGNORE: &str = "settings*.json\nhistory*.json\nsync_state*.json\n*.tmp\n";
+
+// The notebook directory as a git working copy, synced through the "origin" remote with the
+// git command line tool
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    dir: PathBuf,
+    // Names of the notebook file and the attachment store within the directory
+    notes_file: String,
+    attachments_dir: String,
+}
+
+impl GitRepo {
+    pub fn new<P: Into<PathBuf>>(dir: P, notes_file: &str, attachments_dir: &str) -> GitRepo {
+        GitRepo {
+            dir: dir.into(),
+            notes_file: notes_file.to_string(),
+            attachments_dir: attachments_dir.to_string(),
+        }
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    pub fn is_repository(&self) -> bool {
+        self.dir.join(".git").exists()
+    }
+
+    // Makes the directory a working copy of the remote, keeping whatever history it already has
+    pub fn init(&self, remote: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.is_repository() {
+            self.git(&["init", "-q"])?;
+        }
+        let gitignore = self.dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(&gitignore, GITIGNORE)
+                .map_err(|e| format!("Failed to write {}: {}", gitignore.display(), e))?;
+        }
+        // Commits need an author even where git was never set up
+        if self.git(&["config", "user.email"]).is_err() {
+            self.git(&["config", "user.name", "Gnote"])?;
+            self.git(&["config", "user.email", "gnote@localhost"])?;
+        }
+        if self.git(&["remote", "get-url", "origin"]).is_ok() {
+            self.git(&["remote", "set-url", "origin", remote])?;
+        } else {
+            self.git(&["remote", "add", "origin", remote])?;
+        }
+        Ok(())
+    }
+
+    // Commits everything that changed, returning whether there was anything to commit
+    pub fn commit(&self, message: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        self.git(&["add", "-A"])?;
+        if self.git(&["status", "--porcelain"])?.is_empty() {
+            return Ok(false);
+        }
+        self.git(&["commit", "-q", "-m", message])?;
+        Ok(true)
+    }
+
+    // Commits the notebook as saved, described by what changed in it since the last commit
+    pub fn commit_notes(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        let notes_path = self.dir.join(&self.notes_file);
+        let note_file = NoteFile::load(&notes_path.to_string_lossy())?;
+        let message = commit_message(self.committed_note_file().as_ref(), &note_file);
+        self.commit(&message)
+    }
+
+    // The notebook as of the last commit, None before the first one
+    pub fn committed_note_file(&self) -> Option<NoteFile> {
+        self.note_file_at("HEAD")
+    }
+
+    fn note_file_at(&self, revision: &str) -> Option<NoteFile> {
+        let data = self
+            .git(&["show", &format!("{}:{}", revision, self.notes_file)])
+            .ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    fn branch(&self) -> Result<String, Box<dyn std::error::Error>> {
+        self.git(&["symbolic-ref", "--short", "HEAD"])
+    }
+
+    fn revision(&self, name: &str) -> Option<String> {
+        self.git(&["rev-parse", "--verify", "-q", name]).ok()
+    }
+
+    // Brings in what was pushed from elsewhere. The notebook file is merged note by note rather
+    // than by line, so it never ends up with conflict markers; notes changed on both sides keep
+    // the latest version and come back as conflicts. Returns the notebook if it changed.
+    pub fn pull(&self) -> Result<Option<Merged>, Box<dyn std::error::Error>> {
+        self.git(&["fetch", "-q", "origin"])?;
+        let remote_branch = format!("origin/{}", self.branch()?);
+        let remote = match self.revision(&remote_branch) {
+            Some(remote) => remote,
+            // Nothing pushed yet
+            None => return Ok(None),
+        };
+        let mut head = self.revision("HEAD");
+        if head.is_none() {
+            // A notebook that never made it into a commit is merged rather than replaced
+            self.commit("Add notebook")
+                .map_err(|e| format!("Failed to commit the notebook before pulling: {}", e))?;
+            head = self.revision("HEAD");
+        }
+        let merge_base = self.git(&["merge-base", "HEAD", &remote_branch]).ok();
+        if merge_base.as_ref() == Some(&remote) {
+            return Ok(None);
+        }
+
+        if head.is_none() || merge_base == head {
+            if head.is_none() {
+                // Nothing here to commit, so there is nothing of ours to keep
+                self.git(&["reset", "-q", "--hard", &remote_branch])?;
+            } else {
+                self.git(&["merge", "-q", "--ff-only", &remote_branch])?;
+            }
+            return Ok(self.note_file_at("HEAD").map(|note_file| Merged {
+                note_file,
+                conflicts: Vec::new(),
+            }));
+        }
+
+        let local_file = self.note_file_at("HEAD");
+        let remote_file = self.note_file_at(&remote_branch);
+        let merged = match (local_file, remote_file) {
+            (Some(local_file), Some(remote_file)) => {
+                let base_file = merge_base.and_then(|merge_base| self.note_file_at(&merge_base));
+                merge::merge(base_file.as_ref(), &local_file, &remote_file)
+            }
+            (Some(note_file), None) | (None, Some(note_file)) => Merged {
+                note_file,
+                conflicts: Vec::new(),
+            },
+            (None, None) => return Err("Neither side has a notebook to merge".into()),
+        };
+
+        self.git(&[
+            "merge",
+            "-q",
+            "--no-commit",
+            "--no-ff",
+            "--allow-unrelated-histories",
+            "-s",
+            "ours",
+            &remote_branch,
+        ])?;
+        // Attachments never change once stored, so theirs are simply added to ours
+        if !self
+            .git(&[
+                "ls-tree",
+                "--name-only",
+                &remote_branch,
+                &self.attachments_dir,
+            ])?
+            .is_empty()
+        {
+            self.git(&["checkout", &remote_branch, "--", &self.attachments_dir])?;
+        }
+        let notes_path = self.dir.join(&self.notes_file);
+        merged.note_file.save(&notes_path.to_string_lossy())?;
+        let message = match merged.conflicts.len() {
+            0 => format!("Merge notes from {}", remote_branch),
+            count => format!(
+                "Merge notes from {}, keeping the latest versions of {} conflicting notes",
+                remote_branch, count
+            ),
+        };
+        // Committed even when nothing differs from ours, to finish the merge
+        self.git(&["add", "-A"])?;
+        self.git(&["commit", "-q", "-m", &message])?;
+        Ok(Some(merged))
+    }
+
+    pub fn push(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.git(&["push", "-q", "-u", "origin", "HEAD"])?;
+        Ok(())
+    }
+}
+
+// Describes what changed in the notebook for a commit, e.g. "Edit “Groceries”"
+pub fn commit_message(before: Option<&NoteFile>, after: &NoteFile) -> String {
+    let before = before.map(merge::entries).unwrap_or_default();
+    let after = merge::entries(after);
+
+    let mut changes = Vec::new();
+    for (id, entry) in &after {
+        match before.get(id) {
+            None => changes.push(format!("Add “{}”", entry.title)),
+            Some(old) if old.title != entry.title => {
+                changes.push(format!("Rename “{}” to “{}”", old.title, entry.title))
+            }
+            Some(old) if old.body != entry.body => changes.push(format!("Edit “{}”", entry.title)),
+            Some(old) if old.parent != entry.parent => {
+                changes.push(format!("Move “{}”", entry.title))
+            }
+            _ => {}
+        }
+    }
+    for (id, entry) in &before {
+        if !after.contains_key(id) {
+            changes.push(format!("Delete “{}”", entry.title));
+        }
+    }
+
+    match changes.len() {
+        0 => "Update notebook".to_string(),
+        1 => changes.remove(0),
+        count => format!("Update {} notes\n\n{}", count, changes.join("\n")),
+    }
+}
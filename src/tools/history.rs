@@ -0,0 +1,96 @@
+use crate::tools::{
+    crypto::{self, NotebookKey},
+    io::NoteFile,
+};
+use std::{collections::BTreeMap, path::Path};
+
+// Saves closer together than this many seconds end up in the same snapshot
+pub const SNAPSHOT_INTERVAL: i64 = 5 * 60;
+// The oldest snapshots of a note go once it has more than this
+pub const MAX_SNAPSHOTS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Snapshot {
+    // Seconds since the Unix epoch the note was edited
+    pub time: i64,
+    pub title: String,
+    pub body: String,
+}
+
+// Earlier versions of every note, oldest first, keyed by note id
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct History {
+    #[serde(default)]
+    pub notes: BTreeMap<String, Vec<Snapshot>>,
+}
+
+impl History {
+    // A notebook without history yet starts with an empty one
+    // Encrypted along with the notes when the notebook is, as it holds their text too
+    pub fn load(
+        path: &str,
+        key: Option<&NotebookKey>,
+    ) -> Result<History, Box<dyn std::error::Error>> {
+        if !Path::new(path).exists() {
+            return Ok(History::default());
+        }
+        let data = crypto::read_file(path, key)?;
+        let history = serde_json::from_slice(&data)
+            .map_err(|e| format!("Failed to deserialize file {}: {}", path, e))?;
+        Ok(history)
+    }
+
+    // Written next to the old file and moved over it, so a failed save can't lose the history
+    pub fn save(
+        &self,
+        path: &str,
+        key: Option<&NotebookKey>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let data =
+            serde_json::to_vec(&self).map_err(|e| format!("Failed to serialize history: {}", e))?;
+        crypto::write_file(path, &data, key)
+    }
+
+    pub fn snapshots(&self, note_id: &str) -> &[Snapshot] {
+        self.notes.get(note_id).map_or(&[], |snapshots| snapshots)
+    }
+
+    // Keeps the note as it is now, replacing the latest snapshot when that one is too recent.
+    // Returns whether anything changed.
+    pub fn record(&mut self, note_id: &str, title: &str, body: &str, time: i64) -> bool {
+        let snapshots = self.notes.entry(note_id.to_string()).or_default();
+        let snapshot = Snapshot {
+            time,
+            title: title.to_string(),
+            body: body.to_string(),
+        };
+
+        match snapshots.last_mut() {
+            Some(last) if last.title == title && last.body == body => return false,
+            Some(last) if time - last.time < SNAPSHOT_INTERVAL => *last = snapshot,
+            _ => snapshots.push(snapshot),
+        }
+        if snapshots.len() > MAX_SNAPSHOTS {
+            let excess = snapshots.len() - MAX_SNAPSHOTS;
+            snapshots.drain(..excess);
+        }
+        true
+    }
+
+    // Snapshots every note that changed since it was last recorded, timed by when it was edited
+    pub fn record_notes(&mut self, note_file: &NoteFile, now: i64) -> bool {
+        let mut changed = false;
+        for note in note_file.notes() {
+            if note.item.id.is_empty() {
+                continue;
+            }
+            changed |= self.record(
+                &note.item.id,
+                &note.item.title,
+                note.item.body.as_deref().unwrap_or(""),
+                note.item.modified.unwrap_or(now),
+            );
+        }
+        changed
+    }
+}
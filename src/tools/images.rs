@@ -0,0 +1,56 @@
+// How large an image is shown in a note
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageSize {
+    Small,
+    Medium,
+    Large,
+    // As wide as the editor, the size images get when inserted
+    #[default]
+    FitWidth,
+    Original,
+}
+
+impl ImageSize {
+    pub fn from_name(name: &str) -> Option<ImageSize> {
+        match name {
+            "small" => Some(ImageSize::Small),
+            "medium" => Some(ImageSize::Medium),
+            "large" => Some(ImageSize::Large),
+            "fit-width" => Some(ImageSize::FitWidth),
+            "original" => Some(ImageSize::Original),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageSize::Small => "small",
+            ImageSize::Medium => "medium",
+            ImageSize::Large => "large",
+            ImageSize::FitWidth => "fit-width",
+            ImageSize::Original => "original",
+        }
+    }
+
+    fn max_width(&self, available_width: i32) -> Option<i32> {
+        match self {
+            ImageSize::Small => Some(200),
+            ImageSize::Medium => Some(400),
+            ImageSize::Large => Some(800),
+            ImageSize::FitWidth => Some(available_width),
+            ImageSize::Original => None,
+        }
+    }
+}
+
+// The size to show an image at, keeping its aspect ratio and never making it larger than it is
+pub fn scaled_size(size: ImageSize, width: i32, height: i32, available_width: i32) -> (i32, i32) {
+    if width <= 0 || height <= 0 {
+        return (width, height);
+    }
+    let scaled_width = size
+        .max_width(available_width)
+        .map_or(width, |max_width| max_width.clamp(1, width));
+    let scaled_height = (height as i64 * scaled_width as i64 / width as i64).max(1) as i32;
+    (scaled_width, scaled_height)
+}
@@ -1,20 +1,50 @@
-use crate::log_test;
+use crate::{log_test, tools::search::SmartFolder};
 use base64::{engine::general_purpose, Engine};
 use serde::Deserialize;
-use std::{fmt, fs, fs::File as StdFile, io::prelude::*, path::PathBuf};
+use std::{
+    fmt, fs,
+    fs::File as StdFile,
+    io::prelude::*,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct NoteFileItem {
     pub(crate) title: String,
     #[serde(deserialize_with = "from_base64", serialize_with = "to_base64")]
     pub body: Option<String>,
     pub children: Option<Vec<NoteFileItem>>,
     pub is_folder: bool,
+    // Seconds since the Unix epoch the note was last edited, absent in older notebooks
+    #[serde(default)]
+    pub modified: Option<i64>,
+    // Stays with the note through renames and moves, older notebooks get one on load
+    #[serde(default = "new_note_id")]
+    pub id: String,
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct NoteFile {
     pub children: Option<Vec<NoteFileItem>>,
+    #[serde(default)]
+    pub smart_folders: Vec<SmartFolder>,
+}
+
+// Unique enough to tell the notes of one notebook apart without a random number generator
+pub fn new_note_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
 }
 
 fn from_base64<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
@@ -54,6 +84,12 @@ where
 }
 
 impl NoteFileItem {
+    // The empty row that keeps a folder without notes looking like a folder in the tree. It has
+    // no id, and isn't part of the notebook.
+    pub fn is_placeholder(&self) -> bool {
+        self.is_folder && self.id.is_empty()
+    }
+
     fn fmt_recursive(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
         let indent = "  ".repeat(depth);
         let is_folder_str = if self.is_folder { " (folder)" } else { "" };
@@ -109,7 +145,68 @@ impl PartialEq for NoteFile {
     }
 }
 
+// A note somewhere in the notebook along with where it lives
+pub struct NoteRef<'a> {
+    // Child indices from the root of the notebook down to the note, matching the tree paths
+    pub path: Vec<i32>,
+    pub folders: Vec<String>,
+    pub item: &'a NoteFileItem,
+}
+
 impl NoteFile {
+    pub fn notes(&self) -> Vec<NoteRef<'_>> {
+        fn collect_item<'a>(
+            item: &'a NoteFileItem,
+            path: &mut Vec<i32>,
+            folders: &mut Vec<String>,
+            notes: &mut Vec<NoteRef<'a>>,
+        ) {
+            if !item.is_folder {
+                notes.push(NoteRef {
+                    path: path.clone(),
+                    folders: folders.clone(),
+                    item,
+                });
+                return;
+            }
+
+            folders.push(item.title.clone());
+            for (index, child) in item.children.iter().flatten().enumerate() {
+                path.push(index as i32);
+                collect_item(child, path, folders, notes);
+                path.pop();
+            }
+            folders.pop();
+        }
+
+        let mut notes = Vec::new();
+        for (index, item) in self.children.iter().flatten().enumerate() {
+            collect_item(item, &mut vec![index as i32], &mut Vec::new(), &mut notes);
+        }
+        notes
+    }
+
+    // Gives the notes with the same ids the titles, bodies and edit times of these
+    pub fn update_notes(&mut self, notes: &[NoteFileItem]) {
+        fn update_item(item: &mut NoteFileItem, notes: &[NoteFileItem]) {
+            for child in item.children.iter_mut().flatten() {
+                update_item(child, notes);
+            }
+            if let Some(note) = notes
+                .iter()
+                .find(|note| !item.is_folder && note.id == item.id)
+            {
+                item.title = note.title.clone();
+                item.body = note.body.clone();
+                item.modified = note.modified;
+            }
+        }
+
+        for item in self.children.iter_mut().flatten() {
+            update_item(item, notes);
+        }
+    }
+
     pub fn load(path: &str) -> Result<NoteFile, Box<dyn std::error::Error>> {
         // Read JSON file
         let mut file =
@@ -159,6 +256,11 @@ fn ensure_gnote_directory() -> PathBuf {
     gnote_path
 }
 
+// Where the notebook, its history and its attachments are kept
+pub fn get_notebook_dir() -> PathBuf {
+    ensure_gnote_directory()
+}
+
 pub fn get_settings_path() -> String {
     let mut settings_path = ensure_gnote_directory();
 
@@ -189,6 +291,99 @@ pub fn get_notes_path() -> String {
     notes_path.to_str().unwrap().to_owned()
 }
 
+pub fn get_database_path() -> PathBuf {
+    let mut database_path = ensure_gnote_directory();
+
+    #[cfg(test)]
+    {
+        database_path.push("notes_test.sqlite");
+    }
+    #[cfg(not(test))]
+    {
+        database_path.push("notes.sqlite");
+    }
+
+    database_path
+}
+
+// The notebook as a folder of Markdown files, unless another directory was chosen for it
+pub fn get_notes_dir() -> PathBuf {
+    let mut notes_dir = ensure_gnote_directory();
+
+    #[cfg(test)]
+    {
+        notes_dir.push("notes_test");
+    }
+    #[cfg(not(test))]
+    {
+        notes_dir.push("notes");
+    }
+
+    notes_dir
+}
+
+// The key of an encrypted notebook, sealed with its passphrase. The notebook is encrypted when
+// this exists.
+pub fn get_key_path() -> String {
+    let mut key_path = ensure_gnote_directory();
+
+    #[cfg(test)]
+    {
+        key_path.push("notebook_key_test.json");
+    }
+    #[cfg(not(test))]
+    {
+        key_path.push("notebook_key.json");
+    }
+
+    key_path.to_str().unwrap().to_owned()
+}
+
+pub fn get_history_path() -> String {
+    let mut history_path = ensure_gnote_directory();
+
+    #[cfg(test)]
+    {
+        history_path.push("history_test.json");
+    }
+    #[cfg(not(test))]
+    {
+        history_path.push("history.json");
+    }
+
+    history_path.to_str().unwrap().to_owned()
+}
+
+pub fn get_sync_state_path() -> String {
+    let mut sync_state_path = ensure_gnote_directory();
+
+    #[cfg(test)]
+    {
+        sync_state_path.push("sync_state_test.json");
+    }
+    #[cfg(not(test))]
+    {
+        sync_state_path.push("sync_state.json");
+    }
+
+    sync_state_path.to_str().unwrap().to_owned()
+}
+
+pub fn get_attachments_dir() -> PathBuf {
+    let mut attachments_dir = ensure_gnote_directory();
+
+    #[cfg(test)]
+    {
+        attachments_dir.push("attachments_test");
+    }
+    #[cfg(not(test))]
+    {
+        attachments_dir.push("attachments");
+    }
+
+    attachments_dir
+}
+
 // ############################ UNIT TESTS ############################
 
 /*#[cfg(test)]
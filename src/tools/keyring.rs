@@ -0,0 +1,49 @@
+use gtk::{gio, glib};
+use libsecret::{Schema, SchemaAttributeType, SchemaFlags};
+use std::collections::HashMap;
+
+// Passwords live in the desktop's keyring rather than in the settings file, found again by the
+// server and user name they belong to
+fn webdav_schema() -> Schema {
+    let attributes = HashMap::from([
+        ("url", SchemaAttributeType::String),
+        ("username", SchemaAttributeType::String),
+    ]);
+    Schema::new("org.bil4x4.gnote.WebDav", SchemaFlags::NONE, attributes)
+}
+
+fn webdav_attributes<'a>(url: &'a str, username: Option<&'a str>) -> HashMap<&'a str, &'a str> {
+    HashMap::from([("url", url), ("username", username.unwrap_or(""))])
+}
+
+pub fn webdav_password(url: &str, username: Option<&str>) -> Result<Option<String>, glib::Error> {
+    let password = libsecret::password_lookup_sync(
+        Some(&webdav_schema()),
+        webdav_attributes(url, username),
+        gio::Cancellable::NONE,
+    )?;
+    Ok(password.map(|password| password.to_string()))
+}
+
+// Keeps the password for signing in to a server, or forgets it when there is none
+pub fn set_webdav_password(
+    url: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> Result<(), glib::Error> {
+    match password {
+        Some(password) => libsecret::password_store_sync(
+            Some(&webdav_schema()),
+            webdav_attributes(url, username),
+            Some(libsecret::COLLECTION_DEFAULT),
+            &format!("Gnote WebDAV password for {}", url),
+            password,
+            gio::Cancellable::NONE,
+        ),
+        None => libsecret::password_clear_sync(
+            Some(&webdav_schema()),
+            webdav_attributes(url, username),
+            gio::Cancellable::NONE,
+        ),
+    }
+}
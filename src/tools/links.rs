@@ -0,0 +1,49 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+
+static LINK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?P<uri>(?:https?|file)://[^\s<>"]+|mailto:[^\s<>"]+)|(?P<email>[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,})"#,
+    )
+    .unwrap()
+});
+
+// Punctuation that usually ends the sentence rather than the link
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', '\'', '"'];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub range: Range<usize>,
+    pub uri: String,
+}
+
+pub fn find(text: &str) -> Vec<Link> {
+    LINK_RE
+        .captures_iter(text)
+        .map(|captures| match captures.name("uri") {
+            Some(uri) => {
+                let trimmed = uri.as_str().trim_end_matches(TRAILING_PUNCTUATION);
+                Link {
+                    range: uri.start()..uri.start() + trimmed.len(),
+                    uri: trimmed.to_string(),
+                }
+            }
+            None => {
+                let email = captures.name("email").unwrap();
+                Link {
+                    range: email.range(),
+                    uri: format!("mailto:{}", email.as_str()),
+                }
+            }
+        })
+        .collect()
+}
+
+// The URI to open for the text of a link, as styled in the note
+pub fn uri_for(link_text: &str) -> Option<String> {
+    find(link_text)
+        .into_iter()
+        .find(|link| link.range == (0..link_text.len()))
+        .map(|link| link.uri)
+}
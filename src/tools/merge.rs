@@ -0,0 +1,239 @@
+use crate::tools::io::{NoteFile, NoteFileItem};
+use std::collections::{BTreeMap, BTreeSet};
+
+// A folder or note as it sits in the notebook, apart from its children
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub parent: Option<String>,
+    pub position: usize,
+    pub title: String,
+    pub body: String,
+    pub is_folder: bool,
+    pub modified: Option<i64>,
+}
+
+impl Entry {
+    fn content(&self) -> (String, String) {
+        (self.title.clone(), self.body.clone())
+    }
+
+    fn placement(&self) -> (Option<String>, usize) {
+        (self.parent.clone(), self.position)
+    }
+}
+
+// A note that was changed differently on both sides since they last agreed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub id: String,
+    // Title and body on each side
+    pub local: (String, String),
+    pub remote: (String, String),
+    // Whether the remote version is the one the merge went with
+    pub kept_remote: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Merged {
+    pub note_file: NoteFile,
+    // Notes where only one side's changes were kept, for the user to look over
+    pub conflicts: Vec<Conflict>,
+}
+
+// Every folder and note in the notebook, keyed by id
+pub fn entries(note_file: &NoteFile) -> BTreeMap<String, Entry> {
+    fn collect(
+        items: &[NoteFileItem],
+        parent: Option<&str>,
+        entries: &mut BTreeMap<String, Entry>,
+    ) {
+        // Placeholders have no id of their own to be told apart by
+        let items = items.iter().filter(|item| !item.is_placeholder());
+        for (position, item) in items.enumerate() {
+            entries.insert(
+                item.id.clone(),
+                Entry {
+                    parent: parent.map(str::to_string),
+                    position,
+                    title: item.title.clone(),
+                    body: item.body.clone().unwrap_or_default(),
+                    is_folder: item.is_folder,
+                    modified: item.modified,
+                },
+            );
+            collect(
+                item.children.as_deref().unwrap_or(&[]),
+                Some(&item.id),
+                entries,
+            );
+        }
+    }
+
+    let mut entries = BTreeMap::new();
+    collect(
+        note_file.children.as_deref().unwrap_or(&[]),
+        None,
+        &mut entries,
+    );
+    entries
+}
+
+// Whatever changed on one side only, None when both sides changed it differently
+fn pick<T: PartialEq + Clone>(base: Option<&T>, local: &T, remote: &T) -> Option<T> {
+    if local == remote || base == Some(remote) {
+        Some(local.clone())
+    } else if base == Some(local) {
+        Some(remote.clone())
+    } else {
+        None
+    }
+}
+
+// Three-way merge of two versions of the notebook by note id, against the version both started
+// from. Notes changed on both sides keep whichever was modified last, the local one on a tie,
+// and notes deleted on one side and edited on the other are kept.
+pub fn merge(base: Option<&NoteFile>, local: &NoteFile, remote: &NoteFile) -> Merged {
+    let base_entries = base.map(entries).unwrap_or_default();
+    let local_entries = entries(local);
+    let remote_entries = entries(remote);
+
+    let ids: BTreeSet<&String> = local_entries.keys().chain(remote_entries.keys()).collect();
+    let mut merged = BTreeMap::new();
+    let mut conflicts = Vec::new();
+    for id in ids {
+        let base_entry = base_entries.get(id);
+        let entry = match (local_entries.get(id), remote_entries.get(id)) {
+            (Some(local_entry), Some(remote_entry)) => {
+                let content = pick(
+                    base_entry.map(Entry::content).as_ref(),
+                    &local_entry.content(),
+                    &remote_entry.content(),
+                )
+                .unwrap_or_else(|| {
+                    let kept_remote = remote_entry.modified > local_entry.modified;
+                    conflicts.push(Conflict {
+                        id: id.clone(),
+                        local: local_entry.content(),
+                        remote: remote_entry.content(),
+                        kept_remote,
+                    });
+                    if kept_remote {
+                        remote_entry.content()
+                    } else {
+                        local_entry.content()
+                    }
+                });
+                let (parent, position) = pick(
+                    base_entry.map(Entry::placement).as_ref(),
+                    &local_entry.placement(),
+                    &remote_entry.placement(),
+                )
+                .unwrap_or_else(|| local_entry.placement());
+                Entry {
+                    parent,
+                    position,
+                    title: content.0,
+                    body: content.1,
+                    is_folder: local_entry.is_folder,
+                    modified: local_entry.modified.max(remote_entry.modified),
+                }
+            }
+            // Gone from one side, which only counts as a deletion if the other left it alone
+            (Some(entry), None) | (None, Some(entry)) => match base_entry {
+                Some(base_entry) if base_entry.content() == entry.content() => continue,
+                _ => entry.clone(),
+            },
+            (None, None) => continue,
+        };
+        merged.insert(id.clone(), entry);
+    }
+
+    let smart_folders = pick(
+        base.map(|base| &base.smart_folders),
+        &local.smart_folders,
+        &remote.smart_folders,
+    )
+    .unwrap_or_else(|| local.smart_folders.clone());
+
+    Merged {
+        note_file: NoteFile {
+            children: Some(build_tree(merged)),
+            smart_folders,
+        },
+        conflicts,
+    }
+}
+
+// Whether two versions of the notebook hold the same notes in the same places
+pub fn same_notes(a: &NoteFile, b: &NoteFile) -> bool {
+    entries(a) == entries(b) && a.smart_folders == b.smart_folders
+}
+
+// The changes another program made to the notebook on disk since it was last read or written
+// here, merged into the notebook as it is now. Nothing when the disk still holds what we know
+// of, as after our own saves.
+pub fn changes_on_disk(
+    known: Option<&NoteFile>,
+    current: &NoteFile,
+    on_disk: &NoteFile,
+) -> Option<Merged> {
+    if known.is_some_and(|known| same_notes(known, on_disk)) {
+        return None;
+    }
+    Some(merge(known, current, on_disk))
+}
+
+// Puts entries back into a tree, moving anything whose folder is gone (or that ended up inside
+// itself through moves on both sides) to the top level
+fn build_tree(mut entries: BTreeMap<String, Entry>) -> Vec<NoteFileItem> {
+    fn children_of(
+        parent: Option<&str>,
+        entries: &BTreeMap<String, Entry>,
+        placed: &mut BTreeSet<String>,
+    ) -> Vec<NoteFileItem> {
+        let mut children: Vec<(&String, &Entry)> = entries
+            .iter()
+            .filter(|(_, entry)| entry.parent.as_deref() == parent)
+            .collect();
+        children.sort_by(|(a_id, a), (b_id, b)| (a.position, a_id).cmp(&(b.position, b_id)));
+        let mut items = Vec::new();
+        for (id, entry) in children {
+            if !placed.insert(id.to_string()) {
+                continue;
+            }
+            items.push(NoteFileItem {
+                title: entry.title.clone(),
+                body: Some(entry.body.clone()),
+                children: if entry.is_folder {
+                    Some(children_of(Some(id), entries, placed))
+                } else {
+                    None
+                },
+                is_folder: entry.is_folder,
+                modified: entry.modified,
+                id: id.clone(),
+            });
+        }
+        items
+    }
+
+    let ids: BTreeSet<String> = entries.keys().cloned().collect();
+    for entry in entries.values_mut() {
+        if entry
+            .parent
+            .as_ref()
+            .is_some_and(|parent| !ids.contains(parent))
+        {
+            entry.parent = None;
+        }
+    }
+
+    loop {
+        let mut placed = BTreeSet::new();
+        let tree = children_of(None, &entries, &mut placed);
+        match entries.keys().find(|id| !placed.contains(*id)).cloned() {
+            Some(unplaced) => entries.get_mut(&unplaced).unwrap().parent = None,
+            None => return tree,
+        }
+    }
+}
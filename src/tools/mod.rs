@@ -1,2 +1,28 @@
+pub mod attachments;
+pub mod checklist;
+pub mod clipboard;
+pub mod commands;
+pub mod crypto;
+pub mod diff;
+pub mod due_date;
+pub mod emphasis;
+pub mod find;
+pub mod folder_storage;
+pub mod fuzzy;
+pub mod git_sync;
+pub mod history;
+pub mod images;
 pub mod io;
+pub mod keyring;
+pub mod links;
 pub mod logging;
+pub mod merge;
+pub mod search;
+pub mod settings;
+pub mod sqlite_storage;
+pub mod storage;
+pub mod tags;
+pub mod task_export;
+pub mod tasks;
+pub mod webdav_sync;
+pub mod wiki_links;
@@ -0,0 +1,97 @@
+use crate::tools::{
+    checklist,
+    io::{NoteFile, NoteFileItem, NoteRef},
+    tags::{self, TAG_PREFIX},
+};
+use regex::RegexBuilder;
+
+// Every condition that is set has to hold for a note to match
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SearchQuery {
+    // Case-insensitive, looked for in the title and the body
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    // Seconds since the Unix epoch
+    #[serde(default)]
+    pub modified_since: Option<i64>,
+    #[serde(default)]
+    pub has_unchecked_tasks: bool,
+}
+
+// A saved search shown as a folder holding whichever notes match it at the time
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SmartFolder {
+    pub title: String,
+    pub query: SearchQuery,
+}
+
+impl SearchQuery {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none()
+            && self.regex.is_none()
+            && self.tag.is_none()
+            && self.modified_since.is_none()
+            && !self.has_unchecked_tasks
+    }
+
+    pub fn matches(&self, item: &NoteFileItem) -> bool {
+        if item.is_folder {
+            return false;
+        }
+        let body = item.body.as_deref().unwrap_or("");
+
+        if let Some(text) = &self.text {
+            let text = text.to_lowercase();
+            if !item.title.to_lowercase().contains(&text) && !body.to_lowercase().contains(&text) {
+                return false;
+            }
+        }
+
+        if let Some(pattern) = &self.regex {
+            // A pattern that doesn't compile matches nothing rather than everything
+            let matched = RegexBuilder::new(pattern)
+                .multi_line(true)
+                .build()
+                .map(|regex| regex.is_match(&item.title) || regex.is_match(body))
+                .unwrap_or(false);
+            if !matched {
+                return false;
+            }
+        }
+
+        if let Some(tag) = &self.tag {
+            let tag = tag.trim_start_matches(TAG_PREFIX);
+            if !tags::find(body).iter().any(|found| found.name == tag) {
+                return false;
+            }
+        }
+
+        if let Some(modified_since) = self.modified_since {
+            match item.modified {
+                Some(modified) if modified >= modified_since => {}
+                _ => return false,
+            }
+        }
+
+        if self.has_unchecked_tasks {
+            let progress = checklist::progress(body);
+            if progress.done == progress.total {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+pub fn matching_notes<'a>(note_file: &'a NoteFile, query: &SearchQuery) -> Vec<NoteRef<'a>> {
+    note_file
+        .notes()
+        .into_iter()
+        .filter(|note| query.matches(note.item))
+        .collect()
+}
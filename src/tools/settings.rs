@@ -0,0 +1,42 @@
+use crate::tools::storage::StorageKind;
+use std::{fs, path::Path};
+
+// Choices that belong to this machine rather than to the notebook
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Settings {
+    // Where the notebook is pushed to and pulled from when it is kept under git
+    #[serde(default)]
+    pub git_remote: Option<String>,
+    // The WebDAV collection the notebook is synced with, and who to sign in as
+    #[serde(default)]
+    pub webdav_url: Option<String>,
+    #[serde(default)]
+    pub webdav_username: Option<String>,
+    // Kept in the keyring, and only read from here to move it there from older settings files
+    #[serde(default, skip_serializing)]
+    pub webdav_password: Option<String>,
+    // How the notebook is kept on disk, and the directory it is kept in as separate files
+    #[serde(default)]
+    pub storage: StorageKind,
+    #[serde(default)]
+    pub notes_dir: Option<String>,
+}
+
+impl Settings {
+    pub fn load(path: &str) -> Result<Settings, Box<dyn std::error::Error>> {
+        if !Path::new(path).exists() {
+            return Ok(Settings::default());
+        }
+        let data =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+        let settings = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to deserialize file {}: {}", path, e))?;
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = serde_json::to_string_pretty(&self)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        fs::write(path, data).map_err(|e| format!("Failed to write file {}: {}", path, e).into())
+    }
+}
@@ -0,0 +1,449 @@
+use crate::tools::{
+    attachments,
+    io::{NoteFile, NoteFileItem},
+    search::SmartFolder,
+    storage::Storage,
+};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+const SCHEMA_VERSION: i32 = 2;
+const SCHEMA: &str = "
+    CREATE TABLE tree (
+        id TEXT PRIMARY KEY,
+        parent_id TEXT,
+        position INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        is_folder INTEGER NOT NULL
+    );
+    CREATE INDEX tree_parent ON tree (parent_id, position);
+    CREATE TABLE notes (
+        id TEXT PRIMARY KEY REFERENCES tree (id) ON DELETE CASCADE,
+        body TEXT NOT NULL,
+        modified INTEGER
+    );
+    -- Which attachments each note refers to, the files themselves stay in the attachment store
+    CREATE TABLE attachments (
+        note_id TEXT NOT NULL REFERENCES tree (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        PRIMARY KEY (note_id, name)
+    );
+    CREATE INDEX attachments_name ON attachments (name);
+    CREATE TABLE smart_folders (
+        position INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        query TEXT NOT NULL
+    );
+";
+// The first databases also had a full-text index, which nothing ever searched
+const DROP_FTS: &str = "DROP TABLE IF EXISTS notes_fts;";
+const SELECT_ROWS: &str = "
+    SELECT tree.id, parent_id, position, title, is_folder, body, modified
+    FROM tree LEFT JOIN notes ON notes.id = tree.id";
+
+// A note or folder as kept in the tree and notes tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    parent_id: Option<String>,
+    position: i64,
+    title: String,
+    is_folder: bool,
+    body: String,
+    modified: Option<i64>,
+}
+
+// The notebook in an SQLite database. Saving compares the notebook with what the database
+// holds and only writes the notes that changed, all in one transaction.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    path: PathBuf,
+    // Kept open, as the data version only counts changes made through other connections
+    connection: RefCell<Option<Connection>>,
+    // The data version as of the last load or save
+    data_version: Cell<Option<i64>>,
+}
+
+impl SqliteStorage {
+    pub fn new<P: Into<PathBuf>>(path: P) -> SqliteStorage {
+        SqliteStorage {
+            path: path.into(),
+            connection: RefCell::new(None),
+            data_version: Cell::new(None),
+        }
+    }
+
+    fn connection(&self) -> Result<RefMut<'_, Connection>, Box<dyn std::error::Error>> {
+        let mut connection = self.connection.borrow_mut();
+        if connection.is_none() {
+            *connection = Some(self.open()?);
+        }
+        Ok(RefMut::map(connection, |connection| {
+            connection.as_mut().expect("The connection was just opened")
+        }))
+    }
+
+    fn open(&self) -> Result<Connection, Box<dyn std::error::Error>> {
+        let open = || -> rusqlite::Result<Connection> {
+            let connection = Connection::open(&self.path)?;
+            connection.pragma_update(None, "foreign_keys", true)?;
+            let version: i32 =
+                connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+            if version < 1 {
+                connection.execute_batch(SCHEMA)?;
+            } else if version < 2 {
+                connection.execute_batch(DROP_FTS)?;
+            }
+            if version < SCHEMA_VERSION {
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            Ok(connection)
+        };
+        open().map_err(|e| format!("Failed to open {}: {}", self.path.display(), e).into())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn load(&self) -> Result<NoteFile, Box<dyn std::error::Error>> {
+        let connection = self.connection()?;
+        // Taken first, so changes made while loading are found by the next check
+        let version = data_version(&connection);
+        let load = || -> rusqlite::Result<NoteFile> {
+            let rows = read_rows(&connection)?;
+            let mut statement =
+                connection.prepare("SELECT title, query FROM smart_folders ORDER BY position")?;
+            let smart_folders = statement
+                .query_map([], |row| {
+                    let query: String = row.get(1)?;
+                    Ok(SmartFolder {
+                        title: row.get(0)?,
+                        query: serde_json::from_str(&query).unwrap_or_default(),
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<SmartFolder>>>()?;
+            Ok(NoteFile {
+                children: Some(build_tree(rows)),
+                smart_folders,
+            })
+        };
+        let note_file =
+            load().map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))?;
+        self.data_version.set(version.ok());
+        Ok(note_file)
+    }
+
+    fn save(&self, note_file: &NoteFile) -> Result<(), Box<dyn std::error::Error>> {
+        let mut connection = self.connection()?;
+        let version = data_version(&connection);
+        let mut save = || -> rusqlite::Result<()> {
+            let transaction = connection.transaction()?;
+            save_rows(&transaction, note_file)?;
+            save_smart_folders(&transaction, &note_file.smart_folders)?;
+            transaction.commit()
+        };
+        save().map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        self.data_version.set(version.ok());
+        Ok(())
+    }
+
+    fn watched(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+
+    // Reads and writes the rows of those notes alone, leaving the rest of the database as it is
+    fn save_notes(&self, notes: &[NoteFileItem]) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut connection = self.connection()?;
+        let version = data_version(&connection);
+        let mut save = || -> rusqlite::Result<bool> {
+            let transaction = connection.transaction()?;
+            let mut rows = Vec::new();
+            for note in notes {
+                let old = match read_row(&transaction, &note.id)? {
+                    Some(old) if old.is_folder == note.is_folder => old,
+                    // Added or turned into something else, which takes the whole notebook
+                    _ => return Ok(false),
+                };
+                let row = Row {
+                    title: note.title.clone(),
+                    body: note.body.clone().unwrap_or_default(),
+                    modified: note.modified,
+                    ..old.clone()
+                };
+                rows.push((&note.id, old, row));
+            }
+            for (id, old, row) in &rows {
+                write_row(&transaction, id, row, Some(old))?;
+            }
+            transaction.commit()?;
+            Ok(true)
+        };
+        let saved =
+            save().map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        if saved {
+            self.data_version.set(version.ok());
+        }
+        Ok(saved)
+    }
+
+    fn may_have_changed(&self) -> bool {
+        let known = match self.data_version.get() {
+            Some(known) => known,
+            None => return true,
+        };
+        match self.connection() {
+            Ok(connection) => data_version(&connection).ok() != Some(known),
+            Err(_) => true,
+        }
+    }
+}
+
+// Changes whenever another connection commits to the database
+fn data_version(connection: &Connection) -> rusqlite::Result<i64> {
+    connection.pragma_query_value(None, "data_version", |row| row.get(0))
+}
+
+fn row_from(row: &rusqlite::Row) -> rusqlite::Result<(String, Row)> {
+    Ok((
+        row.get(0)?,
+        Row {
+            parent_id: row.get(1)?,
+            position: row.get(2)?,
+            title: row.get(3)?,
+            is_folder: row.get(4)?,
+            body: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            modified: row.get(6)?,
+        },
+    ))
+}
+
+fn read_rows(connection: &Connection) -> rusqlite::Result<BTreeMap<String, Row>> {
+    let mut statement = connection.prepare(SELECT_ROWS)?;
+    let rows = statement.query_map([], row_from)?;
+    rows.collect()
+}
+
+fn read_row(connection: &Connection, id: &str) -> rusqlite::Result<Option<Row>> {
+    connection
+        .query_row(
+            &format!("{} WHERE tree.id = ?1", SELECT_ROWS),
+            params![id],
+            row_from,
+        )
+        .optional()
+        .map(|row| row.map(|(_, row)| row))
+}
+
+fn flatten(items: &[NoteFileItem], parent_id: Option<&str>, rows: &mut BTreeMap<String, Row>) {
+    // Placeholders have no id of their own to be kept by
+    let items = items.iter().filter(|item| !item.is_placeholder());
+    for (position, item) in items.enumerate() {
+        rows.insert(
+            item.id.clone(),
+            Row {
+                parent_id: parent_id.map(str::to_string),
+                position: position as i64,
+                title: item.title.clone(),
+                is_folder: item.is_folder,
+                body: if item.is_folder {
+                    String::new()
+                } else {
+                    item.body.clone().unwrap_or_default()
+                },
+                modified: if item.is_folder { None } else { item.modified },
+            },
+        );
+        if let Some(children) = &item.children {
+            flatten(children, Some(&item.id), rows);
+        }
+    }
+}
+
+// Writes the notes and folders that aren't in the database as they are, and removes the rest
+fn save_rows(transaction: &Transaction, note_file: &NoteFile) -> rusqlite::Result<()> {
+    let stored = read_rows(transaction)?;
+    let mut rows = BTreeMap::new();
+    flatten(
+        note_file.children.as_deref().unwrap_or(&[]),
+        None,
+        &mut rows,
+    );
+
+    for (id, row) in &rows {
+        write_row(transaction, id, row, stored.get(id))?;
+    }
+
+    for id in stored.keys().filter(|id| !rows.contains_key(*id)) {
+        transaction.execute("DELETE FROM tree WHERE id = ?1", params![id])?;
+    }
+    Ok(())
+}
+
+// Writes a note or folder that isn't in the database as it is, along with its attachments
+fn write_row(
+    transaction: &Transaction,
+    id: &str,
+    row: &Row,
+    old: Option<&Row>,
+) -> rusqlite::Result<()> {
+    if old == Some(row) {
+        return Ok(());
+    }
+    transaction.execute(
+        "INSERT INTO tree (id, parent_id, position, title, is_folder)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT (id) DO UPDATE SET parent_id = excluded.parent_id,
+            position = excluded.position, title = excluded.title,
+            is_folder = excluded.is_folder",
+        params![id, row.parent_id, row.position, row.title, row.is_folder],
+    )?;
+    let note_changed =
+        old.is_none_or(|old| old.is_folder || old.body != row.body || old.modified != row.modified);
+    if !row.is_folder && note_changed {
+        transaction.execute(
+            "INSERT INTO notes (id, body, modified) VALUES (?1, ?2, ?3)
+            ON CONFLICT (id) DO UPDATE SET body = excluded.body, modified = excluded.modified",
+            params![id, row.body, row.modified],
+        )?;
+    }
+
+    // Moving or renaming a note leaves the attachments its body refers to as they were
+    let body_changed = old.is_none_or(|old| old.body != row.body || old.is_folder != row.is_folder);
+    if body_changed {
+        transaction.execute("DELETE FROM attachments WHERE note_id = ?1", params![id])?;
+        if row.is_folder {
+            transaction.execute("DELETE FROM notes WHERE id = ?1", params![id])?;
+            return Ok(());
+        }
+        for name in attachments::referenced([row.body.as_str()]) {
+            transaction.execute(
+                "INSERT INTO attachments (note_id, name) VALUES (?1, ?2)",
+                params![id, name],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn save_smart_folders(
+    transaction: &Transaction,
+    smart_folders: &[SmartFolder],
+) -> rusqlite::Result<()> {
+    let mut statement =
+        transaction.prepare("SELECT title, query FROM smart_folders ORDER BY position")?;
+    let stored = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    let smart_folders: Vec<(String, String)> = smart_folders
+        .iter()
+        .map(|smart_folder| {
+            let query = serde_json::to_string(&smart_folder.query).unwrap_or_default();
+            (smart_folder.title.clone(), query)
+        })
+        .collect();
+    if stored == smart_folders {
+        return Ok(());
+    }
+
+    transaction.execute("DELETE FROM smart_folders", [])?;
+    for (position, (title, query)) in smart_folders.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO smart_folders (position, title, query) VALUES (?1, ?2, ?3)",
+            params![position as i64, title, query],
+        )?;
+    }
+    Ok(())
+}
+
+// Puts the rows back into a tree, with anything whose folder is missing at the top level
+fn build_tree(mut rows: BTreeMap<String, Row>) -> Vec<NoteFileItem> {
+    let folders: BTreeSet<String> = rows
+        .iter()
+        .filter(|(_, row)| row.is_folder)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for (id, row) in rows.iter_mut() {
+        if row
+            .parent_id
+            .as_ref()
+            .is_some_and(|parent_id| !folders.contains(parent_id) || parent_id == id)
+        {
+            row.parent_id = None;
+        }
+    }
+    // Folders inside each other in a circle can't be reached from the top, so one of each
+    // circle is moved there
+    while let Some(unreachable) = unreachable_row(&rows) {
+        rows.get_mut(&unreachable)
+            .expect("The row was just found")
+            .parent_id = None;
+    }
+
+    let mut children: BTreeMap<Option<&str>, Vec<(&String, &Row)>> = BTreeMap::new();
+    for (id, row) in &rows {
+        children
+            .entry(row.parent_id.as_deref())
+            .or_default()
+            .push((id, row));
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by_key(|(id, row)| (row.position, id.to_string()));
+    }
+
+    fn items_of(
+        parent_id: Option<&str>,
+        children: &BTreeMap<Option<&str>, Vec<(&String, &Row)>>,
+    ) -> Vec<NoteFileItem> {
+        children
+            .get(&parent_id)
+            .into_iter()
+            .flatten()
+            .map(|(id, row)| NoteFileItem {
+                title: row.title.clone(),
+                body: Some(row.body.clone()),
+                children: if row.is_folder {
+                    Some(items_of(Some(id.as_str()), children))
+                } else {
+                    None
+                },
+                is_folder: row.is_folder,
+                modified: row.modified,
+                id: id.to_string(),
+            })
+            .collect()
+    }
+
+    items_of(None, &children)
+}
+
+fn unreachable_row(rows: &BTreeMap<String, Row>) -> Option<String> {
+    let mut children: BTreeMap<Option<&str>, Vec<&str>> = BTreeMap::new();
+    for (id, row) in rows {
+        children
+            .entry(row.parent_id.as_deref())
+            .or_default()
+            .push(id);
+    }
+    let mut reached = BTreeSet::new();
+    let mut parents = vec![None];
+    while let Some(parent_id) = parents.pop() {
+        for id in children.get(&parent_id).into_iter().flatten() {
+            if reached.insert(*id) {
+                parents.push(Some(*id));
+            }
+        }
+    }
+    // Going up from any row that wasn't reached ends up going round the circle
+    let mut id = rows.keys().find(|id| !reached.contains(id.as_str()))?;
+    let mut passed = BTreeSet::new();
+    while passed.insert(id) {
+        id = rows[id].parent_id.as_ref()?;
+    }
+    Some(id.clone())
+}
@@ -0,0 +1,144 @@
+use crate::tools::{
+    crypto::{self, NotebookKey},
+    folder_storage::FolderStorage,
+    io::{NoteFile, NoteFileItem},
+    sqlite_storage::SqliteStorage,
+};
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// Where a notebook is read from and written to
+pub trait Storage: fmt::Debug {
+    // Whether there is a notebook there to load yet
+    fn exists(&self) -> bool;
+    fn load(&self) -> Result<NoteFile, Box<dyn std::error::Error>>;
+    fn save(&self, note_file: &NoteFile) -> Result<(), Box<dyn std::error::Error>>;
+    // Files and directories to watch for changes made by other programs
+    fn watched(&self) -> Vec<PathBuf>;
+
+    // Writes just these notes, when their titles and bodies are all that changed since the last
+    // save. Storages that can't write part of the notebook return false, and are saved whole.
+    fn save_notes(&self, _notes: &[NoteFileItem]) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(false)
+    }
+
+    // Whether another program may have changed the notebook since it was last loaded or saved
+    // here. Storages that can't tell without loading it always say so.
+    fn may_have_changed(&self) -> bool {
+        true
+    }
+}
+
+// What changed in the notebook since it was last saved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    // Notes whose title or body changed
+    pub note_ids: BTreeSet<String>,
+    // Whether anything was added, removed or moved, or the smart folders changed
+    pub structure: bool,
+}
+
+// The size and modified time of each file, which tell whether any was written without reading it
+pub type FileStamps = Vec<(PathBuf, Option<(u64, SystemTime)>)>;
+
+pub fn file_stamps(paths: Vec<PathBuf>) -> FileStamps {
+    paths
+        .into_iter()
+        .map(|path| {
+            let stamp = fs::metadata(&path)
+                .ok()
+                .and_then(|metadata| Some((metadata.len(), metadata.modified().ok()?)));
+            (path, stamp)
+        })
+        .collect()
+}
+
+// The whole notebook as one JSON file, encrypted when there is a key
+#[derive(Debug, Clone)]
+pub struct JsonStorage {
+    path: String,
+    key: Option<NotebookKey>,
+    // The file as last loaded or saved here
+    stamps: RefCell<Option<FileStamps>>,
+}
+
+impl JsonStorage {
+    pub fn new(path: &str) -> JsonStorage {
+        JsonStorage {
+            path: path.to_string(),
+            key: None,
+            stamps: RefCell::new(None),
+        }
+    }
+
+    pub fn encrypted(path: &str, key: NotebookKey) -> JsonStorage {
+        JsonStorage {
+            path: path.to_string(),
+            key: Some(key),
+            stamps: RefCell::new(None),
+        }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn exists(&self) -> bool {
+        Path::new(&self.path).exists()
+    }
+
+    fn load(&self) -> Result<NoteFile, Box<dyn std::error::Error>> {
+        // Taken first, so a write that comes in while reading is still seen as a change
+        let stamps = file_stamps(self.watched());
+        // Also keeps an encrypted notebook from being taken for an empty one without the key
+        let data = crypto::read_file(&self.path, self.key.as_ref())?;
+        let note_file = serde_json::from_slice(&data)
+            .map_err(|e| format!("Failed to deserialize file {}: {}", self.path, e))?;
+        self.stamps.replace(Some(stamps));
+        Ok(note_file)
+    }
+
+    fn save(&self, note_file: &NoteFile) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.key {
+            Some(key) => {
+                let data = serde_json::to_vec(note_file)
+                    .map_err(|e| format!("Failed to serialize NoteFile: {}", e))?;
+                crypto::write_file(&self.path, &data, Some(key))?;
+            }
+            None => note_file.save(&self.path)?,
+        }
+        self.stamps.replace(Some(file_stamps(self.watched())));
+        Ok(())
+    }
+
+    fn watched(&self) -> Vec<PathBuf> {
+        vec![PathBuf::from(&self.path)]
+    }
+
+    fn may_have_changed(&self) -> bool {
+        self.stamps.borrow().as_ref() != Some(&file_stamps(self.watched()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageKind {
+    #[default]
+    Json,
+    // A directory per folder and a Markdown file per note
+    Folder,
+    Sqlite,
+}
+
+impl StorageKind {
+    pub fn open(&self, json_path: &str, folder: &Path, database: &Path) -> Box<dyn Storage> {
+        match self {
+            StorageKind::Json => Box::new(JsonStorage::new(json_path)),
+            StorageKind::Folder => Box::new(FolderStorage::new(PathBuf::from(folder))),
+            StorageKind::Sqlite => Box::new(SqliteStorage::new(PathBuf::from(database))),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::tools::io::NoteFile;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{collections::BTreeMap, ops::Range};
+
+pub const TAG_PREFIX: char = '#';
+
+// A tag starts with a letter so headings like "#1" or colours like "#fff000" aren't picked up,
+// and must follow whitespace so the fragment of a URL isn't either
+static TAG_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:^|\s)(#(\p{L}[\p{L}\p{N}_/-]*))").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    // Covers the leading '#'
+    pub range: Range<usize>,
+    pub name: String,
+}
+
+pub fn find(text: &str) -> Vec<Tag> {
+    TAG_RE
+        .captures_iter(text)
+        .map(|captures| Tag {
+            range: captures.get(1).unwrap().range(),
+            name: captures[2].to_string(),
+        })
+        .collect()
+}
+
+// Whether the name makes up one whole tag, as a rename has to leave one behind
+pub fn is_valid_name(name: &str) -> bool {
+    let text = format!("{}{}", TAG_PREFIX, name);
+    match find(&text).as_slice() {
+        [tag] => tag.range == (0..text.len()) && tag.name == name,
+        _ => false,
+    }
+}
+
+pub fn has_all(body: &str, tag_names: &[String]) -> bool {
+    let tags = find(body);
+    tag_names
+        .iter()
+        .all(|tag_name| tags.iter().any(|tag| &tag.name == tag_name))
+}
+
+// Every tag in the notebook with the paths of the notes carrying it
+pub fn index(note_file: &NoteFile) -> BTreeMap<String, Vec<Vec<i32>>> {
+    let mut index: BTreeMap<String, Vec<Vec<i32>>> = BTreeMap::new();
+    for note in note_file.notes() {
+        let mut names: Vec<String> = find(note.item.body.as_deref().unwrap_or(""))
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            index.entry(name).or_default().push(note.path.clone());
+        }
+    }
+    index
+}
+
+// Renames the tag throughout a note, which merges it into new_name when that tag already exists.
+// None when the body doesn't carry the tag
+pub fn rename(body: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let tags: Vec<Tag> = find(body)
+        .into_iter()
+        .filter(|tag| tag.name == old_name)
+        .collect();
+    if tags.is_empty() {
+        return None;
+    }
+
+    let mut renamed = String::new();
+    let mut last_end = 0;
+    for tag in tags {
+        renamed.push_str(&body[last_end..tag.range.start]);
+        renamed.push(TAG_PREFIX);
+        renamed.push_str(new_name);
+        last_end = tag.range.end;
+    }
+    renamed.push_str(&body[last_end..]);
+    Some(renamed)
+}
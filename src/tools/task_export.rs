@@ -0,0 +1,164 @@
+use crate::tools::{
+    checklist,
+    due_date::{self, DueDate},
+    tasks::Task,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// iCalendar content lines may not be longer than this many bytes
+const ICAL_LINE_LIMIT: usize = 75;
+
+static TODO_TXT_PREFIX_RE: Lazy<Regex> = Lazy::new(|| {
+    // Completion mark, priority and the completion/creation dates that todo.txt puts up front
+    Regex::new(r"^(x )?(?:\([A-Z]\) )?(?:[0-9]{4}-[0-9]{2}-[0-9]{2} ){0,2}").unwrap()
+});
+
+fn ical_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Splits long content lines by continuing them on lines starting with a space
+fn ical_fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_length = 0;
+    for c in line.chars() {
+        if line_length + c.len_utf8() > ICAL_LINE_LIMIT {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+        folded.push(c);
+        line_length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+fn ical_date_time(due: &DueDate) -> String {
+    match due.time {
+        Some((hour, minute)) => format!(
+            "{:04}{:02}{:02}T{:02}{:02}00",
+            due.year, due.month, due.day, hour, minute
+        ),
+        None => format!("{:04}{:02}{:02}", due.year, due.month, due.day),
+    }
+}
+
+// Calendars want the time a calendar was made in UTC, down to the second
+fn ical_utc_date_time(stamp: &DueDate) -> String {
+    let (hour, minute) = stamp.reminder().time.unwrap_or_default();
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}00Z",
+        stamp.year, stamp.month, stamp.day, hour, minute
+    )
+}
+
+// Stays the same when the task moves around its note or the note around the notebook, so
+// importing again updates tasks rather than adding them twice
+fn ical_uid(task: &Task, occurrence: usize) -> String {
+    let hash: String = Sha256::digest(due_date::strip(&task.text).as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let mut uid = format!("gnote-{}-{}", task.note_id, hash);
+    // Tasks of a note that read the same are told apart by their order
+    if occurrence > 0 {
+        uid.push_str(&format!("-{}", occurrence));
+    }
+    uid.push_str("@org.bil4x4.gnote");
+    uid
+}
+
+// Project and context names can't contain spaces in todo.txt
+fn todo_txt_tag(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+// The stamp is the current time in UTC
+pub fn to_ical(tasks: &[Task], stamp: &DueDate) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//bil4x4//Gnote//EN".to_string(),
+    ];
+
+    let mut occurrences: HashMap<(&str, String), usize> = HashMap::new();
+    for task in tasks {
+        let occurrence = occurrences
+            .entry((task.note_id.as_str(), due_date::strip(&task.text)))
+            .or_default();
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", ical_uid(task, *occurrence)));
+        *occurrence += 1;
+        lines.push(format!("DTSTAMP:{}", ical_utc_date_time(stamp)));
+        lines.push(format!(
+            "SUMMARY:{}",
+            ical_escape(&due_date::strip(&task.text))
+        ));
+        lines.push(format!("DESCRIPTION:{}", ical_escape(&task.location())));
+        if !task.folders.is_empty() {
+            let categories: Vec<String> = task
+                .folders
+                .iter()
+                .map(|folder| ical_escape(folder))
+                .collect();
+            lines.push(format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(due) = &task.due {
+            match due.time {
+                Some(_) => lines.push(format!("DUE:{}", ical_date_time(due))),
+                None => lines.push(format!("DUE;VALUE=DATE:{}", ical_date_time(due))),
+            }
+        }
+        if task.checked {
+            lines.push("STATUS:COMPLETED".to_string());
+        } else {
+            lines.push("STATUS:NEEDS-ACTION".to_string());
+        }
+        lines.push("END:VTODO".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| ical_fold(line)).collect()
+}
+
+pub fn to_todo_txt(tasks: &[Task]) -> String {
+    let mut todo_txt = String::new();
+    for task in tasks {
+        if task.checked {
+            todo_txt.push_str("x ");
+        }
+        todo_txt.push_str(&due_date::strip(&task.text));
+        if let Some(folder) = task.folders.last() {
+            todo_txt.push_str(&format!(" +{}", todo_txt_tag(folder)));
+        }
+        todo_txt.push_str(&format!(" @{}", todo_txt_tag(&task.note_title)));
+        if let Some(due) = &task.due {
+            // todo.txt only knows about dates
+            todo_txt.push_str(&format!(" {}{}", due_date::DUE_PREFIX, due.date()));
+        }
+        todo_txt.push('\n');
+    }
+    todo_txt
+}
+
+// Turns a todo.txt file into the body of a checklist note
+pub fn from_todo_txt(todo_txt: &str) -> String {
+    todo_txt
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let prefix = TODO_TXT_PREFIX_RE.captures(line).unwrap();
+            let checked = prefix.get(1).is_some();
+            let text = &line[prefix.get(0).unwrap().end()..];
+            checklist::format_check_box_line(0, checked, text.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,88 @@
+use crate::tools::{
+    checklist,
+    due_date::{self, DueDate},
+    io::NoteFile,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    pub folders: Vec<String>,
+    pub note_title: String,
+    // Child indices from the root of the notebook down to the note, matching the tree paths
+    pub note_path: Vec<i32>,
+    pub note_id: String,
+    pub line: usize,
+    pub indent_level: usize,
+    pub checked: bool,
+    pub text: String,
+    pub due: Option<DueDate>,
+}
+
+impl Task {
+    pub fn location(&self) -> String {
+        let mut location = self.folders.join(" / ");
+        if !location.is_empty() {
+            location.push_str(" › ");
+        }
+        location.push_str(&self.note_title);
+        location
+    }
+}
+
+pub fn collect(note_file: &NoteFile) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    for note in note_file.notes() {
+        let body = note.item.body.as_deref().unwrap_or("");
+        for (line, text) in body.lines().enumerate() {
+            if let Some(check_box) = checklist::parse_check_box_line(text) {
+                tasks.push(Task {
+                    folders: note.folders.clone(),
+                    note_title: note.item.title.clone(),
+                    note_path: note.path.clone(),
+                    note_id: note.item.id.clone(),
+                    line,
+                    indent_level: check_box.indent_level,
+                    checked: check_box.checked,
+                    text: check_box.text.to_string(),
+                    due: due_date::find(check_box.text).map(|(_, due)| due),
+                });
+            }
+        }
+    }
+    tasks
+}
+
+pub fn open_tasks(note_file: &NoteFile) -> Vec<Task> {
+    collect(note_file)
+        .into_iter()
+        .filter(|task| !task.checked)
+        .collect()
+}
+
+// Open tasks that carry a due date, soonest first
+pub fn upcoming(note_file: &NoteFile) -> Vec<Task> {
+    let mut upcoming: Vec<Task> = open_tasks(note_file)
+        .into_iter()
+        .filter(|task| task.due.is_some())
+        .collect();
+    upcoming.sort_by_key(|task| task.due.map(|due| due.reminder()));
+    upcoming
+}
+
+// Flips the check box on the given line, returns None when that line isn't a check box
+pub fn toggle_line(body: &str, line: usize) -> Option<String> {
+    let mut lines: Vec<String> = body.lines().map(String::from).collect();
+    let check_box = checklist::parse_check_box_line(lines.get(line)?)?;
+    let toggled_line = checklist::format_check_box_line(
+        check_box.indent_level,
+        !check_box.checked,
+        check_box.text,
+    );
+    lines[line] = toggled_line;
+
+    let mut toggled = lines.join("\n");
+    if body.ends_with('\n') {
+        toggled.push('\n');
+    }
+    Some(toggled)
+}
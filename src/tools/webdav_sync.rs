@@ -0,0 +1,319 @@
+use crate::tools::{
+    attachments::{self, AttachmentStore},
+    io::NoteFile,
+    merge::{self, Conflict},
+};
+use base64::{engine::general_purpose, Engine};
+use std::{collections::BTreeSet, fmt, fs, io::Read, path::Path};
+
+const NOTES_FILE: &str = "notes.json";
+const ATTACHMENTS_DIR: &str = "attachments";
+// Downloads and uploads in a row before giving up on a server that keeps changing under us
+const UPLOAD_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncError {
+    // The server couldn't be reached, so changes wait for the next sync
+    Offline(String),
+    Failed(String),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::Offline(message) | SyncError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+// What this machine last agreed on with the server, kept between runs. Edits made while offline
+// need nothing more to be queued: merging against the base works out what changed.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct SyncState {
+    // The notebook as it was last downloaded or uploaded
+    #[serde(default)]
+    pub base: Option<NoteFile>,
+    #[serde(default)]
+    pub etag: Option<String>,
+    // Attachments known to be on the server
+    #[serde(default)]
+    pub uploaded: BTreeSet<String>,
+}
+
+impl SyncState {
+    pub fn load(path: &str) -> Result<SyncState, Box<dyn std::error::Error>> {
+        if !Path::new(path).exists() {
+            return Ok(SyncState::default());
+        }
+        let data =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+        let state = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to deserialize file {}: {}", path, e))?;
+        Ok(state)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = serde_json::to_string(&self)
+            .map_err(|e| format!("Failed to serialize sync state: {}", e))?;
+        fs::write(path, data).map_err(|e| format!("Failed to write file {}: {}", path, e).into())
+    }
+
+    // Whether the notebook has changes the server hasn't seen yet
+    pub fn has_pending(&self, note_file: &NoteFile) -> bool {
+        match &self.base {
+            Some(base) => {
+                !merge::same_notes(base, note_file)
+                    || !attachments::referenced_by_notes(note_file).is_subset(&self.uploaded)
+            }
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Synced {
+    // The notebook as it now is on the server
+    pub note_file: NoteFile,
+    pub conflicts: Vec<Conflict>,
+    pub state: SyncState,
+}
+
+enum Download {
+    // Still the version with the ETag we asked about
+    Unchanged,
+    Missing,
+    Found(Vec<u8>, Option<String>),
+}
+
+enum Upload {
+    Stored(Option<String>),
+    // Someone else got there first
+    Changed,
+}
+
+// A WebDAV collection holding the notebook file and an attachments collection beside it
+#[derive(Debug, Clone)]
+pub struct WebDav {
+    url: String,
+    authorization: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl WebDav {
+    pub fn new(url: &str, username: Option<&str>, password: Option<&str>) -> WebDav {
+        let authorization = username.map(|username| {
+            let credentials = format!("{}:{}", username, password.unwrap_or(""));
+            format!("Basic {}", general_purpose::STANDARD.encode(credentials))
+        });
+        WebDav {
+            url: format!("{}/", url.trim_end_matches('/')),
+            authorization,
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    fn request(&self, method: &str, name: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}{}", self.url, name));
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    fn get(&self, name: &str, etag: Option<&str>) -> Result<Download, SyncError> {
+        let mut request = self.request("GET", name);
+        if let Some(etag) = etag {
+            request = request.set("If-None-Match", etag);
+        }
+        let response = match request.call() {
+            Ok(response) if response.status() == 304 => return Ok(Download::Unchanged),
+            Ok(response) => response,
+            Err(ureq::Error::Status(304, _)) => return Ok(Download::Unchanged),
+            Err(ureq::Error::Status(404, _)) => return Ok(Download::Missing),
+            Err(e) => return Err(request_error(name, e)),
+        };
+        let etag = response.header("ETag").map(str::to_string);
+        let mut content = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut content)
+            .map_err(|e| SyncError::Offline(format!("Failed to download {}: {}", name, e)))?;
+        Ok(Download::Found(content, etag))
+    }
+
+    // Only stores the content if the file still has the ETag, or doesn't exist yet without one
+    fn put(&self, name: &str, content: &[u8], etag: Option<&str>) -> Result<Upload, SyncError> {
+        let request = match etag {
+            Some(etag) => self.request("PUT", name).set("If-Match", etag),
+            None => self.request("PUT", name).set("If-None-Match", "*"),
+        };
+        match request.send_bytes(content) {
+            Ok(response) => Ok(Upload::Stored(response.header("ETag").map(str::to_string))),
+            Err(ureq::Error::Status(412, _)) => Ok(Upload::Changed),
+            Err(e) => Err(request_error(name, e)),
+        }
+    }
+
+    // Servers may leave the ETag out of the reply to an upload
+    fn etag(&self, name: &str) -> Result<Option<String>, SyncError> {
+        match self.request("HEAD", name).call() {
+            Ok(response) => Ok(response.header("ETag").map(str::to_string)),
+            Err(e) => Err(request_error(name, e)),
+        }
+    }
+
+    fn make_collection(&self, name: &str) -> Result<(), SyncError> {
+        match self.request("MKCOL", name).call() {
+            // Already there
+            Ok(_) | Err(ureq::Error::Status(405, _)) => Ok(()),
+            Err(e) => Err(request_error(name, e)),
+        }
+    }
+
+    // Brings the notebook and the server in step: downloads what changed there, merges it note
+    // by note with what changed here since the last sync, and uploads the result
+    pub fn sync(
+        &self,
+        local: &NoteFile,
+        state: &SyncState,
+        store: &AttachmentStore,
+    ) -> Result<Synced, SyncError> {
+        let mut state = state.clone();
+        let mut note_file = local.clone();
+        let mut conflicts: Vec<Conflict> = Vec::new();
+
+        for _ in 0..UPLOAD_ATTEMPTS {
+            // A notebook we don't have the base of has to be downloaded whatever its ETag
+            let known_etag = state.base.as_ref().and(state.etag.as_deref());
+            let (remote, etag) = match self.get(NOTES_FILE, known_etag)? {
+                Download::Unchanged => (state.base.clone(), state.etag.clone()),
+                Download::Missing => (None, None),
+                Download::Found(content, etag) => {
+                    let remote: NoteFile = serde_json::from_slice(&content).map_err(|e| {
+                        SyncError::Failed(format!("Failed to read {}: {}", NOTES_FILE, e))
+                    })?;
+                    let merged = merge::merge(state.base.as_ref(), &note_file, &remote);
+                    for conflict in merged.conflicts {
+                        conflicts.retain(|known| known.id != conflict.id);
+                        conflicts.push(conflict);
+                    }
+                    note_file = merged.note_file;
+                    (Some(remote), etag)
+                }
+            };
+            state.base = remote.clone();
+            state.etag = etag.clone();
+
+            // Notes never refer to attachments the server doesn't have
+            self.upload_attachments(&note_file, &mut state, store)?;
+            if remote.is_some_and(|remote| merge::same_notes(&remote, &note_file)) {
+                self.download_attachments(&note_file, &mut state, store)?;
+                return Ok(Synced {
+                    note_file,
+                    conflicts,
+                    state,
+                });
+            }
+
+            let content = serde_json::to_vec_pretty(&note_file).map_err(|e| {
+                SyncError::Failed(format!("Failed to serialize {}: {}", NOTES_FILE, e))
+            })?;
+            if let Upload::Stored(etag) = self.put(NOTES_FILE, &content, etag.as_deref())? {
+                state.etag = match etag {
+                    Some(etag) => Some(etag),
+                    None => self.etag(NOTES_FILE)?,
+                };
+                state.base = Some(note_file.clone());
+                self.download_attachments(&note_file, &mut state, store)?;
+                return Ok(Synced {
+                    note_file,
+                    conflicts,
+                    state,
+                });
+            }
+        }
+        Err(SyncError::Failed(format!(
+            "{} kept changing on the server, try again later",
+            NOTES_FILE
+        )))
+    }
+
+    fn upload_attachments(
+        &self,
+        note_file: &NoteFile,
+        state: &mut SyncState,
+        store: &AttachmentStore,
+    ) -> Result<(), SyncError> {
+        let names: Vec<String> = attachments::referenced_by_notes(note_file)
+            .into_iter()
+            .filter(|name| !state.uploaded.contains(name) && store.path(name).exists())
+            .collect();
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        self.make_collection(ATTACHMENTS_DIR)?;
+        for name in names {
+            let path = store.path(&name);
+            let content = fs::read(&path).map_err(|e| {
+                SyncError::Failed(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            // Attachments never change, so one that is already there is the same
+            self.put(&format!("{}/{}", ATTACHMENTS_DIR, name), &content, None)?;
+            state.uploaded.insert(name);
+        }
+        Ok(())
+    }
+
+    fn download_attachments(
+        &self,
+        note_file: &NoteFile,
+        state: &mut SyncState,
+        store: &AttachmentStore,
+    ) -> Result<(), SyncError> {
+        for name in attachments::referenced_by_notes(note_file) {
+            if store.path(&name).exists() {
+                continue;
+            }
+            let content = match self.get(&format!("{}/{}", ATTACHMENTS_DIR, name), None)? {
+                Download::Found(content, _) => content,
+                // Not uploaded yet from wherever it was attached
+                _ => continue,
+            };
+            let extension = name.split_once('.').map_or("", |(_, extension)| extension);
+            let stored = store
+                .add(&content, extension)
+                .map_err(|e| SyncError::Failed(e.to_string()))?;
+            if stored != name {
+                return Err(SyncError::Failed(format!(
+                    "The server's copy of attachment {} is damaged",
+                    name
+                )));
+            }
+            state.uploaded.insert(name);
+        }
+        Ok(())
+    }
+}
+
+fn request_error(name: &str, error: ureq::Error) -> SyncError {
+    match error {
+        ureq::Error::Status(401, _) | ureq::Error::Status(403, _) => SyncError::Failed(format!(
+            "The server refused access to {}, check the user name and password",
+            name
+        )),
+        ureq::Error::Status(status, response) => SyncError::Failed(format!(
+            "The server answered {} {} for {}",
+            status,
+            response.status_text(),
+            name
+        )),
+        ureq::Error::Transport(transport) => {
+            SyncError::Offline(format!("Failed to reach the server: {}", transport))
+        }
+    }
+}
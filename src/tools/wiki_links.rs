@@ -0,0 +1,102 @@
+use crate::tools::io::NoteFile;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+
+static WIKI_LINK_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\[([^\[\]\n]+)\]\]").unwrap());
+// An unfinished link right before the cursor, e.g. "see [[Meeting no"
+static OPEN_WIKI_LINK_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\[([^\[\]\n]*)$").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    pub range: Range<usize>,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlink {
+    pub note_path: Vec<i32>,
+    pub title: String,
+}
+
+pub fn format(title: &str) -> String {
+    format!("[[{}]]", title)
+}
+
+pub fn find(text: &str) -> Vec<WikiLink> {
+    WIKI_LINK_RE
+        .captures_iter(text)
+        .map(|captures| WikiLink {
+            range: captures.get(0).unwrap().range(),
+            title: captures[1].trim().to_string(),
+        })
+        .collect()
+}
+
+// The note title for the text of a link, as styled in the note
+pub fn title_for(link_text: &str) -> Option<String> {
+    find(link_text)
+        .into_iter()
+        .find(|link| link.range == (0..link_text.len()))
+        .map(|link| link.title)
+}
+
+// What has been typed of the title so far when the cursor is inside an unfinished link
+pub fn partial_title(text_before_cursor: &str) -> Option<&str> {
+    OPEN_WIKI_LINK_RE
+        .captures(text_before_cursor)
+        .map(|captures| captures.get(1).unwrap().as_str())
+}
+
+pub fn completions<'a>(partial_title: &str, titles: &'a [String], limit: usize) -> Vec<&'a str> {
+    let partial_title = partial_title.to_lowercase();
+    let mut matches: Vec<&str> = titles
+        .iter()
+        .map(String::as_str)
+        .filter(|title| title.to_lowercase().contains(&partial_title))
+        .collect();
+
+    // Titles starting with what was typed come first
+    matches.sort_by_key(|title| {
+        let title = title.to_lowercase();
+        (!title.starts_with(&partial_title), title)
+    });
+    matches.dedup();
+    matches.truncate(limit);
+    matches
+}
+
+// Points every link to old_title at new_title, None when the body has no such links
+pub fn rename(body: &str, old_title: &str, new_title: &str) -> Option<String> {
+    let links = find(body);
+    if !links.iter().any(|link| link.title == old_title) {
+        return None;
+    }
+
+    let mut renamed = String::new();
+    let mut last_end = 0;
+    for link in links.iter().filter(|link| link.title == old_title) {
+        renamed.push_str(&body[last_end..link.range.start]);
+        renamed.push_str(&format(new_title));
+        last_end = link.range.end;
+    }
+    renamed.push_str(&body[last_end..]);
+    Some(renamed)
+}
+
+// Every note, other than the note itself, with a link to the title
+pub fn backlinks(note_file: &NoteFile, title: &str) -> Vec<Backlink> {
+    note_file
+        .notes()
+        .into_iter()
+        .filter(|note| note.item.title != title)
+        .filter(|note| {
+            let body = note.item.body.as_deref().unwrap_or("");
+            find(body).iter().any(|link| link.title == title)
+        })
+        .map(|note| Backlink {
+            note_path: note.path,
+            title: note.item.title.clone(),
+        })
+        .collect()
+}
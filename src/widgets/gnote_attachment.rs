@@ -0,0 +1,131 @@
+use gtk::{
+    builders::FileChooserDialogBuilder,
+    gio,
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+    FileChooserAction, ResponseType,
+};
+use std::{cell::RefCell, fs, path::PathBuf};
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/attachment")]
+    pub struct GnoteAttachment {
+        #[template_child]
+        pub icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub file_name: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub file_size: TemplateChild<gtk::Label>,
+
+        // Where the attachment store keeps the file
+        pub path: RefCell<PathBuf>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteAttachment {
+        const NAME: &'static str = "GnoteAttachment";
+        type Type = super::GnoteAttachment;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+
+            klass.install_action("attachment.open", None, |attachment, _, _| {
+                attachment.open();
+            });
+            klass.install_action("attachment.save-as", None, |attachment, _, _| {
+                attachment.show_save_dialog();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteAttachment {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder("error")
+                    .param_types([<String>::static_type()])
+                    .build()]
+            });
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteAttachment {}
+    impl BoxImpl for GnoteAttachment {}
+}
+
+glib::wrapper! {
+    pub struct GnoteAttachment(ObjectSubclass<imp::GnoteAttachment>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+impl GnoteAttachment {
+    // A chip for the stored file at path, which was attached under the given file name
+    pub fn new(file_name: &str, path: PathBuf) -> Self {
+        let attachment = Object::new::<Self>(&[]);
+        let imp = attachment.imp();
+
+        let (content_type, _) = gio::content_type_guess(Some(file_name), &[]);
+        imp.icon
+            .set_from_gicon(&gio::content_type_get_symbolic_icon(&content_type));
+        imp.file_name.set_text(file_name);
+        // A file missing from the store still gets a chip, it just can't be opened
+        match fs::metadata(&path) {
+            Ok(metadata) => imp.file_size.set_text(&glib::format_size(metadata.len())),
+            Err(_) => imp.file_size.set_text("Missing"),
+        }
+        attachment.set_tooltip_text(Some(file_name));
+        imp.path.replace(path);
+        attachment
+    }
+
+    fn open(&self) {
+        let file = gio::File::for_path(&*self.imp().path.borrow());
+        let context = self.display().app_launch_context();
+        if let Err(e) = gio::AppInfo::launch_default_for_uri(&file.uri(), Some(&context)) {
+            let message = format!(
+                "Couldn't open {}: {}",
+                self.imp().file_name.text(),
+                e.message()
+            );
+            self.emit_by_name::<()>("error", &[&message]);
+        }
+    }
+
+    fn show_save_dialog(&self) {
+        let window = self
+            .root()
+            .and_then(|root| root.downcast::<gtk::Window>().ok());
+        let file_chooser = FileChooserDialogBuilder::new()
+            .title("Save attachment")
+            .action(FileChooserAction::Save)
+            .modal(true)
+            .build();
+        file_chooser.set_transient_for(window.as_ref());
+        file_chooser.set_current_name(&self.imp().file_name.text());
+        file_chooser.add_buttons(&[("Save", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+
+        file_chooser.connect_response(clone!(@weak self as attachment => move |dialog, response| {
+            if response == ResponseType::Ok {
+                if let Some(file_path) = dialog.file().and_then(|file| file.path()) {
+                    if let Err(e) = fs::copy(&*attachment.imp().path.borrow(), &file_path) {
+                        let message = format!("Couldn't save {}: {}", file_path.display(), e);
+                        attachment.emit_by_name::<()>("error", &[&message]);
+                    }
+                }
+            }
+            dialog.destroy();
+        }));
+
+        file_chooser.present();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::tools::commands::{self, Command};
+use gtk::{
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::cell::RefCell;
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/command_palette")]
+    pub struct GnoteCommandPalette {
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub results: TemplateChild<gtk::ListBox>,
+
+        // The command for every row in the results
+        pub rows: RefCell<Vec<&'static Command>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteCommandPalette {
+        const NAME: &'static str = "GnoteCommandPalette";
+        type Type = super::GnoteCommandPalette;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteCommandPalette {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("command-chosen")
+                        .param_types([<String>::static_type()])
+                        .build(),
+                    Signal::builder("cancelled").build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // The arrow keys move through the results while typing carries on in the entry
+            let palette = self.instance();
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.connect_key_pressed(
+                clone!(@weak palette => @default-return gtk::Inhibit(false), move |_, key, _, _| {
+                    let step = match key {
+                        gtk::gdk::Key::Down => 1,
+                        gtk::gdk::Key::Up => -1,
+                        _ => return gtk::Inhibit(false),
+                    };
+                    palette.move_selection(step);
+                    gtk::Inhibit(true)
+                }),
+            );
+            self.search_entry.add_controller(&key_controller);
+
+            palette.update_results();
+        }
+    }
+    impl WidgetImpl for GnoteCommandPalette {}
+    impl BoxImpl for GnoteCommandPalette {}
+}
+
+glib::wrapper! {
+    pub struct GnoteCommandPalette(ObjectSubclass<imp::GnoteCommandPalette>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteCommandPalette {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    fn update_results(&self) {
+        let results = &self.imp().results;
+        while let Some(child) = results.first_child() {
+            results.remove(&child);
+        }
+
+        let rows = commands::filter(&self.imp().search_entry.text());
+        for command in &rows {
+            let label = gtk::Label::builder()
+                .label(command.label)
+                .xalign(0.0)
+                .hexpand(true)
+                .build();
+            let accel = gtk::Label::builder()
+                .label(&accel_label(command))
+                .css_classes(vec!["dim-label".to_string()])
+                .build();
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            row.set_margin_top(5);
+            row.set_margin_bottom(5);
+            row.append(&label);
+            row.append(&accel);
+            results.append(&row);
+        }
+        self.imp().rows.replace(rows);
+
+        if let Some(first_row) = results.row_at_index(0) {
+            results.select_row(Some(&first_row));
+        }
+    }
+
+    fn move_selection(&self, step: i32) {
+        let results = &self.imp().results;
+        let index = results
+            .selected_row()
+            .map_or(0, |row| row.index() + step)
+            .max(0);
+        if let Some(row) = results.row_at_index(index) {
+            results.select_row(Some(&row));
+        }
+    }
+
+    #[template_callback]
+    fn handle_search_changed(&self) {
+        self.update_results();
+    }
+
+    #[template_callback]
+    fn handle_search_activate(&self) {
+        if let Some(row) = self.imp().results.selected_row() {
+            self.handle_row_activated(&row);
+        }
+    }
+
+    #[template_callback]
+    fn handle_stop_search(&self) {
+        self.emit_by_name::<()>("cancelled", &[]);
+    }
+
+    #[template_callback]
+    fn handle_row_activated(&self, row: &gtk::ListBoxRow) {
+        let action = self
+            .imp()
+            .rows
+            .borrow()
+            .get(row.index() as usize)
+            .map(|command| command.action.to_string());
+        if let Some(action) = action {
+            self.emit_by_name::<()>("command-chosen", &[&action]);
+        }
+    }
+}
+
+// The first key bound to the command as it reads on the keyboard, e.g. "Ctrl+Shift+P"
+fn accel_label(command: &Command) -> String {
+    command
+        .accels
+        .first()
+        .and_then(|accel| gtk::accelerator_parse(*accel))
+        .map(|(key, modifiers)| gtk::accelerator_get_label(key, modifiers).to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,204 @@
+use crate::tools::{
+    diff::{self, DiffLine},
+    merge::Conflict,
+};
+use gtk::{
+    glib::{self, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::cell::RefCell;
+
+const ADDED_TAG: &str = "added";
+const REMOVED_TAG: &str = "removed";
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/conflicts")]
+    pub struct GnoteConflicts {
+        #[template_child]
+        pub notes: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub keep_mine_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub keep_theirs_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub diff_view: TemplateChild<gtk::TextView>,
+
+        // Conflicts not yet resolved, in the order of the list
+        pub conflicts: RefCell<Vec<Conflict>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteConflicts {
+        const NAME: &'static str = "GnoteConflicts";
+        type Type = super::GnoteConflicts;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteConflicts {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let buffer = self.diff_view.buffer();
+            buffer.create_tag(Some(ADDED_TAG), &[("paragraph-background", &"#d4f4d2")]);
+            buffer.create_tag(
+                Some(REMOVED_TAG),
+                &[
+                    ("paragraph-background", &"#f8d0d0"),
+                    ("strikethrough", &true),
+                ],
+            );
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                // The note id along with the title and body it should end up with
+                vec![Signal::builder("resolved")
+                    .param_types([
+                        <String>::static_type(),
+                        <String>::static_type(),
+                        <String>::static_type(),
+                    ])
+                    .build()]
+            });
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteConflicts {}
+    impl BoxImpl for GnoteConflicts {}
+}
+
+glib::wrapper! {
+    pub struct GnoteConflicts(ObjectSubclass<imp::GnoteConflicts>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteConflicts {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    pub fn set_conflicts(&self, conflicts: Vec<Conflict>) {
+        let notes = &self.imp().notes;
+        while let Some(child) = notes.first_child() {
+            notes.remove(&child);
+        }
+        for conflict in &conflicts {
+            let row = gtk::Label::builder()
+                .label(&conflict.local.0)
+                .tooltip_text(if conflict.kept_remote {
+                    "The other version was kept"
+                } else {
+                    "This version was kept"
+                })
+                .xalign(0.0)
+                .ellipsize(gtk::pango::EllipsizeMode::End)
+                .build();
+            notes.append(&row);
+        }
+        self.imp().conflicts.replace(conflicts);
+        notes.select_row(notes.row_at_index(0).as_ref());
+        self.update_diff();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.imp().conflicts.borrow().is_empty()
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        Some(self.imp().notes.selected_row()?.index() as usize)
+    }
+
+    // Shows how this machine's version would become the other one
+    fn update_diff(&self) {
+        let imp = self.imp();
+        let buffer = imp.diff_view.buffer();
+        buffer.set_text("");
+
+        let conflicts = imp.conflicts.borrow();
+        let conflict = self.selected_index().and_then(|index| conflicts.get(index));
+        imp.keep_mine_button.set_sensitive(conflict.is_some());
+        imp.keep_theirs_button.set_sensitive(conflict.is_some());
+        let (mine, theirs) = match conflict {
+            Some(conflict) => (&conflict.local, &conflict.remote),
+            None => return,
+        };
+
+        let mut end = buffer.end_iter();
+        if mine.0 != theirs.0 {
+            buffer.insert_with_tags_by_name(&mut end, &format!("{}\n", mine.0), &[REMOVED_TAG]);
+            buffer.insert_with_tags_by_name(&mut end, &format!("{}\n\n", theirs.0), &[ADDED_TAG]);
+        }
+        for line in diff::diff_lines(&mine.1, &theirs.1) {
+            match line {
+                DiffLine::Same(text) => buffer.insert(&mut end, &format!("  {}\n", text)),
+                DiffLine::Added(text) => buffer.insert_with_tags_by_name(
+                    &mut end,
+                    &format!("+ {}\n", text),
+                    &[ADDED_TAG],
+                ),
+                DiffLine::Removed(text) => buffer.insert_with_tags_by_name(
+                    &mut end,
+                    &format!("- {}\n", text),
+                    &[REMOVED_TAG],
+                ),
+            }
+        }
+    }
+
+    // Takes the selected conflict off the list, moving on to the next one
+    fn take_selected(&self) -> Option<Conflict> {
+        let index = self.selected_index()?;
+        let notes = &self.imp().notes;
+        let conflict = self.imp().conflicts.borrow_mut().remove(index);
+        if let Some(row) = notes.row_at_index(index as i32) {
+            notes.remove(&row);
+        }
+        let next = notes
+            .row_at_index(index as i32)
+            .or_else(|| notes.row_at_index(index as i32 - 1));
+        notes.select_row(next.as_ref());
+        self.update_diff();
+        Some(conflict)
+    }
+
+    #[template_callback]
+    fn handle_note_selected(&self) {
+        self.update_diff();
+    }
+
+    #[template_callback]
+    fn handle_keep_mine_clicked(&self) {
+        if let Some(conflict) = self.take_selected() {
+            self.emit_by_name::<()>(
+                "resolved",
+                &[&conflict.id, &conflict.local.0, &conflict.local.1],
+            );
+        }
+    }
+
+    #[template_callback]
+    fn handle_keep_theirs_clicked(&self) {
+        if let Some(conflict) = self.take_selected() {
+            self.emit_by_name::<()>(
+                "resolved",
+                &[&conflict.id, &conflict.remote.0, &conflict.remote.1],
+            );
+        }
+    }
+}
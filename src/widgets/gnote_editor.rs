@@ -1,3 +1,11 @@
+use crate::{
+    tools::{
+        due_date::{self, DueDate},
+        find::{self, FindOptions},
+        wiki_links::{self, Backlink},
+    },
+    widgets::gnote_text_buffer::GnoteTextBuffer,
+};
 use gtk::{
     glib::{self, clone, Object, ParamFlags, ParamSpec, ParamSpecString, Value},
     prelude::*,
@@ -6,14 +14,19 @@ use gtk::{
 };
 use std::{
     cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    ops::Range,
     sync::Once,
+    time::Duration,
 };
 
+// Milliseconds without typing before the whole note is scanned and highlighted again
+const HIGHLIGHT_DELAY: u64 = 200;
+
 // Remove the following line
 // use adw::prelude::*;
 mod imp {
     use super::*;
-    use crate::widgets::gnote_text_buffer::GnoteTextBuffer;
     use gtk::glib::subclass::Signal;
     use gtk::template_callbacks;
     use once_cell::sync::Lazy;
@@ -26,7 +39,46 @@ mod imp {
         #[template_child]
         pub note: TemplateChild<gtk::TextView>,
         #[template_child]
-        pub note_buffer: TemplateChild<GnoteTextBuffer>,
+        pub checklist_progress: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub due_date_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub due_date_calendar: TemplateChild<gtk::Calendar>,
+        #[template_child]
+        pub backlinks_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub backlinks: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub wiki_link_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub wiki_link_completions: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub find_bar: TemplateChild<gtk::SearchBar>,
+        #[template_child]
+        pub find_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub find_count: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub find_case_sensitive: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub find_whole_word: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub find_regex: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub replace_entry: TemplateChild<gtk::Entry>,
+
+        pub backlink_paths: RefCell<Vec<Vec<i32>>>,
+        pub note_titles: RefCell<Vec<String>>,
+        // The title as it was when the note was opened or the title was last committed
+        pub committed_title: RefCell<String>,
+        // Byte ranges of the note's full text matching the find bar, and the one moved to
+        pub find_matches: RefCell<Vec<Range<usize>>>,
+        pub find_current: Cell<Option<usize>>,
+        // A buffer for every note opened so far, so each note keeps its own undo history
+        pub note_buffers: RefCell<HashMap<String, GnoteTextBuffer>>,
+        pub note_id: RefCell<String>,
+        // The highlighting waiting for typing to settle down
+        pub pending_highlight: RefCell<Option<glib::SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -38,6 +90,75 @@ mod imp {
         fn class_init(klass: &mut Self::Class) {
             klass.bind_template();
             klass.bind_template_instance_callbacks();
+
+            klass.install_action("editor.insert-image", None, |editor, _, _| {
+                editor.note_buffer().insert_image(&editor.imp().note);
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.copy-note-markdown", None, |editor, _, _| {
+                let title = editor.imp().title.text();
+                editor
+                    .note_buffer()
+                    .copy_as_markdown(&editor.imp().note, &title);
+            });
+            klass.install_action("editor.copy-note-html", None, |editor, _, _| {
+                let title = editor.imp().title.text();
+                editor
+                    .note_buffer()
+                    .copy_as_html(&editor.imp().note, &title);
+            });
+            klass.install_action("editor.attach-file", None, |editor, _, _| {
+                editor.note_buffer().attach_file(&editor.imp().note);
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.insert-check-box", None, |editor, _, _| {
+                editor.note_buffer().insert_check_box(&editor.imp().note);
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.insert-bullet", None, |editor, _, _| {
+                editor.note_buffer().insert_bullet();
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.indent-less", None, |editor, _, _| {
+                editor.note_buffer().indent_less();
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.indent-more", None, |editor, _, _| {
+                editor.note_buffer().indent_more();
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.undo", None, |editor, _, _| {
+                editor.note_buffer().undo();
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.redo", None, |editor, _, _| {
+                editor.note_buffer().redo();
+                editor.imp().note.grab_focus();
+            });
+            klass.install_action("editor.find", None, |editor, _, _| {
+                editor.show_find_bar();
+            });
+            klass.install_action("editor.find-next", None, |editor, _, _| {
+                editor.move_to_match(1);
+            });
+            klass.install_action("editor.find-previous", None, |editor, _, _| {
+                editor.move_to_match(-1);
+            });
+            klass.install_action("editor.replace", None, |editor, _, _| {
+                editor.replace_current_match();
+            });
+            klass.install_action("editor.replace-all", None, |editor, _, _| {
+                editor.replace_all_matches();
+            });
+            klass.install_action("editor.move-completed-to-bottom", None, |editor, _, _| {
+                editor.note_buffer().move_completed_to_bottom();
+            });
+            klass.install_action("editor.uncheck-all", None, |editor, _, _| {
+                editor.note_buffer().uncheck_all();
+            });
+            klass.install_action("editor.clear-completed", None, |editor, _, _| {
+                editor.note_buffer().clear_completed();
+            });
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -67,6 +188,18 @@ mod imp {
                     Signal::builder("note-changed")
                         .param_types([<String>::static_type()])
                         .build(),
+                    Signal::builder("title-renamed")
+                        .param_types([<String>::static_type(), <String>::static_type()])
+                        .build(),
+                    Signal::builder("wiki-link-activated")
+                        .param_types([<String>::static_type()])
+                        .build(),
+                    Signal::builder("backlink-activated")
+                        .param_types([<u32>::static_type()])
+                        .build(),
+                    Signal::builder("error")
+                        .param_types([<String>::static_type()])
+                        .build(),
                 ]
             });
             SIGNALS.as_ref()
@@ -80,11 +213,11 @@ mod imp {
                         .expect("type conformity checked by `Object::set_property`");
                     self.title.set_text(title)
                 }
-                "add-folder-visible" => {
+                "note" => {
                     let note = value
                         .get()
                         .expect("type conformity checked by `Object::set_property`");
-                    self.note_buffer.set_text(note)
+                    self.instance().note_buffer().load_note_text(note)
                 }
                 _ => unimplemented!(),
             }
@@ -93,14 +226,7 @@ mod imp {
         fn property(&self, _id: usize, pspec: &ParamSpec) -> Value {
             match pspec.name() {
                 "title" => self.title.text().as_str().to_value(),
-                "note" => {
-                    let start = self.note_buffer.start_iter();
-                    let end = self.note_buffer.end_iter();
-                    self.note_buffer
-                        .text(&start, &end, true)
-                        .as_str()
-                        .to_value()
-                }
+                "note" => self.instance().note_buffer().note_text().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -143,7 +269,48 @@ mod imp {
                 gtk::STYLE_PROVIDER_PRIORITY_USER,
             );
 
-            self.note_buffer.init(&self.note);
+            let editor = self.instance();
+            self.note.set_buffer(Some(&editor.new_note_buffer()));
+            GnoteTextBuffer::init(&self.note);
+            editor.update_undo_actions();
+            // Escape in the find entry closes the find bar
+            self.find_bar.connect_entry(&*self.find_entry);
+
+            self.wiki_link_popover.set_parent(&*self.note);
+
+            // Tab picks the first suggested title while a link is being typed
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.connect_key_pressed(
+                clone!(@weak editor => @default-return gtk::Inhibit(false), move |_, key, _, _| {
+                    let popover = &editor.imp().wiki_link_popover;
+                    if !popover.is_visible() {
+                        return gtk::Inhibit(false);
+                    }
+
+                    if key == gtk::gdk::Key::Tab {
+                        if let Some(row) = editor.imp().wiki_link_completions.row_at_index(0) {
+                            editor.handle_wiki_link_completion_activated(&row);
+                        }
+                        gtk::Inhibit(true)
+                    } else if key == gtk::gdk::Key::Escape {
+                        popover.popdown();
+                        gtk::Inhibit(true)
+                    } else {
+                        gtk::Inhibit(false)
+                    }
+                }),
+            );
+            self.note.add_controller(&key_controller);
+
+            let focus_controller = gtk::EventControllerFocus::new();
+            focus_controller.connect_leave(clone!(@weak editor => move |_| {
+                editor.commit_title();
+            }));
+            self.title.add_controller(&focus_controller);
+        }
+
+        fn dispose(&self) {
+            self.wiki_link_popover.unparent();
         }
     }
     impl WidgetImpl for GnoteEditor {}
@@ -169,40 +336,507 @@ impl GnoteEditor {
     #[template_callback]
     fn handle_title_changed(&self, title: &gtk::Entry) {
         println!("Title Changed");
+        self.emit_by_name::<()>("title-changed", &[&title.text().to_string()]);
+    }
+
+    // Shows the note in its own buffer, created the first time the note is opened
+    pub fn set_note(&self, note_id: &str, title: &str, note: &str) {
+        let imp = self.imp();
+        // Set first, so the title shown below goes to this note and not the one before
+        imp.note_id.replace(note_id.to_string());
+        imp.committed_title.replace(title.to_string());
+        self.set_property("title", title);
+
+        let existing = imp.note_buffers.borrow().get(note_id).cloned();
+        let buffer = match existing {
+            Some(buffer) => buffer,
+            None => {
+                let buffer = self.new_note_buffer();
+                buffer.load_note_text(note);
+                imp.note_buffers
+                    .borrow_mut()
+                    .insert(note_id.to_string(), buffer.clone());
+                buffer
+            }
+        };
+        imp.note.set_buffer(Some(&buffer));
+        buffer.attach_widgets(&imp.note);
+
+        // Changed elsewhere since it was last shown, which can be undone like any edit
+        if buffer.note_text() != note {
+            buffer.replace_note_text(&imp.note, note);
+        }
+        self.highlight_note();
+        self.update_undo_actions();
+    }
+
+    // Drops the buffers, and with them the undo history, of notes no longer in the notebook
+    pub fn retain_note_buffers(&self, note_ids: &HashSet<String>) {
+        self.imp()
+            .note_buffers
+            .borrow_mut()
+            .retain(|note_id, _| note_ids.contains(note_id));
+    }
+
+    // Forgets every note shown so far, as when the notebook is locked
+    pub fn clear(&self) {
+        let imp = self.imp();
+        imp.note_buffers.borrow_mut().clear();
+        imp.note_id.replace(String::new());
+        imp.committed_title.replace(String::new());
+        self.set_property("title", "");
+        imp.note.set_buffer(Some(&self.new_note_buffer()));
+        self.update_undo_actions();
+    }
+
+    // The note being edited, which need not be the one selected in the notebook
+    pub fn note_id(&self) -> Option<String> {
+        Some(self.imp().note_id.borrow().clone()).filter(|note_id| !note_id.is_empty())
+    }
+
+    // The image or attachment right before or after the cursor
+    pub fn attachment_at_cursor(&self) -> Option<gtk::Widget> {
+        let buffer = self.note_buffer();
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let mut before = cursor.clone();
+        before.backward_char();
+        [cursor, before]
+            .iter()
+            .find_map(|iter| iter.child_anchor())
+            .and_then(|anchor| anchor.widgets().into_iter().next())
+    }
+
+    pub fn note_buffer(&self) -> GnoteTextBuffer {
+        self.imp()
+            .note
+            .buffer()
+            .downcast::<GnoteTextBuffer>()
+            .expect("The note view only shows note buffers")
+    }
+
+    fn new_note_buffer(&self) -> GnoteTextBuffer {
+        let buffer = GnoteTextBuffer::new();
+        buffer.connect_changed(clone!(@weak self as editor => move |buffer| {
+            // Buffers of notes that aren't shown only change while being loaded
+            if editor.imp().note.buffer() == *buffer.upcast_ref::<gtk::TextBuffer>() {
+                editor.handle_note_buffer_changed();
+            }
+        }));
+        buffer.connect_local(
+            "wiki-link-activated",
+            false,
+            clone!(@weak self as editor => @default-return None, move |values| {
+                let title = values[1].get::<String>().unwrap();
+                editor.emit_by_name::<()>("wiki-link-activated", &[&title]);
+                None
+            }),
+        );
+        buffer.connect_local(
+            "error",
+            false,
+            clone!(@weak self as editor => @default-return None, move |values| {
+                let message = values[1].get::<String>().unwrap();
+                editor.emit_by_name::<()>("error", &[&message]);
+                None
+            }),
+        );
+        buffer.connect_can_undo_notify(clone!(@weak self as editor => move |_| {
+            editor.update_undo_actions();
+        }));
+        buffer.connect_can_redo_notify(clone!(@weak self as editor => move |_| {
+            editor.update_undo_actions();
+        }));
+        buffer
+    }
+
+    fn update_undo_actions(&self) {
+        let buffer = self.note_buffer();
+        self.action_set_enabled("editor.undo", buffer.can_undo());
+        self.action_set_enabled("editor.redo", buffer.can_redo());
+    }
+
+    // Lets the notebook know about a finished rename so links to the note can follow it
+    pub fn commit_title(&self) {
+        let title = self.imp().title.text().to_string();
+        let old_title = self.imp().committed_title.replace(title.clone());
+        if old_title != title {
+            self.emit_by_name::<()>("title-renamed", &[&old_title, &title]);
+        }
+    }
+
+    pub fn set_note_titles(&self, titles: Vec<String>) {
+        self.imp().note_titles.replace(titles);
+    }
+
+    pub fn set_backlinks(&self, backlinks: Vec<Backlink>) {
+        let list = &self.imp().backlinks;
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+
+        for backlink in &backlinks {
+            list.append(
+                &gtk::Label::builder()
+                    .label(&backlink.title)
+                    .halign(gtk::Align::Start)
+                    .build(),
+            );
+        }
+
+        self.imp().backlinks_box.set_visible(!backlinks.is_empty());
+        self.imp().backlink_paths.replace(
+            backlinks
+                .into_iter()
+                .map(|backlink| backlink.note_path)
+                .collect(),
+        );
+    }
+
+    pub fn backlink_path(&self, index: usize) -> Option<Vec<i32>> {
+        self.imp().backlink_paths.borrow().get(index).cloned()
     }
 
     #[template_callback]
-    fn handle_note_buffer_changed(&self, note_buffer: &gtk::TextBuffer) {
-        println!("Note Changed");
+    fn handle_backlink_activated(&self, row: &gtk::ListBoxRow) {
+        self.emit_by_name::<()>("backlink-activated", &[&(row.index() as u32)]);
     }
 
     #[template_callback]
-    fn handle_insert_image_clicked(&self, button: &gtk::Button) {
-        self.imp().note_buffer.insert_image(&self.imp().note);
-        self.imp().note.grab_focus();
+    fn handle_title_activate(&self, _title: &gtk::Entry) {
+        self.commit_title();
+    }
+
+    // Offers the titles of other notes while a [[link]] is being typed
+    fn update_wiki_link_completion(&self) {
+        let buffer = self.note_buffer();
+        let popover = &self.imp().wiki_link_popover;
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let mut line_start = cursor.clone();
+        line_start.set_line_offset(0);
+
+        let text_before_cursor = buffer.text(&line_start, &cursor, false);
+        let partial_title = match wiki_links::partial_title(&text_before_cursor) {
+            Some(partial_title) => partial_title,
+            None => {
+                popover.popdown();
+                return;
+            }
+        };
+
+        let titles = self.imp().note_titles.borrow();
+        let completions = wiki_links::completions(partial_title, &titles, 8);
+        if completions.is_empty() {
+            popover.popdown();
+            return;
+        }
+
+        let list = &self.imp().wiki_link_completions;
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+        for title in completions {
+            list.append(
+                &gtk::Label::builder()
+                    .label(title)
+                    .halign(gtk::Align::Start)
+                    .build(),
+            );
+        }
+
+        let location = self.imp().note.iter_location(&cursor);
+        let (x, y) = self.imp().note.buffer_to_window_coords(
+            gtk::TextWindowType::Widget,
+            location.x(),
+            location.y(),
+        );
+        popover.set_pointing_to(Some(&gtk::gdk::Rectangle::new(x, y, 1, location.height())));
+        popover.popup();
     }
 
     #[template_callback]
-    fn handle_insert_check_box_clicked(&self, button: &gtk::Button) {
-        self.imp().note_buffer.insert_check_box(&self.imp().note);
+    fn handle_wiki_link_completion_activated(&self, row: &gtk::ListBoxRow) {
+        let title = match row
+            .child()
+            .and_then(|child| child.downcast::<gtk::Label>().ok())
+        {
+            Some(label) => label.text().to_string(),
+            None => return,
+        };
+
+        let buffer = self.note_buffer();
+        let mut cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let mut line_start = cursor.clone();
+        line_start.set_line_offset(0);
+
+        let text_before_cursor = buffer.text(&line_start, &cursor, false);
+        if let Some(partial_title) = wiki_links::partial_title(&text_before_cursor) {
+            // Replace the unfinished "[[..." with the complete link
+            let mut link_start = cursor.clone();
+            link_start.backward_chars(partial_title.chars().count() as i32 + 2);
+
+            buffer.begin_user_action();
+            buffer.delete(&mut link_start, &mut cursor);
+            buffer.insert(&mut link_start, &wiki_links::format(&title));
+            buffer.end_user_action();
+        }
+
+        self.imp().wiki_link_popover.popdown();
         self.imp().note.grab_focus();
     }
 
+    pub fn goto_line(&self, line: i32) {
+        let buffer = self.note_buffer();
+        if let Some(iter) = buffer.iter_at_line(line) {
+            buffer.place_cursor(&iter);
+            self.imp()
+                .note
+                .scroll_to_mark(&buffer.get_insert(), 0.1, false, 0.0, 0.0);
+            self.imp().note.grab_focus();
+        }
+    }
+
+    // Brings back an earlier version of the note as a single edit, so it can be undone
+    pub fn restore_note(&self, title: &str, body: &str) {
+        self.imp().title.set_text(title);
+        self.commit_title();
+        self.note_buffer().replace_note_text(&self.imp().note, body);
+    }
+
+    fn find_options(&self) -> FindOptions {
+        FindOptions {
+            case_sensitive: self.imp().find_case_sensitive.is_active(),
+            whole_word: self.imp().find_whole_word.is_active(),
+            regex: self.imp().find_regex.is_active(),
+        }
+    }
+
+    pub fn show_find_bar(&self) {
+        let imp = self.imp();
+        // Start with the selected text, when it is on one line
+        if let Some((start, end)) = self.note_buffer().selection_bounds() {
+            let selected = self.note_buffer().text(&start, &end, false);
+            if !selected.contains('\n') {
+                imp.find_entry.set_text(&selected);
+            }
+        }
+        imp.find_bar.set_search_mode(true);
+        imp.find_entry.grab_focus();
+    }
+
+    // Finds the matches again after the query or the note changed. The current match becomes the
+    // first one at or after start_from, or otherwise keeps its place in the list
+    fn refresh_find(&self, start_from: Option<usize>) {
+        let imp = self.imp();
+        if !imp.find_bar.is_search_mode() {
+            return;
+        }
+
+        let text = self.note_buffer().full_text();
+        let query = imp.find_entry.text();
+        let matches = match find::find(&text, &query, &self.find_options()) {
+            Ok(matches) => matches,
+            Err(_) => {
+                imp.find_matches.replace(Vec::new());
+                imp.find_current.set(None);
+                self.note_buffer().highlight_find_matches(&[], None);
+                imp.find_count.set_text("Invalid expression");
+                return;
+            }
+        };
+
+        let current = if matches.is_empty() {
+            None
+        } else if let Some(start_from) = start_from {
+            matches
+                .iter()
+                .position(|range| range.start >= start_from)
+                .or(Some(0))
+        } else {
+            imp.find_current
+                .get()
+                .map(|current| current.min(matches.len() - 1))
+        };
+
+        self.note_buffer().highlight_find_matches(&matches, current);
+        imp.find_count.set_text(&match (matches.len(), current) {
+            (0, _) if query.is_empty() => String::new(),
+            (0, _) => "No matches".to_string(),
+            (count, Some(current)) => format!("{} of {}", current + 1, count),
+            (count, None) => format!("{} matches", count),
+        });
+        imp.find_matches.replace(matches);
+        imp.find_current.set(current);
+    }
+
+    fn select_current_match(&self) {
+        let imp = self.imp();
+        let current = imp.find_current.get();
+        if let Some(range) =
+            current.and_then(|current| imp.find_matches.borrow().get(current).cloned())
+        {
+            self.note_buffer().select_byte_range(&imp.note, &range);
+        }
+    }
+
+    fn move_to_match(&self, step: i32) {
+        let imp = self.imp();
+        if !imp.find_bar.is_search_mode() {
+            self.show_find_bar();
+            return;
+        }
+
+        let count = imp.find_matches.borrow().len();
+        if count == 0 {
+            return;
+        }
+        let current = match imp.find_current.get() {
+            Some(current) => (current as i32 + step).rem_euclid(count as i32) as usize,
+            None => 0,
+        };
+        imp.find_current.set(Some(current));
+        self.refresh_find(None);
+        self.select_current_match();
+    }
+
+    fn replace_current_match(&self) {
+        let imp = self.imp();
+        let current = match imp.find_current.get() {
+            Some(current) => current,
+            None => return,
+        };
+
+        let text = self.note_buffer().full_text();
+        let replacements = find::replacements(
+            &text,
+            &imp.find_entry.text(),
+            &imp.replace_entry.text(),
+            &self.find_options(),
+        )
+        .unwrap_or_default();
+        if let Some((range, replacement)) = replacements.get(current).cloned() {
+            // Carry on after the replacement, so one containing the query isn't found again
+            let resume_at = range.start + replacement.len();
+            self.note_buffer()
+                .replace_byte_ranges(&[(range, replacement)]);
+            self.refresh_find(Some(resume_at));
+            self.select_current_match();
+        }
+    }
+
+    fn replace_all_matches(&self) {
+        let imp = self.imp();
+        let text = self.note_buffer().full_text();
+        let replacements = find::replacements(
+            &text,
+            &imp.find_entry.text(),
+            &imp.replace_entry.text(),
+            &self.find_options(),
+        )
+        .unwrap_or_default();
+        if replacements.is_empty() {
+            return;
+        }
+
+        self.note_buffer().replace_byte_ranges(&replacements);
+        self.refresh_find(None);
+        imp.find_count
+            .set_text(&format!("Replaced {}", replacements.len()));
+    }
+
     #[template_callback]
-    fn handle_bullet_point_clicked(&self, button: &gtk::Button) {
-        self.imp().note_buffer.insert_bullet();
-        self.imp().note.grab_focus();
+    fn handle_find_changed(&self) {
+        let text = self.note_buffer().full_text();
+        let start_from = self.note_buffer().selection_start_byte(&text);
+        self.refresh_find(Some(start_from));
+        self.select_current_match();
     }
 
     #[template_callback]
-    fn handle_indent_less_clicked(&self, button: &gtk::Button) {
-        self.imp().note_buffer.indent_less();
-        self.imp().note.grab_focus();
+    fn handle_find_activate(&self) {
+        self.move_to_match(1);
     }
 
     #[template_callback]
-    fn handle_indent_more_clicked(&self, button: &gtk::Button) {
-        self.imp().note_buffer.indent_more();
+    fn handle_find_previous_match(&self) {
+        self.move_to_match(-1);
+    }
+
+    #[template_callback]
+    fn handle_replace_activate(&self) {
+        self.replace_current_match();
+    }
+
+    #[template_callback]
+    fn handle_find_mode_changed(&self) {
+        let imp = self.imp();
+        if imp.find_bar.is_search_mode() {
+            self.handle_find_changed();
+        } else {
+            imp.find_matches.replace(Vec::new());
+            imp.find_current.set(None);
+            self.note_buffer().highlight_find_matches(&[], None);
+            imp.note.grab_focus();
+        }
+    }
+
+    fn handle_note_buffer_changed(&self) {
+        println!("Note Changed");
+        self.update_wiki_link_completion();
+        self.schedule_highlight();
+
+        self.emit_by_name::<()>("note-changed", &[&self.note_buffer().note_text()]);
+    }
+
+    // Scanning the whole note on each key press would slow typing down in long notes
+    fn schedule_highlight(&self) {
+        if let Some(source_id) = self.imp().pending_highlight.take() {
+            source_id.remove();
+        }
+        let source_id = glib::timeout_add_local_once(
+            Duration::from_millis(HIGHLIGHT_DELAY),
+            clone!(@weak self as editor => move || {
+                editor.imp().pending_highlight.take();
+                editor.highlight_note();
+            }),
+        );
+        self.imp().pending_highlight.replace(Some(source_id));
+    }
+
+    fn highlight_note(&self) {
+        if let Some(source_id) = self.imp().pending_highlight.take() {
+            source_id.remove();
+        }
+        let note_buffer = self.note_buffer();
+        self.update_checklist_progress();
+        self.refresh_due_dates();
+        note_buffer.highlight_links();
+        note_buffer.highlight_tags();
+        note_buffer.highlight_emphasis();
+        self.refresh_find(None);
+    }
+
+    pub fn refresh_due_dates(&self) {
+        self.note_buffer()
+            .highlight_due_dates(&due_date::now_local());
+    }
+
+    #[template_callback]
+    fn handle_due_date_selected(&self, calendar: &gtk::Calendar) {
+        let date = calendar.date();
+        let due = DueDate::new(date.year(), date.month() as u32, date.day_of_month() as u32);
+
+        self.note_buffer().set_due_date(&due);
+        self.imp().due_date_popover.popdown();
         self.imp().note.grab_focus();
     }
+
+    fn update_checklist_progress(&self) {
+        let progress = self.note_buffer().checklist_progress();
+        self.imp()
+            .checklist_progress
+            .set_text(&progress.to_string());
+        self.imp()
+            .checklist_progress
+            .set_visible(progress.total > 0);
+    }
 }
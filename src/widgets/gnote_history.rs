@@ -0,0 +1,220 @@
+use crate::tools::{
+    diff::{self, DiffLine},
+    history::Snapshot,
+};
+use gtk::{
+    glib::{self, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::cell::RefCell;
+
+const ADDED_TAG: &'static str = "added";
+const REMOVED_TAG: &'static str = "removed";
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/history")]
+    pub struct GnoteHistory {
+        #[template_child]
+        pub versions: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub compare_with: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub summary: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub restore_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub diff_view: TemplateChild<gtk::TextView>,
+
+        // The note as it is now, then its snapshots newest first
+        pub snapshots: RefCell<Vec<Snapshot>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteHistory {
+        const NAME: &'static str = "GnoteHistory";
+        type Type = super::GnoteHistory;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteHistory {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let buffer = self.diff_view.buffer();
+            buffer.create_tag(Some(ADDED_TAG), &[("paragraph-background", &"#d4f4d2")]);
+            buffer.create_tag(
+                Some(REMOVED_TAG),
+                &[
+                    ("paragraph-background", &"#f8d0d0"),
+                    ("strikethrough", &true),
+                ],
+            );
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder("restore-requested")
+                    .param_types([<String>::static_type(), <String>::static_type()])
+                    .build()]
+            });
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteHistory {}
+    impl BoxImpl for GnoteHistory {}
+}
+
+glib::wrapper! {
+    pub struct GnoteHistory(ObjectSubclass<imp::GnoteHistory>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteHistory {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    pub fn set_snapshots(&self, current: Snapshot, snapshots: &[Snapshot]) {
+        let imp = self.imp();
+        let mut all_snapshots = vec![current];
+        for snapshot in snapshots.iter().rev() {
+            // The latest snapshot is usually just the note as it is now
+            if snapshot.title != all_snapshots[0].title || snapshot.body != all_snapshots[0].body {
+                all_snapshots.push(snapshot.clone());
+            }
+        }
+
+        while let Some(child) = imp.versions.first_child() {
+            imp.versions.remove(&child);
+        }
+        let mut labels = Vec::new();
+        for (index, snapshot) in all_snapshots.iter().enumerate() {
+            let label = if index == 0 {
+                String::from("Current Version")
+            } else {
+                format_time(snapshot.time)
+            };
+            let row = gtk::Label::builder()
+                .label(&label)
+                .tooltip_text(&snapshot.title)
+                .xalign(0.0)
+                .build();
+            imp.versions.append(&row);
+            labels.push(label);
+        }
+
+        imp.snapshots.replace(all_snapshots);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        imp.compare_with
+            .set_model(Some(&gtk::StringList::new(&labels)));
+        imp.compare_with.set_selected(0);
+        // Straight to the most recent earlier version, if there is one
+        let row = imp
+            .versions
+            .row_at_index(1)
+            .or_else(|| imp.versions.row_at_index(0));
+        imp.versions.select_row(row.as_ref());
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        Some(self.imp().versions.selected_row()?.index() as usize)
+    }
+
+    // Shows how the selected version became the one it is compared with
+    fn update_diff(&self) {
+        let imp = self.imp();
+        let buffer = imp.diff_view.buffer();
+        buffer.set_text("");
+
+        let snapshots = imp.snapshots.borrow();
+        let selected = self.selected_index();
+        imp.restore_button
+            .set_sensitive(selected.map_or(false, |index| index > 0));
+        let (old, new) = match (
+            selected.and_then(|index| snapshots.get(index)),
+            snapshots.get(imp.compare_with.selected() as usize),
+        ) {
+            (Some(old), Some(new)) => (old, new),
+            _ => {
+                imp.summary.set_text("");
+                return;
+            }
+        };
+
+        let mut end = buffer.end_iter();
+        if old.title != new.title {
+            buffer.insert_with_tags_by_name(&mut end, &format!("{}\n", old.title), &[REMOVED_TAG]);
+            buffer.insert_with_tags_by_name(&mut end, &format!("{}\n\n", new.title), &[ADDED_TAG]);
+        }
+
+        let (mut added, mut removed) = (0, 0);
+        for line in diff::diff_lines(&old.body, &new.body) {
+            match line {
+                DiffLine::Same(text) => buffer.insert(&mut end, &format!("  {}\n", text)),
+                DiffLine::Added(text) => {
+                    added += 1;
+                    buffer.insert_with_tags_by_name(
+                        &mut end,
+                        &format!("+ {}\n", text),
+                        &[ADDED_TAG],
+                    );
+                }
+                DiffLine::Removed(text) => {
+                    removed += 1;
+                    buffer.insert_with_tags_by_name(
+                        &mut end,
+                        &format!("- {}\n", text),
+                        &[REMOVED_TAG],
+                    );
+                }
+            }
+        }
+        imp.summary
+            .set_text(&format!("{} added, {} removed", added, removed));
+    }
+
+    #[template_callback]
+    fn handle_version_selected(&self) {
+        self.update_diff();
+    }
+
+    #[template_callback]
+    fn handle_compare_changed(&self) {
+        self.update_diff();
+    }
+
+    #[template_callback]
+    fn handle_restore_clicked(&self) {
+        let snapshot = match self
+            .selected_index()
+            .and_then(|index| self.imp().snapshots.borrow().get(index).cloned())
+        {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        self.emit_by_name::<()>("restore-requested", &[&snapshot.title, &snapshot.body]);
+    }
+}
+
+fn format_time(time: i64) -> String {
+    glib::DateTime::from_unix_local(time)
+        .and_then(|date_time| date_time.format("%x %X"))
+        .map(|formatted| formatted.to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
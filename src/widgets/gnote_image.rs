@@ -0,0 +1,209 @@
+use crate::tools::images::{self, ImageSize};
+use gtk::{
+    builders::FileChooserDialogBuilder,
+    gdk::Texture,
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+    FileChooserAction, GestureClick, ResponseType,
+};
+use std::cell::{Cell, RefCell};
+
+// Room left around an image fitted to the width of the note view
+const FIT_WIDTH_MARGIN: i32 = 40;
+// Used until the note view has been given a width
+const DEFAULT_AVAILABLE_WIDTH: i32 = 600;
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/image")]
+    pub struct GnoteImage {
+        #[template_child]
+        pub picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub caption: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub context_menu: TemplateChild<gtk::PopoverMenu>,
+        #[template_child]
+        pub caption_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub caption_entry: TemplateChild<gtk::Entry>,
+
+        pub texture: RefCell<Option<Texture>>,
+        pub size: Cell<ImageSize>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteImage {
+        const NAME: &'static str = "GnoteImage";
+        type Type = super::GnoteImage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+
+            klass.install_action("image.size", Some("s"), |image, _, parameter| {
+                if let Some(size) = parameter
+                    .and_then(|parameter| parameter.get::<String>())
+                    .and_then(|name| ImageSize::from_name(&name))
+                {
+                    image.set_size(size);
+                    image.emit_by_name::<()>("changed", &[]);
+                }
+            });
+            klass.install_action("image.edit-caption", None, |image, _, _| {
+                let imp = image.imp();
+                imp.caption_entry.set_text(&imp.caption.text());
+                imp.caption_popover.popup();
+                imp.caption_entry.grab_focus();
+            });
+            klass.install_action("image.copy", None, |image, _, _| {
+                if let Some(texture) = image.imp().texture.borrow().as_ref() {
+                    image.clipboard().set_texture(texture);
+                }
+            });
+            klass.install_action("image.save-as", None, |image, _, _| {
+                image.show_save_dialog();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteImage {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let image = self.instance();
+            self.context_menu.set_parent(&*image);
+            self.caption_popover.set_parent(&*image);
+
+            let context_click = GestureClick::builder()
+                .button(gtk::gdk::BUTTON_SECONDARY)
+                .build();
+            context_click.connect_pressed(clone!(@weak image => move |_, _, x, y| {
+                let menu = &image.imp().context_menu;
+                menu.set_pointing_to(Some(&gtk::gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                menu.popup();
+            }));
+            image.add_controller(&context_click);
+        }
+
+        fn dispose(&self) {
+            self.context_menu.unparent();
+            self.caption_popover.unparent();
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    // The size or caption was changed from the image's menu
+                    Signal::builder("changed").build(),
+                    Signal::builder("error")
+                        .param_types([<String>::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteImage {}
+    impl BoxImpl for GnoteImage {}
+}
+
+glib::wrapper! {
+    pub struct GnoteImage(ObjectSubclass<imp::GnoteImage>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteImage {
+    pub fn new(texture: &Texture) -> Self {
+        let image = Object::new::<Self>(&[]);
+        image.imp().picture.set_paintable(Some(texture));
+        image.imp().texture.replace(Some(texture.clone()));
+        image.set_size(ImageSize::default());
+        image
+    }
+
+    pub fn texture(&self) -> Option<Texture> {
+        self.imp().texture.borrow().clone()
+    }
+
+    pub fn set_size(&self, size: ImageSize) {
+        let imp = self.imp();
+        imp.size.set(size);
+        let (width, height) = match imp.texture.borrow().as_ref() {
+            Some(texture) => (texture.width(), texture.height()),
+            None => return,
+        };
+        let (width, height) = images::scaled_size(size, width, height, self.available_width());
+        imp.picture.set_size_request(width, height);
+    }
+
+    pub fn size(&self) -> ImageSize {
+        self.imp().size.get()
+    }
+
+    pub fn set_caption(&self, caption: &str) {
+        let caption = caption.trim();
+        self.imp().caption.set_text(caption);
+        self.imp().caption.set_visible(!caption.is_empty());
+    }
+
+    pub fn caption(&self) -> String {
+        self.imp().caption.text().to_string()
+    }
+
+    // The width of the note view the image is in, less some room around it
+    fn available_width(&self) -> i32 {
+        self.ancestor(gtk::TextView::static_type())
+            .map(|text_view| text_view.width() - FIT_WIDTH_MARGIN)
+            .filter(|width| *width > 0)
+            .unwrap_or(DEFAULT_AVAILABLE_WIDTH)
+    }
+
+    fn show_save_dialog(&self) {
+        let window = self
+            .root()
+            .and_then(|root| root.downcast::<gtk::Window>().ok());
+        let file_chooser = FileChooserDialogBuilder::new()
+            .title("Save image")
+            .action(FileChooserAction::Save)
+            .modal(true)
+            .build();
+        file_chooser.set_transient_for(window.as_ref());
+        file_chooser.set_current_name("image.png");
+        file_chooser.add_buttons(&[("Save", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+
+        file_chooser.connect_response(clone!(@weak self as image => move |dialog, response| {
+            if response == ResponseType::Ok {
+                if let (Some(file_path), Some(texture)) =
+                    (dialog.file().and_then(|file| file.path()), image.texture())
+                {
+                    if let Err(e) = texture.save_to_png(&file_path) {
+                        let message = format!("Couldn't save {}: {}", file_path.display(), e);
+                        image.emit_by_name::<()>("error", &[&message]);
+                    }
+                }
+            }
+            dialog.destroy();
+        }));
+
+        file_chooser.present();
+    }
+
+    #[template_callback]
+    fn handle_caption_activate(&self) {
+        self.set_caption(&self.imp().caption_entry.text());
+        self.imp().caption_popover.popdown();
+        self.emit_by_name::<()>("changed", &[]);
+    }
+}
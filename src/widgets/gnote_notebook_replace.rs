@@ -0,0 +1,229 @@
+use crate::tools::{
+    find::{self, FindOptions, Occurrence},
+    io::NoteFile,
+};
+use gtk::{
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::cell::RefCell;
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/notebook_replace")]
+    pub struct GnoteNotebookReplace {
+        #[template_child]
+        pub find_entry: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub replace_entry: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub case_sensitive: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub whole_word: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub regex: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub include_titles: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub summary: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub occurrence_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub apply_button: TemplateChild<gtk::Button>,
+
+        pub note_file: RefCell<Option<NoteFile>>,
+        pub occurrences: RefCell<Vec<Occurrence>>,
+        // Whether each occurrence will be replaced
+        pub included: RefCell<Vec<bool>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteNotebookReplace {
+        const NAME: &'static str = "GnoteNotebookReplace";
+        type Type = super::GnoteNotebookReplace;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteNotebookReplace {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> =
+                Lazy::new(|| vec![Signal::builder("apply-requested").build()]);
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteNotebookReplace {}
+    impl BoxImpl for GnoteNotebookReplace {}
+}
+
+glib::wrapper! {
+    pub struct GnoteNotebookReplace(ObjectSubclass<imp::GnoteNotebookReplace>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteNotebookReplace {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    pub fn set_note_file(&self, note_file: NoteFile) {
+        self.imp().note_file.replace(Some(note_file));
+        self.update_occurrences();
+    }
+
+    pub fn included_occurrences(&self) -> Vec<Occurrence> {
+        let included = self.imp().included.borrow();
+        self.imp()
+            .occurrences
+            .borrow()
+            .iter()
+            .zip(included.iter())
+            .filter(|(_, included)| **included)
+            .map(|(occurrence, _)| occurrence.clone())
+            .collect()
+    }
+
+    fn update_occurrences(&self) {
+        let imp = self.imp();
+        let options = FindOptions {
+            case_sensitive: imp.case_sensitive.is_active(),
+            whole_word: imp.whole_word.is_active(),
+            regex: imp.regex.is_active(),
+        };
+
+        let occurrences = match imp.note_file.borrow().as_ref() {
+            Some(note_file) => find::notebook_occurrences(
+                note_file,
+                &imp.find_entry.text(),
+                &imp.replace_entry.text(),
+                &options,
+                imp.include_titles.is_active(),
+            ),
+            None => Ok(Vec::new()),
+        };
+        let occurrences = match occurrences {
+            Ok(occurrences) => occurrences,
+            Err(_) => {
+                self.set_occurrences(Vec::new());
+                imp.summary.set_text("Invalid expression");
+                return;
+            }
+        };
+        self.set_occurrences(occurrences);
+    }
+
+    fn set_occurrences(&self, occurrences: Vec<Occurrence>) {
+        let occurrence_list = &self.imp().occurrence_list;
+        while let Some(child) = occurrence_list.first_child() {
+            occurrence_list.remove(&child);
+        }
+
+        // A check box per note switches all of its occurrences at once
+        let mut note_check_buttons: Vec<(gtk::CheckButton, Vec<gtk::CheckButton>)> = Vec::new();
+        for (index, occurrence) in occurrences.iter().enumerate() {
+            let new_note = index == 0 || occurrences[index - 1].note_path != occurrence.note_path;
+            if new_note {
+                let note_check_button = gtk::CheckButton::builder()
+                    .label(&occurrence.note_title)
+                    .active(true)
+                    .css_classes(vec!["heading".to_string()])
+                    .build();
+                occurrence_list.append(&note_check_button);
+                note_check_buttons.push((note_check_button, Vec::new()));
+            }
+
+            let line = &occurrence.line;
+            let range = &occurrence.line_range;
+            let markup = format!(
+                "{}{}<s>{}</s><b>{}</b>{}",
+                if occurrence.in_title { "Title: " } else { "" },
+                glib::markup_escape_text(&line[..range.start]),
+                glib::markup_escape_text(&line[range.clone()]),
+                glib::markup_escape_text(&occurrence.replacement),
+                glib::markup_escape_text(&line[range.end..]),
+            );
+            let label = gtk::Label::builder()
+                .use_markup(true)
+                .label(&markup)
+                .xalign(0.0)
+                .ellipsize(gtk::pango::EllipsizeMode::End)
+                .build();
+            let check_button = gtk::CheckButton::builder().active(true).build();
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            row.set_margin_start(20);
+            row.append(&check_button);
+            row.append(&label);
+            check_button.connect_toggled(clone!(@weak self as self_clone => move |check_button| {
+                if let Some(included) = self_clone.imp().included.borrow_mut().get_mut(index) {
+                    *included = check_button.is_active();
+                }
+                self_clone.update_summary();
+            }));
+            occurrence_list.append(&row);
+            note_check_buttons.last_mut().unwrap().1.push(check_button);
+        }
+
+        for (note_check_button, check_buttons) in note_check_buttons {
+            note_check_button.connect_toggled(move |note_check_button| {
+                for check_button in &check_buttons {
+                    check_button.set_active(note_check_button.is_active());
+                }
+            });
+        }
+
+        self.imp().included.replace(vec![true; occurrences.len()]);
+        self.imp().occurrences.replace(occurrences);
+        self.update_summary();
+    }
+
+    fn update_summary(&self) {
+        let imp = self.imp();
+        let occurrences = imp.occurrences.borrow();
+        let included = imp.included.borrow();
+
+        let mut note_paths: Vec<&Vec<i32>> = occurrences
+            .iter()
+            .zip(included.iter())
+            .filter(|(_, included)| **included)
+            .map(|(occurrence, _)| &occurrence.note_path)
+            .collect();
+        let included_count = note_paths.len();
+        note_paths.dedup();
+
+        imp.summary.set_text(&if occurrences.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{} of {} occurrences in {} notes",
+                included_count,
+                occurrences.len(),
+                note_paths.len()
+            )
+        });
+        imp.apply_button.set_sensitive(included_count > 0);
+    }
+
+    #[template_callback]
+    fn handle_query_changed(&self) {
+        self.update_occurrences();
+    }
+
+    #[template_callback]
+    fn handle_apply_clicked(&self) {
+        self.emit_by_name::<()>("apply-requested", &[]);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::tools::fuzzy::{self, Candidate};
+use gtk::{
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::cell::{Cell, RefCell};
+
+// Enough to fill the popup, the rest is a few more letters away
+const RESULT_LIMIT: usize = 50;
+
+// What choosing a row of the results does
+#[derive(Debug, Clone)]
+pub enum SwitcherRow {
+    Note(Vec<i32>),
+    Create(String),
+}
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/quick_switcher")]
+    pub struct GnoteQuickSwitcher {
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub results: TemplateChild<gtk::ListBox>,
+
+        pub candidates: RefCell<Vec<Candidate>>,
+        pub rows: RefCell<Vec<SwitcherRow>>,
+        // Seconds since the Unix epoch when the switcher was opened, for ranking by recency
+        pub now: Cell<i64>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteQuickSwitcher {
+        const NAME: &'static str = "GnoteQuickSwitcher";
+        type Type = super::GnoteQuickSwitcher;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteQuickSwitcher {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("row-chosen")
+                        .param_types([<u32>::static_type()])
+                        .build(),
+                    Signal::builder("cancelled").build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // The arrow keys move through the results while typing carries on in the entry
+            let switcher = self.instance();
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.connect_key_pressed(
+                clone!(@weak switcher => @default-return gtk::Inhibit(false), move |_, key, _, _| {
+                    let step = match key {
+                        gtk::gdk::Key::Down => 1,
+                        gtk::gdk::Key::Up => -1,
+                        _ => return gtk::Inhibit(false),
+                    };
+                    switcher.move_selection(step);
+                    gtk::Inhibit(true)
+                }),
+            );
+            self.search_entry.add_controller(&key_controller);
+        }
+    }
+    impl WidgetImpl for GnoteQuickSwitcher {}
+    impl BoxImpl for GnoteQuickSwitcher {}
+}
+
+glib::wrapper! {
+    pub struct GnoteQuickSwitcher(ObjectSubclass<imp::GnoteQuickSwitcher>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteQuickSwitcher {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    pub fn set_candidates(&self, candidates: Vec<Candidate>, now: i64) {
+        self.imp().candidates.replace(candidates);
+        self.imp().now.set(now);
+        self.update_results();
+    }
+
+    pub fn row(&self, index: usize) -> Option<SwitcherRow> {
+        self.imp().rows.borrow().get(index).cloned()
+    }
+
+    fn update_results(&self) {
+        let query = self.imp().search_entry.text().trim().to_string();
+        let results = &self.imp().results;
+        while let Some(child) = results.first_child() {
+            results.remove(&child);
+        }
+
+        let mut rows = Vec::new();
+        {
+            let candidates = self.imp().candidates.borrow();
+            for candidate in fuzzy::rank(&query, &candidates, self.imp().now.get(), RESULT_LIMIT) {
+                let title = gtk::Label::builder()
+                    .label(&candidate.title)
+                    .xalign(0.0)
+                    .build();
+                let location = gtk::Label::builder()
+                    .label(&candidate.location)
+                    .xalign(0.0)
+                    .css_classes(vec!["dim-label".to_string(), "caption".to_string()])
+                    .visible(!candidate.location.is_empty())
+                    .build();
+
+                let row = gtk::Box::new(gtk::Orientation::Vertical, 2);
+                row.set_margin_top(5);
+                row.set_margin_bottom(5);
+                row.append(&title);
+                row.append(&location);
+                results.append(&row);
+                rows.push(SwitcherRow::Note(candidate.note_path.clone()));
+            }
+
+            let exists = candidates
+                .iter()
+                .any(|candidate| candidate.title.to_lowercase() == query.to_lowercase());
+            if !query.is_empty() && !exists {
+                let create = gtk::Label::builder()
+                    .label(&format!("Create “{}”", query))
+                    .xalign(0.0)
+                    .margin_top(5)
+                    .margin_bottom(5)
+                    .build();
+                results.append(&create);
+                rows.push(SwitcherRow::Create(query));
+            }
+        }
+        self.imp().rows.replace(rows);
+
+        if let Some(first_row) = results.row_at_index(0) {
+            results.select_row(Some(&first_row));
+        }
+    }
+
+    fn move_selection(&self, step: i32) {
+        let results = &self.imp().results;
+        let index = results
+            .selected_row()
+            .map_or(0, |row| row.index() + step)
+            .max(0);
+        if let Some(row) = results.row_at_index(index) {
+            results.select_row(Some(&row));
+        }
+    }
+
+    #[template_callback]
+    fn handle_search_changed(&self) {
+        self.update_results();
+    }
+
+    #[template_callback]
+    fn handle_search_activate(&self) {
+        if let Some(row) = self.imp().results.selected_row() {
+            self.handle_row_activated(&row);
+        }
+    }
+
+    #[template_callback]
+    fn handle_stop_search(&self) {
+        self.emit_by_name::<()>("cancelled", &[]);
+    }
+
+    #[template_callback]
+    fn handle_row_activated(&self, row: &gtk::ListBoxRow) {
+        self.emit_by_name::<()>("row-chosen", &[&(row.index() as u32)]);
+    }
+}
@@ -0,0 +1,144 @@
+use gtk::{
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::{cell::RefCell, collections::BTreeMap};
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/tag_list")]
+    pub struct GnoteTagList {
+        #[template_child]
+        pub tag_list: TemplateChild<gtk::ListBox>,
+
+        // Every tag in the notebook with the number of notes carrying it
+        pub tag_counts: RefCell<Vec<(String, usize)>>,
+        pub selected_tags: RefCell<Vec<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteTagList {
+        const NAME: &'static str = "GnoteTagList";
+        type Type = super::GnoteTagList;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteTagList {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("selection-changed").build(),
+                    Signal::builder("rename-requested")
+                        .param_types([<String>::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteTagList {}
+    impl BoxImpl for GnoteTagList {}
+}
+
+glib::wrapper! {
+    pub struct GnoteTagList(ObjectSubclass<imp::GnoteTagList>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+impl GnoteTagList {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    pub fn selected_tags(&self) -> Vec<String> {
+        self.imp().selected_tags.borrow().clone()
+    }
+
+    pub fn set_index(&self, index: &BTreeMap<String, Vec<Vec<i32>>>) {
+        let tag_counts: Vec<(String, usize)> = index
+            .iter()
+            .map(|(name, note_paths)| (name.clone(), note_paths.len()))
+            .collect();
+        // Typing in a note changes the index far less often than it is asked to update
+        if *self.imp().tag_counts.borrow() == tag_counts {
+            return;
+        }
+
+        let selection_changed = {
+            let mut selected_tags = self.imp().selected_tags.borrow_mut();
+            let selected_count = selected_tags.len();
+            selected_tags.retain(|name| index.contains_key(name));
+            selected_tags.len() != selected_count
+        };
+
+        let tag_list = &self.imp().tag_list;
+        while let Some(child) = tag_list.first_child() {
+            tag_list.remove(&child);
+        }
+
+        for (name, count) in &tag_counts {
+            let check_button = gtk::CheckButton::builder()
+                .label(&format!("#{}", name))
+                .active(self.imp().selected_tags.borrow().contains(name))
+                .hexpand(true)
+                .build();
+            check_button.connect_toggled(
+                clone!(@weak self as self_clone, @strong name => move |check_button| {
+                    self_clone.set_tag_selected(&name, check_button.is_active());
+                }),
+            );
+
+            let count_label = gtk::Label::builder()
+                .label(&count.to_string())
+                .css_classes(vec!["dim-label".to_string()])
+                .build();
+
+            let rename_button = gtk::Button::builder()
+                .icon_name("document-edit-symbolic")
+                .tooltip_text("Rename or merge tag")
+                .css_classes(vec!["flat".to_string()])
+                .build();
+            rename_button.connect_clicked(
+                clone!(@weak self as self_clone, @strong name => move |_| {
+                    self_clone.emit_by_name::<()>("rename-requested", &[&name]);
+                }),
+            );
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            row.append(&check_button);
+            row.append(&count_label);
+            row.append(&rename_button);
+            tag_list.append(&row);
+        }
+
+        self.imp().tag_counts.replace(tag_counts);
+        if selection_changed {
+            self.emit_by_name::<()>("selection-changed", &[]);
+        }
+    }
+
+    fn set_tag_selected(&self, name: &str, selected: bool) {
+        {
+            let mut selected_tags = self.imp().selected_tags.borrow_mut();
+            if selected && !selected_tags.iter().any(|tag| tag == name) {
+                selected_tags.push(name.to_string());
+            } else if !selected {
+                selected_tags.retain(|tag| tag != name);
+            }
+        }
+        self.emit_by_name::<()>("selection-changed", &[]);
+    }
+}
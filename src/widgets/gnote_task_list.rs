@@ -0,0 +1,125 @@
+use crate::tools::tasks::Task;
+use gtk::{
+    glib::{self, clone, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
+use std::cell::RefCell;
+
+mod imp {
+    use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/bil4x4/gnote/task_list")]
+    pub struct GnoteTaskList {
+        #[template_child]
+        pub task_list: TemplateChild<gtk::ListBox>,
+
+        pub tasks: RefCell<Vec<Task>>,
+        // Task index for every row in the list, None for the note headers
+        pub rows: RefCell<Vec<Option<usize>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GnoteTaskList {
+        const NAME: &'static str = "GnoteTaskList";
+        type Type = super::GnoteTaskList;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_instance_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for GnoteTaskList {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("task-toggled")
+                        .param_types([<u32>::static_type()])
+                        .build(),
+                    Signal::builder("task-activated")
+                        .param_types([<u32>::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+    }
+    impl WidgetImpl for GnoteTaskList {}
+    impl BoxImpl for GnoteTaskList {}
+}
+
+glib::wrapper! {
+    pub struct GnoteTaskList(ObjectSubclass<imp::GnoteTaskList>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+#[gtk::template_callbacks]
+impl GnoteTaskList {
+    pub fn new() -> Self {
+        Object::new::<Self>(&[])
+    }
+
+    pub fn task(&self, index: usize) -> Option<Task> {
+        self.imp().tasks.borrow().get(index).cloned()
+    }
+
+    pub fn set_tasks(&self, tasks: Vec<Task>) {
+        let task_list = &self.imp().task_list;
+        while let Some(child) = task_list.first_child() {
+            task_list.remove(&child);
+        }
+
+        let mut rows = Vec::new();
+        let mut location = None;
+        for (index, task) in tasks.iter().enumerate() {
+            // Group the tasks under a header for each note
+            if location.as_ref() != Some(&task.note_path) {
+                location = Some(task.note_path.clone());
+
+                let header = gtk::Label::builder()
+                    .label(&task.location())
+                    .halign(gtk::Align::Start)
+                    .margin_top(10)
+                    .css_classes(vec!["heading".to_string()])
+                    .build();
+                let row = gtk::ListBoxRow::builder()
+                    .child(&header)
+                    .activatable(false)
+                    .build();
+                task_list.append(&row);
+                rows.push(None);
+            }
+
+            let check_button = gtk::CheckButton::builder()
+                .label(&task.text)
+                .active(task.checked)
+                .margin_start(10 + 20 * task.indent_level as i32)
+                .build();
+            check_button.connect_toggled(clone!(@weak self as self_clone => move |_| {
+                self_clone.emit_by_name::<()>("task-toggled", &[&(index as u32)]);
+            }));
+            task_list.append(&check_button);
+            rows.push(Some(index));
+        }
+
+        self.imp().rows.replace(rows);
+        self.imp().tasks.replace(tasks);
+    }
+
+    #[template_callback]
+    fn handle_row_activated(&self, row: &gtk::ListBoxRow) {
+        let index = self.imp().rows.borrow().get(row.index() as usize).copied();
+        if let Some(Some(index)) = index {
+            self.emit_by_name::<()>("task-activated", &[&(index as u32)]);
+        }
+    }
+}
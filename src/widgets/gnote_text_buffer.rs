@@ -1,7 +1,21 @@
-use adw::gdk::Display;
+use crate::{
+    tools::{
+        attachments::{self, AttachmentLink, AttachmentStore},
+        checklist,
+        clipboard::{self, PasteFormat},
+        diff,
+        due_date::{self, DueDate, Urgency},
+        emphasis::{self, Style},
+        find,
+        images::ImageSize,
+        io, links, tags, wiki_links,
+    },
+    widgets::{gnote_attachment::GnoteAttachment, gnote_image::GnoteImage},
+};
 use adw::gio::UnixSocketAddressType::Path;
-use gtk::gdk::{ContentFormats, Paintable, Texture};
-use gtk::gio::Cancellable;
+use base64::{engine::general_purpose, Engine};
+use gtk::gdk::{self, ContentFormats, Paintable, Texture};
+use gtk::gio::{self, Cancellable};
 use gtk::{
     builders::FileChooserDialogBuilder,
     gdk::{Key, ModifierType},
@@ -10,13 +24,17 @@ use gtk::{
     interface_age, pango,
     prelude::*,
     subclass::prelude::*,
-    FileChooserAction, GestureClick, Image, Inhibit, ResponseType, TextBuffer, TextView,
+    FileChooserAction, GestureClick, Image, Inhibit, ResponseType, TextBuffer, TextChildAnchor,
+    TextIter, TextView,
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, json, to_string};
 use std::{
     cell::{Cell, RefCell},
+    collections::HashMap,
+    fs,
+    ops::Range,
     sync::Once,
 };
 
@@ -33,17 +51,38 @@ struct TextBufferContent {
     elements: Vec<Element>,
 }
 
-const INDENT: &'static str = "  ";
-const BULLET: char = '•';
-const CHECK_BOX_UNCHECKED: char = '☐';
-const CHECK_BOX_CHECKED: char = '☑';
-const SPECIAL_CHAR_PADDING: &'static str = " "; // After the bullet and check box characters
+pub(crate) const INDENT: &'static str = "  ";
+pub(crate) const BULLET: char = '•';
+pub(crate) const CHECK_BOX_UNCHECKED: char = '☐';
+pub(crate) const CHECK_BOX_CHECKED: char = '☑';
+pub(crate) const SPECIAL_CHAR_PADDING: &'static str = " "; // After the bullet and check box characters
+
+const DUE_DATE_TAG: &'static str = "due-date";
+const DUE_TODAY_TAG: &'static str = "due-today";
+const DUE_OVERDUE_TAG: &'static str = "due-overdue";
+const LINK_TAG: &'static str = "link";
+const WIKI_LINK_TAG: &'static str = "wiki-link";
+const HASHTAG_TAG: &'static str = "hashtag";
+const STRONG_TAG: &'static str = "strong";
+const EMPHASIS_TAG: &'static str = "emphasis";
+const FIND_MATCH_TAG: &'static str = "find-match";
+const FIND_CURRENT_TAG: &'static str = "find-current";
+
+// Stands in for an anchored widget in the buffer's text
+const OBJECT_REPLACEMENT_CHAR: char = '\u{fffc}';
 
 mod imp {
     use super::*;
+    use gtk::glib::subclass::Signal;
+    use once_cell::sync::Lazy;
 
     #[derive(Default)]
-    pub struct GnoteTextBuffer;
+    pub struct GnoteTextBuffer {
+        // Link under the pointer when the context menu was opened
+        pub context_link: RefCell<Option<String>>,
+        // What each anchor in the buffer stands for, kept apart from the widgets showing them
+        pub attachments: RefCell<HashMap<TextChildAnchor, AttachmentLink>>,
+    }
 
     #[glib::object_subclass]
     impl ObjectSubclass for GnoteTextBuffer {
@@ -55,10 +94,75 @@ mod imp {
     impl ObjectImpl for GnoteTextBuffer {
         fn constructed(&self) {
             self.parent_constructed();
+
+            let buffer = self.instance();
+            buffer.create_tag(
+                Some(DUE_DATE_TAG),
+                &[("background", &"#dce7f7"), ("weight", &600)],
+            );
+            buffer.create_tag(
+                Some(DUE_TODAY_TAG),
+                &[("background", &"#fde7c2"), ("foreground", &"#9c4f00")],
+            );
+            buffer.create_tag(
+                Some(DUE_OVERDUE_TAG),
+                &[("background", &"#f8d0d0"), ("foreground", &"#a51d2d")],
+            );
+            buffer.create_tag(
+                Some(LINK_TAG),
+                &[
+                    ("foreground", &"#1a5fb4"),
+                    ("underline", &pango::Underline::Single),
+                ],
+            );
+            buffer.create_tag(
+                Some(WIKI_LINK_TAG),
+                &[
+                    ("foreground", &"#613583"),
+                    ("underline", &pango::Underline::Single),
+                ],
+            );
+            buffer.create_tag(
+                Some(HASHTAG_TAG),
+                &[("foreground", &"#26a269"), ("weight", &600)],
+            );
+            buffer.create_tag(Some(STRONG_TAG), &[("weight", &700)]);
+            buffer.create_tag(Some(EMPHASIS_TAG), &[("style", &pango::Style::Italic)]);
+            buffer.create_tag(Some(FIND_MATCH_TAG), &[("background", &"#f9f06b")]);
+            buffer.create_tag(Some(FIND_CURRENT_TAG), &[("background", &"#ffa348")]);
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("wiki-link-activated")
+                        .param_types([<String>::static_type()])
+                        .build(),
+                    // Something went wrong that the user should hear about
+                    Signal::builder("error")
+                        .param_types([<String>::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
         }
     }
     impl WidgetImpl for GnoteTextBuffer {}
-    impl TextBufferImpl for GnoteTextBuffer {}
+    impl TextBufferImpl for GnoteTextBuffer {
+        // Undo would put back the character an anchor sits on but not the anchor, leaving an
+        // attachment that is neither shown nor saved, so removing one can't be undone
+        fn delete_range(&self, start: &mut TextIter, end: &mut TextIter) {
+            let removes_anchor = start.slice(end).contains(OBJECT_REPLACEMENT_CHAR);
+            let buffer = self.instance();
+            if removes_anchor {
+                buffer.begin_irreversible_action();
+            }
+            self.parent_delete_range(start, end);
+            if removes_anchor {
+                buffer.end_irreversible_action();
+            }
+        }
+    }
 }
 
 glib::wrapper! {
@@ -71,16 +175,24 @@ impl GnoteTextBuffer {
         Object::new::<Self>(&[])
     }
 
-    pub fn init(&self, text_view: &gtk::TextView) {
+    // Sets up the note view once, acting on whichever note's buffer it shows at the time
+    pub fn init(text_view: &gtk::TextView) {
         let key_controller = gtk::EventControllerKey::new();
         text_view.add_controller(&key_controller);
 
-        let self_clone = self.clone();
-        key_controller.connect_key_pressed(move |_controller, key, _keycode, state| {
+        key_controller.connect_key_pressed(clone!(@weak text_view => @default-return Inhibit(false), move |_controller, key, _keycode, state| {
+            // Ctrl+Shift+V leaves out everything but the text
+            if key.to_lower() == Key::v && state.contains(ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK) {
+                if let Some(buffer) = shown_buffer(&text_view) {
+                    buffer.paste_unformatted(&text_view);
+                }
+                return Inhibit(true);
+            }
+
             let is_enter_or_return = key == Key::Return || key == Key::KP_Enter;
 
             if is_enter_or_return && !state.contains(ModifierType::SHIFT_MASK) {
-                let buffer = self_clone.imp().instance();
+                let buffer = text_view.buffer();
 
                 let mut current_iter = buffer.iter_at_mark(&buffer.get_insert());
                 let mut start = current_iter.clone();
@@ -117,6 +229,7 @@ impl GnoteTextBuffer {
                                     first_char = CHECK_BOX_UNCHECKED;
                                 }
 
+                                buffer.begin_user_action();
                                 buffer.insert(
                                     &mut current_iter,
                                     format!(
@@ -127,6 +240,7 @@ impl GnoteTextBuffer {
                                     )
                                     .as_str(),
                                 );
+                                buffer.end_user_action();
                                 return Inhibit(true);
                             }
                         }
@@ -134,12 +248,12 @@ impl GnoteTextBuffer {
                 }
             }
             Inhibit(false)
-        });
+        }));
 
         let gesture_click = GestureClick::new();
-        gesture_click.connect_pressed(clone!(@weak self as self_clone, @weak text_view => move |_gesture, n_press, x, y| {
+        gesture_click.connect_pressed(clone!(@weak text_view => move |_gesture, n_press, x, y| {
             if n_press == 1 { // Single click
-                let buffer = self_clone.imp().instance();
+                let buffer = text_view.buffer();
                 if let Some(mut iter) = text_view.iter_at_location(x as i32, y as i32) {
                     iter.backward_char();
 
@@ -147,34 +261,337 @@ impl GnoteTextBuffer {
                         let new_check_box_char = if iter.char() == CHECK_BOX_CHECKED { CHECK_BOX_UNCHECKED } else { CHECK_BOX_CHECKED };
                         let mut end_iter = iter.clone();
                         end_iter.forward_char();
+                        buffer.begin_user_action();
                         buffer.delete(&mut iter, &mut end_iter);
                         buffer.insert(&mut iter, &new_check_box_char.to_string());
+                        buffer.end_user_action();
                     }
                 }
             }
         }));
         text_view.add_controller(&gesture_click);
 
+        // Ctrl+click opens the link under the pointer
+        let link_click = GestureClick::new();
+        link_click.connect_released(clone!(@weak text_view => move |gesture, n_press, x, y| {
+            if n_press != 1 || !gesture.current_event_state().contains(ModifierType::CONTROL_MASK) {
+                return;
+            }
+            let self_clone = match shown_buffer(&text_view) {
+                Some(buffer) => buffer,
+                None => return,
+            };
+            if let Some(uri) = self_clone.link_at_location(&text_view, x, y) {
+                let window = text_view.root().and_then(|root| root.downcast::<gtk::Window>().ok());
+                gtk::show_uri(window.as_ref(), &uri, gtk::gdk::CURRENT_TIME);
+            } else if let Some(title) = self_clone.wiki_link_at_location(&text_view, x, y) {
+                self_clone.emit_by_name::<()>("wiki-link-activated", &[&title]);
+            }
+        }));
+        text_view.add_controller(&link_click);
+
+        // Show where a link goes when hovering over it
+        let motion = gtk::EventControllerMotion::new();
+        motion.connect_motion(clone!(@weak text_view => move |_, x, y| {
+            let target = shown_buffer(&text_view).and_then(|buffer| {
+                buffer
+                    .link_at_location(&text_view, x, y)
+                    .or_else(|| buffer.wiki_link_at_location(&text_view, x, y).map(|title| format!("“{}”", title)))
+            });
+            match target {
+                Some(target) => {
+                    text_view.set_tooltip_text(Some(format!("{}\nCtrl+click to open", target).as_str()));
+                    text_view.set_cursor_from_name(Some("pointer"));
+                }
+                None => {
+                    text_view.set_tooltip_text(None);
+                    text_view.set_cursor_from_name(Some("text"));
+                }
+            }
+        }));
+        text_view.add_controller(&motion);
+
+        let copy_link_action = gio::SimpleAction::new("copy-link", None);
+        copy_link_action.connect_activate(clone!(@weak text_view => move |_, _| {
+            if let Some(buffer) = shown_buffer(&text_view) {
+                if let Some(uri) = buffer.imp().context_link.borrow().as_ref() {
+                    text_view.clipboard().set_text(uri.trim_start_matches("mailto:"));
+                }
+            }
+        }));
+        let paste_unformatted_action = gio::SimpleAction::new("paste-unformatted", None);
+        paste_unformatted_action.connect_activate(clone!(@weak text_view => move |_, _| {
+            if let Some(buffer) = shown_buffer(&text_view) {
+                buffer.paste_unformatted(&text_view);
+            }
+        }));
+        let note_actions = gio::SimpleActionGroup::new();
+        note_actions.add_action(&copy_link_action);
+        note_actions.add_action(&paste_unformatted_action);
+        text_view.insert_action_group("note", Some(&note_actions));
+
+        let extra_menu = gio::Menu::new();
+        extra_menu.append(Some("Copy Link"), Some("note.copy-link"));
+        extra_menu.append(Some("Paste Unformatted"), Some("note.paste-unformatted"));
+        text_view.set_extra_menu(Some(&extra_menu));
+
+        // Remember the link that was right clicked on so the context menu can copy it
+        let context_click = GestureClick::builder()
+            .button(gtk::gdk::BUTTON_SECONDARY)
+            .build();
+        context_click.connect_pressed(
+            clone!(@weak text_view, @weak copy_link_action => move |_, _, x, y| {
+                if let Some(buffer) = shown_buffer(&text_view) {
+                    let link = buffer.link_at_location(&text_view, x, y);
+                    copy_link_action.set_enabled(link.is_some());
+                    buffer.imp().context_link.replace(link);
+                }
+            }),
+        );
+        text_view.add_controller(&context_click);
+
+        // Dropped content is taken in the same way as pasted content
+        let drop_formats = gdk::ContentFormatsBuilder::new()
+            .add_mime_type(clipboard::NOTE_MIME_TYPE)
+            .add_gtype(Texture::static_type())
+            .add_gtype(String::static_type())
+            .add_mime_type("text/uri-list")
+            .add_mime_type("text/html")
+            .build()
+            .union_deserialize_mime_types();
+        let drop_target = gtk::DropTargetAsync::new(Some(&drop_formats), gdk::DragAction::COPY);
+        // Ahead of the note view's own handling, which is still left text dragged within the app
+        drop_target.set_propagation_phase(gtk::PropagationPhase::Capture);
+        drop_target.connect_accept(|_, drop| {
+            drop.drag().is_none()
+                || !matches!(
+                    paste_format(&drop.formats()),
+                    Some(PasteFormat::Text) | None
+                )
+        });
+        drop_target.connect_drop(clone!(@weak text_view => @default-return false, move |_, drop, x, y| {
+            let buffer = match shown_buffer(&text_view) {
+                Some(buffer) => buffer,
+                None => return false,
+            };
+            let (x, y) = text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+            if let Some(iter) = text_view.iter_at_location(x, y) {
+                buffer.place_cursor(&iter);
+            }
+            buffer.paste(&text_view, PasteSource::Drop(drop.clone()));
+            true
+        }));
+        text_view.add_controller(&drop_target);
+
+        // Other applications get the selection as Markdown and HTML, other notes get it as it is
+        text_view.connect_copy_clipboard(|text_view| {
+            if let Some(buffer) = shown_buffer(text_view) {
+                if let Some((start, end)) = buffer.selection_bounds() {
+                    text_view.stop_signal_emission_by_name("copy-clipboard");
+                    buffer.copy_to_clipboard(text_view, &buffer.note_text_between(&start, &end));
+                }
+            }
+        });
+        text_view.connect_cut_clipboard(|text_view| {
+            if let Some(buffer) = shown_buffer(text_view) {
+                if let Some((start, end)) = buffer.selection_bounds() {
+                    text_view.stop_signal_emission_by_name("cut-clipboard");
+                    buffer.copy_to_clipboard(text_view, &buffer.note_text_between(&start, &end));
+                    buffer.delete_selection(true, text_view.is_editable());
+                }
+            }
+        });
+
         text_view.connect_paste_clipboard(|text_view| {
-            if let Some(display) = Display::default() {
-                let clipboard = display.clipboard();
-                clipboard.read_texture_async(
-                    Cancellable::NONE,
-                    clone!(@weak text_view => move |res| {
-                        let buffer = text_view.buffer();
-                        if let Ok(Some(texture)) = res {
-                            let mut start = buffer.iter_at_mark(&buffer.get_insert());
-                            buffer.insert_paintable(&mut start, &texture);
-                        }
-                    }),
-                );
+            let buffer = match shown_buffer(text_view) {
+                Some(buffer) => buffer,
+                None => return,
+            };
+            let clipboard = text_view.clipboard();
+            // Plain text is left to the note view itself
+            if matches!(
+                paste_format(&clipboard.formats()),
+                Some(PasteFormat::Text) | None
+            ) {
+                return;
             }
+            text_view.stop_signal_emission_by_name("paste-clipboard");
+            buffer.paste(text_view, PasteSource::Clipboard(clipboard));
         });
     }
 
-    pub fn insert_image(&self, text_view: &gtk::TextView) {
+    // Takes in pasted or dropped content in the richest format it is offered in
+    fn paste(&self, text_view: &TextView, source: PasteSource) {
+        let finishing_source = source.clone();
+        let done = clone!(@weak self as buffer => move |result: Result<(), String>| {
+            if let Err(e) = &result {
+                let message = format!("Couldn't paste: {}", e);
+                buffer.emit_by_name::<()>("error", &[&message]);
+            }
+            finishing_source.finish(result.is_ok());
+        });
+
+        match paste_format(&source.formats()) {
+            Some(PasteFormat::Note) => source.read_bytes(
+                clipboard::NOTE_MIME_TYPE,
+                clone!(@weak self as buffer, @weak text_view => move |result| {
+                    match result {
+                        Ok(bytes) => {
+                            buffer.insert_pasted_text(&text_view, &String::from_utf8_lossy(&bytes));
+                            done(Ok(()));
+                        }
+                        Err(e) => done(Err(e)),
+                    }
+                }),
+            ),
+            Some(PasteFormat::Image) => source.read_value(
+                Texture::static_type(),
+                clone!(@weak self as buffer, @weak text_view => move |result| {
+                    match result.and_then(|value| value.get::<Texture>().map_err(|e| e.to_string())) {
+                        Ok(texture) => {
+                            buffer.insert_texture(&text_view, &texture);
+                            done(Ok(()));
+                        }
+                        Err(e) => done(Err(e)),
+                    }
+                }),
+            ),
+            Some(PasteFormat::UriList) => source.read_bytes(
+                "text/uri-list",
+                clone!(@weak self as buffer, @weak text_view => move |result| {
+                    match result {
+                        Ok(bytes) => {
+                            let uris = clipboard::parse_uri_list(&String::from_utf8_lossy(&bytes));
+                            buffer.insert_uris(&text_view, &uris);
+                            done(Ok(()));
+                        }
+                        Err(e) => done(Err(e)),
+                    }
+                }),
+            ),
+            Some(PasteFormat::Html) => source.read_bytes(
+                "text/html",
+                clone!(@weak self as buffer, @weak text_view => move |result| {
+                    match result {
+                        Ok(bytes) => {
+                            let note = clipboard::html_to_note(&clipboard::decode_html(&bytes));
+                            buffer.insert_pasted_text(&text_view, &note);
+                            done(Ok(()));
+                        }
+                        Err(e) => done(Err(e)),
+                    }
+                }),
+            ),
+            Some(PasteFormat::Text) => source.read_value(
+                String::static_type(),
+                clone!(@weak self as buffer, @weak text_view => move |result| {
+                    match result.and_then(|value| value.get::<String>().map_err(|e| e.to_string())) {
+                        Ok(text) => {
+                            buffer.insert_pasted_text(&text_view, &text);
+                            done(Ok(()));
+                        }
+                        Err(e) => done(Err(e)),
+                    }
+                }),
+            ),
+            None => source.finish(false),
+        }
+    }
+
+    fn copy_to_clipboard(&self, text_view: &TextView, note: &str) {
+        let markdown = clipboard::note_to_markdown(note, &attachment_file_uri);
+        let html = clipboard::note_to_html(note, &attachment_html_uri);
+        let content = gdk::ContentProvider::new_union(&[
+            gdk::ContentProvider::for_bytes(
+                clipboard::NOTE_MIME_TYPE,
+                &glib::Bytes::from_owned(note.to_string().into_bytes()),
+            ),
+            gdk::ContentProvider::for_bytes(
+                "text/html",
+                &glib::Bytes::from_owned(html.into_bytes()),
+            ),
+            gdk::ContentProvider::for_value(&markdown.to_value()),
+        ]);
+        self.set_clipboard_content(text_view, &content);
+    }
+
+    // The whole note, headed by its title
+    pub fn copy_as_markdown(&self, text_view: &TextView, title: &str) {
+        let markdown = clipboard::note_to_markdown(&self.note_text(), &attachment_file_uri);
+        text_view
+            .clipboard()
+            .set_text(&format!("# {}\n\n{}", title, markdown));
+    }
+
+    pub fn copy_as_html(&self, text_view: &TextView, title: &str) {
+        let note = self.note_text();
+        let markdown = clipboard::note_to_markdown(&note, &attachment_file_uri);
+        let html = format!(
+            "<h1>{}</h1>{}",
+            clipboard::escape_html(title),
+            clipboard::note_to_html(&note, &attachment_html_uri)
+        );
+        let content = gdk::ContentProvider::new_union(&[
+            gdk::ContentProvider::for_bytes(
+                "text/html",
+                &glib::Bytes::from_owned(html.into_bytes()),
+            ),
+            gdk::ContentProvider::for_value(&format!("# {}\n\n{}", title, markdown).to_value()),
+        ]);
+        self.set_clipboard_content(text_view, &content);
+    }
+
+    fn set_clipboard_content(&self, text_view: &TextView, content: &gdk::ContentProvider) {
+        if let Err(e) = text_view.clipboard().set_content(Some(content)) {
+            let message = format!("Couldn't copy: {}", e);
+            self.emit_by_name::<()>("error", &[&message]);
+        }
+    }
+
+    pub fn paste_unformatted(&self, text_view: &TextView) {
+        text_view.clipboard().read_text_async(
+            Cancellable::NONE,
+            clone!(@weak self as buffer, @weak text_view => move |result| {
+                if let Ok(Some(text)) = result {
+                    buffer.begin_user_action();
+                    buffer.delete_selection(true, text_view.is_editable());
+                    buffer.insert_interactive_at_cursor(&text, text_view.is_editable());
+                    buffer.end_user_action();
+                    text_view.scroll_mark_onscreen(&buffer.get_insert());
+                }
+            }),
+        );
+    }
+
+    // Pasted text replaces the selection as typing would, with its attachment links shown
+    fn insert_pasted_text(&self, text_view: &TextView, text: &str) {
         let buffer = self.imp().instance();
+        buffer.begin_user_action();
+        buffer.delete_selection(true, text_view.is_editable());
+        let mut iter = buffer.iter_at_mark(&buffer.get_insert());
+        self.insert_note_text(&mut iter, text);
+        buffer.end_user_action();
+        self.attach_widgets(text_view);
+        // The anchors went in before there were links to save for them
+        buffer.emit_by_name::<()>("changed", &[]);
+        text_view.scroll_mark_onscreen(&buffer.get_insert());
+    }
+
+    // Local files are attached, anything else goes in as a link
+    fn insert_uris(&self, text_view: &TextView, uris: &[String]) {
+        for (index, uri) in uris.iter().enumerate() {
+            if index > 0 {
+                self.insert_pasted_text(text_view, "\n");
+            }
+            if uri.starts_with("file:") {
+                self.insert_file(text_view, &gio::File::for_uri(uri));
+            } else {
+                self.insert_pasted_text(text_view, uri);
+            }
+        }
+    }
 
+    pub fn insert_image(&self, text_view: &gtk::TextView) {
         // Create a new FileChooserDialog
         let file_chooser = FileChooserDialogBuilder::new()
             .title("Choose an image file")
@@ -183,15 +600,18 @@ impl GnoteTextBuffer {
 
         file_chooser.add_buttons(&[("Open", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
 
-        file_chooser.connect_response(clone!(@weak buffer => move |dialog, response| {
+        file_chooser.connect_response(clone!(@weak self as buffer, @weak text_view => move |dialog, response| {
             if response == ResponseType::Ok {
                 if let Some(file) = dialog.file() {
                     if let Some(file_path) = file.path() {
-                        // Load the image into a Pixbuf
-                        let pixbuf = gdk_pixbuf::Pixbuf::from_file(&file_path).expect("Could not load image");
-                        let texture = Texture::for_pixbuf(&pixbuf);
-                        let mut start = buffer.iter_at_mark(&buffer.get_insert());
-                        buffer.insert_paintable(&mut start, &texture);
+                        // Only images that can be shown go into the store
+                        match Texture::from_file(&file) {
+                            Ok(_) => buffer.insert_stored(&text_view, true, "", attachment_store().add_file(&file_path)),
+                            Err(e) => {
+                                let message = format!("Couldn't open {}: {}", file_path.display(), e.message());
+                                buffer.emit_by_name::<()>("error", &[&message]);
+                            }
+                        }
                     }
                 }
             }
@@ -201,6 +621,242 @@ impl GnoteTextBuffer {
         file_chooser.present();
     }
 
+    pub fn attach_file(&self, text_view: &gtk::TextView) {
+        let window = text_view
+            .root()
+            .and_then(|root| root.downcast::<gtk::Window>().ok());
+        let file_chooser = FileChooserDialogBuilder::new()
+            .title("Choose a file to attach")
+            .action(FileChooserAction::Open)
+            .modal(true)
+            .build();
+        file_chooser.set_transient_for(window.as_ref());
+        file_chooser.add_buttons(&[
+            ("Attach", ResponseType::Ok),
+            ("Cancel", ResponseType::Cancel),
+        ]);
+
+        file_chooser.connect_response(
+            clone!(@weak self as buffer, @weak text_view => move |dialog, response| {
+                if response == ResponseType::Ok {
+                    if let Some(file) = dialog.file() {
+                        buffer.insert_file(&text_view, &file);
+                    }
+                }
+                dialog.destroy();
+            }),
+        );
+
+        file_chooser.present();
+    }
+
+    // Images are shown as images, anything else as a chip to open it from
+    pub fn insert_file(&self, text_view: &gtk::TextView, file: &gio::File) {
+        let file_path = match file.path() {
+            Some(file_path) => file_path,
+            None => return,
+        };
+        let file_name = file_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (content_type, _) = gio::content_type_guess(Some(&file_name), &[]);
+        let is_image = content_type.starts_with("image/") && Texture::from_file(file).is_ok();
+        let label = if is_image { "" } else { file_name.as_str() };
+        self.insert_stored(
+            text_view,
+            is_image,
+            label,
+            attachment_store().add_file(&file_path),
+        );
+    }
+
+    pub fn insert_texture(&self, text_view: &gtk::TextView, texture: &Texture) {
+        self.insert_stored(text_view, true, "", store_texture(texture));
+    }
+
+    // Links to what was just put into the attachment store, at the cursor
+    fn insert_stored(
+        &self,
+        text_view: &gtk::TextView,
+        is_image: bool,
+        label: &str,
+        stored: Result<String, Box<dyn std::error::Error>>,
+    ) {
+        match stored {
+            Ok(name) => self.insert_attachment(
+                text_view,
+                AttachmentLink {
+                    is_image,
+                    label: label.to_string(),
+                    name,
+                    size: None,
+                },
+            ),
+            Err(e) => {
+                let what = if is_image { "the image" } else { label };
+                let message = format!("Couldn't attach {}: {}", what, e);
+                self.emit_by_name::<()>("error", &[&message]);
+            }
+        }
+    }
+
+    fn insert_attachment(&self, text_view: &gtk::TextView, link: AttachmentLink) {
+        let buffer = self.imp().instance();
+        let mut start = buffer.iter_at_mark(&buffer.get_insert());
+        buffer.begin_user_action();
+        let anchor = buffer.create_child_anchor(&mut start);
+        buffer.end_user_action();
+
+        self.imp()
+            .attachments
+            .borrow_mut()
+            .insert(anchor.clone(), link.clone());
+        self.attach_widget(text_view, &anchor, &link);
+        // The anchor went in before there was a link to save for it
+        buffer.emit_by_name::<()>("changed", &[]);
+    }
+
+    // The note as it is saved, with a link in place of every attachment
+    pub fn note_text(&self) -> String {
+        let (start, end) = self.bounds();
+        self.note_text_between(&start, &end)
+    }
+
+    pub fn note_text_between(&self, start: &TextIter, end: &TextIter) -> String {
+        let buffer = self.imp().instance();
+        let attachments = self.imp().attachments.borrow();
+        let mut text = String::new();
+        let mut iter = start.clone();
+        for c in buffer.slice(start, end, true).chars() {
+            if c != OBJECT_REPLACEMENT_CHAR {
+                text.push(c);
+            } else if let Some(link) = iter
+                .child_anchor()
+                .and_then(|anchor| attachments.get(&anchor))
+            {
+                text.push_str(&link.to_markup());
+            }
+            iter.forward_char();
+        }
+        text
+    }
+
+    // Puts in the text of a note with an anchor in place of every attachment link
+    fn insert_note_text(&self, iter: &mut TextIter, text: &str) {
+        let buffer = self.imp().instance();
+        let mut last_end = 0;
+        for (range, link) in attachments::parse(text) {
+            buffer.insert(iter, &text[last_end..range.start]);
+            let anchor = buffer.create_child_anchor(iter);
+            self.imp().attachments.borrow_mut().insert(anchor, link);
+            last_end = range.end;
+        }
+        buffer.insert(iter, &text[last_end..]);
+    }
+
+    // Opening a note isn't something to undo
+    pub fn load_note_text(&self, text: &str) {
+        let buffer = self.imp().instance();
+        buffer.begin_irreversible_action();
+        let (mut start, mut end) = buffer.bounds();
+        buffer.delete(&mut start, &mut end);
+        self.insert_note_text(&mut start, text);
+        buffer.end_irreversible_action();
+    }
+
+    // Replaces the whole note as a single edit
+    pub fn replace_note_text(&self, text_view: &gtk::TextView, text: &str) {
+        let buffer = self.imp().instance();
+        buffer.begin_user_action();
+        let (mut start, mut end) = buffer.bounds();
+        buffer.delete(&mut start, &mut end);
+        self.insert_note_text(&mut start, text);
+        buffer.end_user_action();
+        self.attach_widgets(text_view);
+    }
+
+    // The note view drops the widgets of attachments whenever it switches to another note, so
+    // they are given new ones each time the note is shown
+    pub fn attach_widgets(&self, text_view: &gtk::TextView) {
+        let unattached: Vec<(TextChildAnchor, AttachmentLink)> = self
+            .imp()
+            .attachments
+            .borrow()
+            .iter()
+            .filter(|(anchor, _)| !anchor.is_deleted() && anchor.widgets().is_empty())
+            .map(|(anchor, link)| (anchor.clone(), link.clone()))
+            .collect();
+        for (anchor, link) in unattached {
+            self.attach_widget(text_view, &anchor, &link);
+        }
+    }
+
+    fn attach_widget(
+        &self,
+        text_view: &gtk::TextView,
+        anchor: &TextChildAnchor,
+        link: &AttachmentLink,
+    ) {
+        let path = attachment_store().path(&link.name);
+        if !link.is_image {
+            let attachment = GnoteAttachment::new(&link.label, path);
+            attachment.connect_local(
+                "error",
+                false,
+                clone!(@weak self as buffer => @default-return None, move |values| {
+                    let message = values[1].get::<String>().unwrap();
+                    buffer.emit_by_name::<()>("error", &[&message]);
+                    None
+                }),
+            );
+            text_view.add_child_at_anchor(&attachment, anchor);
+            return;
+        }
+
+        let texture = match Texture::from_file(&gio::File::for_path(&path)) {
+            Ok(texture) => texture,
+            Err(e) => {
+                let message = format!("Couldn't load {}: {}", path.display(), e.message());
+                self.emit_by_name::<()>("error", &[&message]);
+                return;
+            }
+        };
+
+        let image = GnoteImage::new(&texture);
+        image.set_caption(&link.label);
+        image.connect_local(
+            "changed",
+            false,
+            clone!(@weak self as buffer, @weak anchor => @default-return None, move |values| {
+                let image = values[0].get::<GnoteImage>().unwrap();
+                if let Some(link) = buffer.imp().attachments.borrow_mut().get_mut(&anchor) {
+                    link.label = image.caption();
+                    link.size = Some(image.size().name().to_string());
+                }
+                buffer.emit_by_name::<()>("changed", &[]);
+                None
+            }),
+        );
+        image.connect_local(
+            "error",
+            false,
+            clone!(@weak self as buffer => @default-return None, move |values| {
+                let message = values[1].get::<String>().unwrap();
+                buffer.emit_by_name::<()>("error", &[&message]);
+                None
+            }),
+        );
+        text_view.add_child_at_anchor(&image, anchor);
+        // Sized once it knows how wide the note view is
+        image.set_size(
+            link.size
+                .as_deref()
+                .and_then(ImageSize::from_name)
+                .unwrap_or_default(),
+        );
+    }
+
     pub fn insert_check_box(&self, text_view: &gtk::TextView) {
         let buffer = self.imp().instance();
         let mut start = buffer.iter_at_mark(&buffer.get_insert());
@@ -212,8 +868,10 @@ impl GnoteTextBuffer {
         let line_text = CHECK_BOX_UNCHECKED.to_string()
             + SPECIAL_CHAR_PADDING
             + buffer.text(&start, &end, false).trim_start();
+        buffer.begin_user_action();
         buffer.delete(&mut start, &mut end);
         buffer.insert(&mut start, &line_text);
+        buffer.end_user_action();
     }
 
     pub fn insert_bullet(&self) {
@@ -227,8 +885,10 @@ impl GnoteTextBuffer {
         let line_text = BULLET.to_string()
             + SPECIAL_CHAR_PADDING
             + buffer.text(&start, &end, false).trim_start();
+        buffer.begin_user_action();
         buffer.delete(&mut start, &mut end);
         buffer.insert(&mut start, &line_text);
+        buffer.end_user_action();
     }
 
     pub fn indent_more(&self) {
@@ -236,7 +896,9 @@ impl GnoteTextBuffer {
         let mut current_iter = buffer.iter_at_mark(&buffer.get_insert());
         let mut start_of_line = buffer.iter_at_line(current_iter.line()).unwrap();
 
+        buffer.begin_user_action();
         buffer.insert(&mut start_of_line, INDENT);
+        buffer.end_user_action();
     }
 
     pub fn indent_less(&self) {
@@ -250,7 +912,439 @@ impl GnoteTextBuffer {
         let text_at_start = buffer.text(&start_of_line, &end_of_indent, false);
 
         if text_at_start.as_str() == INDENT {
+            buffer.begin_user_action();
             buffer.delete(&mut start_of_line, &mut end_of_indent);
+            buffer.end_user_action();
+        }
+    }
+
+    pub fn checklist_progress(&self) -> checklist::ChecklistProgress {
+        let buffer = self.imp().instance();
+        checklist::progress(&buffer.text(&buffer.start_iter(), &buffer.end_iter(), false))
+    }
+
+    pub fn move_completed_to_bottom(&self) {
+        self.rewrite_checklists(checklist::move_completed_to_bottom);
+    }
+
+    pub fn clear_completed(&self) {
+        self.rewrite_checklists(checklist::clear_completed);
+    }
+
+    pub fn uncheck_all(&self) {
+        self.rewrite_checklists(checklist::uncheck_all);
+    }
+
+    fn rewrite_checklists(&self, rewrite: fn(&[&str]) -> Vec<String>) {
+        let buffer = self.imp().instance();
+        // Attachments are kept as their links, which stay on the line they are on
+        let text = self.note_text();
+        let lines: Vec<&str> = text.lines().collect();
+
+        buffer.begin_user_action();
+        // Work from the last list up so the line numbers of earlier lists stay valid
+        for range in checklist::list_ranges(&text).into_iter().rev() {
+            let list_lines = &lines[range.clone()];
+            let rewritten = rewrite(list_lines);
+            if rewritten
+                .iter()
+                .map(String::as_str)
+                .eq(list_lines.iter().copied())
+            {
+                continue;
+            }
+
+            let mut start = buffer.iter_at_line(range.start as i32).unwrap();
+            let mut end = buffer.iter_at_line(range.end as i32 - 1).unwrap();
+            end.forward_to_line_end();
+            if rewritten.is_empty() {
+                // Take the line break along with the removed list
+                if !end.forward_char() {
+                    start.backward_char();
+                }
+            }
+
+            buffer.delete(&mut start, &mut end);
+            self.insert_note_text(&mut start, &rewritten.join("\n"));
+        }
+        buffer.end_user_action();
+    }
+
+    // Adds or replaces the due date of the check box on the cursor's line
+    pub fn set_due_date(&self, due: &DueDate) {
+        let buffer = self.imp().instance();
+        let mut start = buffer.iter_at_mark(&buffer.get_insert());
+        start.set_line_offset(0);
+
+        let mut end = start.clone();
+        end.forward_to_line_end();
+
+        let line_text = buffer.slice(&start, &end, true).to_string();
+        if checklist::parse_check_box_line(&line_text).is_none() {
+            return;
+        }
+
+        // Only what changes is touched, leaving any attachment on the line in place
+        let new_line = due_date::set(&line_text, due);
+        let (range, replacement) = diff::changed_range(&line_text, &new_line);
+        start.forward_chars(line_text[..range.start].chars().count() as i32);
+        let mut changed_end = start.clone();
+        changed_end.forward_chars(line_text[range].chars().count() as i32);
+
+        buffer.begin_user_action();
+        buffer.delete(&mut start, &mut changed_end);
+        buffer.insert(&mut start, replacement);
+        buffer.end_user_action();
+    }
+
+    // Styles the due dates on check box lines as chips, coloured by how soon they are due
+    pub fn highlight_due_dates(&self, today: &DueDate) {
+        let buffer = self.imp().instance();
+        let (start, end) = buffer.bounds();
+        for tag in [DUE_DATE_TAG, DUE_TODAY_TAG, DUE_OVERDUE_TAG] {
+            buffer.remove_tag_by_name(tag, &start, &end);
+        }
+
+        let text = buffer.slice(&start, &end, true);
+        for (line_number, line) in text.lines().enumerate() {
+            if checklist::parse_check_box_line(line).is_none() {
+                continue;
+            }
+
+            if let Some((range, due)) = due_date::find(line) {
+                let tag = match due.urgency(today) {
+                    Urgency::Overdue => DUE_OVERDUE_TAG,
+                    Urgency::Today => DUE_TODAY_TAG,
+                    Urgency::Upcoming => DUE_DATE_TAG,
+                };
+                self.apply_tag_to_line(tag, line_number, line, range);
+            }
+        }
+    }
+
+    // Styles every URL, email address and link to another note as a link
+    pub fn highlight_links(&self) {
+        let buffer = self.imp().instance();
+        let (start, end) = buffer.bounds();
+        buffer.remove_tag_by_name(LINK_TAG, &start, &end);
+
+        buffer.remove_tag_by_name(WIKI_LINK_TAG, &start, &end);
+
+        let text = buffer.slice(&start, &end, true);
+        for (line_number, line) in text.lines().enumerate() {
+            for link in links::find(line) {
+                self.apply_tag_to_line(LINK_TAG, line_number, line, link.range);
+            }
+            for wiki_link in wiki_links::find(line) {
+                self.apply_tag_to_line(WIKI_LINK_TAG, line_number, line, wiki_link.range);
+            }
+        }
+    }
+
+    pub fn highlight_emphasis(&self) {
+        let buffer = self.imp().instance();
+        let (start, end) = buffer.bounds();
+        buffer.remove_tag_by_name(STRONG_TAG, &start, &end);
+        buffer.remove_tag_by_name(EMPHASIS_TAG, &start, &end);
+
+        let text = buffer.slice(&start, &end, true);
+        for (line_number, line) in text.lines().enumerate() {
+            for span in emphasis::find(line) {
+                let tag = match span.style {
+                    Style::Strong => STRONG_TAG,
+                    Style::Emphasis => EMPHASIS_TAG,
+                };
+                self.apply_tag_to_line(tag, line_number, line, span.range);
+            }
+        }
+    }
+
+    pub fn highlight_tags(&self) {
+        let buffer = self.imp().instance();
+        let (start, end) = buffer.bounds();
+        buffer.remove_tag_by_name(HASHTAG_TAG, &start, &end);
+
+        let text = buffer.slice(&start, &end, true);
+        for (line_number, line) in text.lines().enumerate() {
+            for tag in tags::find(line) {
+                self.apply_tag_to_line(HASHTAG_TAG, line_number, line, tag.range);
+            }
+        }
+    }
+
+    fn link_at_location(&self, text_view: &TextView, x: f64, y: f64) -> Option<String> {
+        let link_text = self.tagged_text_at_location(text_view, x, y, LINK_TAG)?;
+        links::uri_for(&link_text)
+    }
+
+    fn wiki_link_at_location(&self, text_view: &TextView, x: f64, y: f64) -> Option<String> {
+        let link_text = self.tagged_text_at_location(text_view, x, y, WIKI_LINK_TAG)?;
+        wiki_links::title_for(&link_text)
+    }
+
+    // The whole run of text carrying the tag at the pointer, if the tag is there at all
+    fn tagged_text_at_location(
+        &self,
+        text_view: &TextView,
+        x: f64,
+        y: f64,
+        tag_name: &str,
+    ) -> Option<String> {
+        let buffer = self.imp().instance();
+        let (buffer_x, buffer_y) =
+            text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+        let iter = text_view.iter_at_location(buffer_x, buffer_y)?;
+
+        let tag = buffer.tag_table().lookup(tag_name)?;
+        if !iter.has_tag(&tag) {
+            return None;
+        }
+
+        let mut start = iter.clone();
+        if !start.starts_tag(Some(&tag)) {
+            start.backward_to_tag_toggle(Some(&tag));
+        }
+        let mut end = iter;
+        end.forward_to_tag_toggle(Some(&tag));
+
+        Some(buffer.text(&start, &end, false).to_string())
+    }
+
+    // The whole note with a placeholder for every image, so byte offsets found in it map onto iters
+    pub fn full_text(&self) -> String {
+        let buffer = self.imp().instance();
+        let (start, end) = buffer.bounds();
+        buffer.slice(&start, &end, true).to_string()
+    }
+
+    // Byte offset into full_text of where the selection, or the cursor, starts
+    pub fn selection_start_byte(&self, text: &str) -> usize {
+        let buffer = self.imp().instance();
+        let offset = match buffer.selection_bounds() {
+            Some((start, _)) => start.offset(),
+            None => buffer.iter_at_mark(&buffer.get_insert()).offset(),
+        };
+        text.char_indices()
+            .nth(offset as usize)
+            .map_or(text.len(), |(byte, _)| byte)
+    }
+
+    // Matches are byte ranges of full_text
+    pub fn highlight_find_matches(&self, matches: &[Range<usize>], current: Option<usize>) {
+        let buffer = self.imp().instance();
+        let (start, end) = buffer.bounds();
+        buffer.remove_tag_by_name(FIND_MATCH_TAG, &start, &end);
+        buffer.remove_tag_by_name(FIND_CURRENT_TAG, &start, &end);
+
+        let text = self.full_text();
+        for (index, range) in find::char_ranges(&text, matches).into_iter().enumerate() {
+            let tag = if Some(index) == current {
+                FIND_CURRENT_TAG
+            } else {
+                FIND_MATCH_TAG
+            };
+            buffer.apply_tag_by_name(
+                tag,
+                &buffer.iter_at_offset(range.start),
+                &buffer.iter_at_offset(range.end),
+            );
         }
     }
+
+    pub fn select_byte_range(&self, text_view: &TextView, range: &Range<usize>) {
+        let buffer = self.imp().instance();
+        let text = self.full_text();
+        let range = &find::char_ranges(&text, std::slice::from_ref(range))[0];
+        buffer.select_range(
+            &buffer.iter_at_offset(range.start),
+            &buffer.iter_at_offset(range.end),
+        );
+        text_view.scroll_to_mark(&buffer.get_insert(), 0.1, false, 0.0, 0.0);
+    }
+
+    // Replaces byte ranges of full_text as a single step to undo
+    pub fn replace_byte_ranges(&self, replacements: &[(Range<usize>, String)]) {
+        let buffer = self.imp().instance();
+        let text = self.full_text();
+        let ranges: Vec<Range<usize>> = replacements
+            .iter()
+            .map(|(range, _)| range.clone())
+            .collect();
+        let char_ranges = find::char_ranges(&text, &ranges);
+
+        buffer.begin_user_action();
+        // From the end back so the earlier offsets stay valid
+        for (range, (_, replacement)) in char_ranges.iter().zip(replacements).rev() {
+            let mut start = buffer.iter_at_offset(range.start);
+            let mut end = buffer.iter_at_offset(range.end);
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, replacement);
+        }
+        buffer.end_user_action();
+    }
+
+    // Applies a rewrite of the note as one edit that only touches what changed, so the cursor
+    // and the attachments stay where they are
+    pub fn rewrite_text<F: Fn(&str) -> Option<String>>(&self, rewrite: F) {
+        let text = self.full_text();
+        if let Some(rewritten) = rewrite(&text) {
+            let replacements = diff::changed_ranges(&text, &rewritten).unwrap_or_else(|| {
+                let (range, replacement) = diff::changed_range(&text, &rewritten);
+                vec![(range, replacement.to_string())]
+            });
+            self.replace_byte_ranges(&replacements);
+        }
+    }
+
+    // Tags the byte range of a line, as found by scanning the buffer's slice
+    fn apply_tag_to_line(&self, tag: &str, line_number: usize, line: &str, range: Range<usize>) {
+        let buffer = self.imp().instance();
+        let start_offset = line[..range.start].chars().count() as i32;
+        let end_offset = start_offset + line[range].chars().count() as i32;
+
+        if let (Some(tag_start), Some(tag_end)) = (
+            buffer.iter_at_line_offset(line_number as i32, start_offset),
+            buffer.iter_at_line_offset(line_number as i32, end_offset),
+        ) {
+            buffer.apply_tag_by_name(tag, &tag_start, &tag_end);
+        }
+    }
+}
+
+// Where pasted or dropped content is read from
+#[derive(Clone)]
+enum PasteSource {
+    Clipboard(gdk::Clipboard),
+    Drop(gdk::Drop),
+}
+
+impl PasteSource {
+    fn formats(&self) -> ContentFormats {
+        match self {
+            PasteSource::Clipboard(clipboard) => clipboard.formats(),
+            PasteSource::Drop(drop) => drop.formats(),
+        }
+    }
+
+    fn read_value<F: FnOnce(Result<Value, String>) + 'static>(
+        &self,
+        type_: glib::Type,
+        callback: F,
+    ) {
+        let callback = move |result: Result<Value, glib::Error>| {
+            callback(result.map_err(|e| e.message().to_string()))
+        };
+        match self {
+            PasteSource::Clipboard(clipboard) => clipboard.read_value_async(
+                type_,
+                glib::PRIORITY_DEFAULT,
+                Cancellable::NONE,
+                callback,
+            ),
+            PasteSource::Drop(drop) => {
+                drop.read_value_async(type_, glib::PRIORITY_DEFAULT, Cancellable::NONE, callback)
+            }
+        }
+    }
+
+    // All of the content as the given MIME type
+    fn read_bytes<F: FnOnce(Result<Vec<u8>, String>) + 'static>(
+        &self,
+        mime_type: &str,
+        callback: F,
+    ) {
+        let read = move |result: Result<(gio::InputStream, glib::GString), glib::Error>| {
+            let input = match result {
+                Ok((input, _)) => input,
+                Err(e) => return callback(Err(e.message().to_string())),
+            };
+            let output = gio::MemoryOutputStream::new_resizable();
+            output.clone().splice_async(
+                &input,
+                gio::OutputStreamSpliceFlags::CLOSE_SOURCE
+                    | gio::OutputStreamSpliceFlags::CLOSE_TARGET,
+                glib::PRIORITY_DEFAULT,
+                Cancellable::NONE,
+                move |result| {
+                    callback(
+                        result
+                            .map(|_| output.steal_as_bytes().to_vec())
+                            .map_err(|e| e.message().to_string()),
+                    )
+                },
+            );
+        };
+        match self {
+            PasteSource::Clipboard(clipboard) => clipboard.read_async(
+                &[mime_type],
+                glib::PRIORITY_DEFAULT,
+                Cancellable::NONE,
+                read,
+            ),
+            PasteSource::Drop(drop) => drop.read_async(
+                &[mime_type],
+                glib::PRIORITY_DEFAULT,
+                Cancellable::NONE,
+                read,
+            ),
+        }
+    }
+
+    // Tells whoever dragged the content that the drop is over
+    fn finish(&self, success: bool) {
+        if let PasteSource::Drop(drop) = self {
+            drop.finish(if success {
+                gdk::DragAction::COPY
+            } else {
+                gdk::DragAction::empty()
+            });
+        }
+    }
+}
+
+fn paste_format(formats: &ContentFormats) -> Option<PasteFormat> {
+    if formats.contain_gtype(Texture::static_type()) {
+        return Some(PasteFormat::Image);
+    }
+    clipboard::negotiate(&formats.mime_types())
+}
+
+// The buffer of the note the view is showing
+fn shown_buffer(text_view: &TextView) -> Option<GnoteTextBuffer> {
+    text_view.buffer().downcast::<GnoteTextBuffer>().ok()
+}
+
+fn attachment_store() -> AttachmentStore {
+    AttachmentStore::new(io::get_attachments_dir())
+}
+
+fn attachment_file_uri(link: &AttachmentLink) -> String {
+    gio::File::for_path(attachment_store().path(&link.name))
+        .uri()
+        .to_string()
+}
+
+// Images go along inside the HTML, as the place they're stored means nothing elsewhere
+fn attachment_html_uri(link: &AttachmentLink) -> String {
+    if link.is_image {
+        if let Ok(content) = fs::read(attachment_store().path(&link.name)) {
+            let (content_type, _) = gio::content_type_guess(Some(&link.name), &content);
+            let mime_type = gio::content_type_get_mime_type(&content_type)
+                .map(|mime_type| mime_type.to_string())
+                .unwrap_or_else(|| "image/png".to_string());
+            return format!(
+                "data:{};base64,{}",
+                mime_type,
+                general_purpose::STANDARD.encode(content)
+            );
+        }
+    }
+    attachment_file_uri(link)
+}
+
+// Pasted images have no file of their own, so they are stored as PNG
+fn store_texture(texture: &Texture) -> Result<String, Box<dyn std::error::Error>> {
+    let temp_file = tempfile::Builder::new().suffix(".png").tempfile()?;
+    texture.save_to_png(temp_file.path())?;
+    attachment_store().add_file(temp_file.path())
 }
@@ -1,15 +1,35 @@
 use crate::{
     log_error,
-    tools::io::{self, NoteFile, NoteFileItem},
+    tools::{
+        checklist,
+        io::{self, NoteFile, NoteFileItem},
+        search::{self, SearchQuery, SmartFolder},
+        storage::Changes,
+        tags,
+    },
 };
 use gtk::{
-    glib::{self, ParamSpec, ParamSpecBoolean, Value},
+    glib::{self, clone, ParamSpec, ParamSpecBoolean, Value},
     prelude::*,
     subclass::prelude::*,
     TreeIter, TreePath, TreeStore, TreeViewColumn,
 };
 use once_cell::sync::Lazy;
-use std::cell::Cell;
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeSet,
+    rc::Rc,
+};
+
+// What a row of the tree stands for, kept in column 5
+const ROW_ITEM: i32 = 0;
+const ROW_SMART_FOLDER: i32 = 1;
+// A note matching a smart folder's query, standing in for the note's own row
+const ROW_SMART_RESULT: i32 = 2;
+
+const SMART_FOLDER_ICON: &str = "folder-saved-search-symbolic";
+// Seconds without edits before smart folders catch up with what was typed
+const SMART_FOLDER_REFRESH_DELAY: u32 = 1;
 
 mod imp {
     use super::*;
@@ -20,8 +40,16 @@ mod imp {
         #[template_child]
         pub tree_store: TemplateChild<gtk::TreeStore>,
         #[template_child]
+        pub tree_filter: TemplateChild<gtk::TreeModelFilter>,
+        #[template_child]
         pub tree_selection: TemplateChild<gtk::TreeSelection>,
 
+        pub tag_filter: RefCell<Vec<String>>,
+        // The smart folder refresh waiting for typing to settle down
+        pub pending_refresh: RefCell<Option<glib::SourceId>>,
+        // What changed since the notebook was last saved
+        pub changes: RefCell<Changes>,
+
         pub add_note_visible: Cell<bool>,
         pub add_folder_visible: Cell<bool>,
         pub remove_item_visible: Cell<bool>,
@@ -97,6 +125,23 @@ mod imp {
 
         fn constructed(&self) {
             self.parent_constructed();
+
+            let tree_view = self.instance();
+            // Rows come and go when notes are added, removed or dragged somewhere else
+            self.tree_store
+                .connect_row_inserted(clone!(@weak tree_view => move |_, _, _| {
+                    tree_view.imp().changes.borrow_mut().structure = true;
+                }));
+            self.tree_store
+                .connect_row_deleted(clone!(@weak tree_view => move |_, _| {
+                    tree_view.imp().changes.borrow_mut().structure = true;
+                }));
+            self.tree_filter.set_visible_func(
+                clone!(@weak tree_view => @default-return true, move |model, iter| {
+                    let tag_filter = tree_view.imp().tag_filter.borrow();
+                    tag_filter.is_empty() || has_tags(model, iter, &tag_filter)
+                }),
+            );
         }
     }
 
@@ -125,7 +170,7 @@ impl GnoteTreeView {
     ) {
         println!("Selection changed");
 
-        if let Some((_, iter)) = self.imp().tree_selection.selected() {
+        if let Some(iter) = self.selected_row() {
             //let name = self.imp().tree_store.get_value(&iter, 0).get::<String>().unwrap();
             let is_folder = self
                 .imp()
@@ -134,15 +179,18 @@ impl GnoteTreeView {
                 .get::<bool>()
                 .unwrap();
             let has_parent = self.imp().tree_store.iter_parent(&iter).is_some();
-
-            if !has_parent {
-                self.imp().remove_item_visible.set(false);
-            } else {
-                self.imp().remove_item_visible.set(true);
+            let row_kind = row_kind(&self.imp().tree_store, &iter);
+
+            // A smart folder can be deleted but not added to, and the notes in it only live there
+            // for as long as they match
+            match row_kind {
+                ROW_SMART_FOLDER => self.imp().remove_item_visible.set(true),
+                ROW_SMART_RESULT => self.imp().remove_item_visible.set(false),
+                _ => self.imp().remove_item_visible.set(has_parent),
             }
             self.notify("remove-item-visible");
 
-            if is_folder {
+            if is_folder && row_kind == ROW_ITEM {
                 self.imp().add_folder_visible.set(true);
                 self.imp().add_note_visible.set(true);
             } else {
@@ -155,7 +203,7 @@ impl GnoteTreeView {
     }
 
     pub fn add_folder(&self, name: &str) {
-        if let Some((_, selected_iter)) = self.imp().tree_selection.selected() {
+        if let Some(selected_iter) = self.selected_item_row() {
             let child_count = self.imp().tree_store.iter_n_children(Some(&selected_iter));
 
             if child_count == 1 {
@@ -178,11 +226,11 @@ impl GnoteTreeView {
             }
         }
 
-        let selected = self.imp().tree_selection.selected();
+        let selected = self.selected_item_row();
         let iter = self.imp().tree_store.insert_with_values(
-            selected.as_ref().map(|(_, iter)| iter),
-            None,
-            &[(0, &name), (1, &""), (2, &true)],
+            selected.as_ref(),
+            self.insert_position(selected.as_ref()),
+            &[(0, &name), (1, &""), (2, &true), (8, &io::new_note_id())],
         );
 
         self.imp().tree_store.insert_with_values(
@@ -192,8 +240,8 @@ impl GnoteTreeView {
         );
     }
 
-    pub fn add_note(&self, name: &str) {
-        if let Some((_, selected_iter)) = self.imp().tree_selection.selected() {
+    pub fn add_note(&self, name: &str) -> Vec<i32> {
+        if let Some(selected_iter) = self.current_folder() {
             let child_count = self.imp().tree_store.iter_n_children(Some(&selected_iter));
 
             if child_count == 1 {
@@ -216,17 +264,57 @@ impl GnoteTreeView {
             }
         }
 
-        let selected = self.imp().tree_selection.selected();
-        self.imp().tree_store.insert_with_values(
-            selected.as_ref().map(|(_, iter)| iter),
-            None,
-            &[(0, &name), (1, &""), (2, &false)],
+        let selected = self.current_folder();
+        let iter = self.imp().tree_store.insert_with_values(
+            selected.as_ref(),
+            self.insert_position(selected.as_ref()),
+            &[
+                (0, &name),
+                (1, &""),
+                (2, &false),
+                (4, &now()),
+                (8, &io::new_note_id()),
+            ],
         );
+
+        let note_path = self.imp().tree_store.path(&iter).indices().to_vec();
+        self.refresh_smart_folders();
+        note_path
+    }
+
+    // New rows at the top level go after the other folders but before the smart folders, so the
+    // paths of real notes never count smart folders
+    fn insert_position(&self, parent: Option<&TreeIter>) -> Option<u32> {
+        if parent.is_some() {
+            return None;
+        }
+
+        let tree_store = &self.imp().tree_store;
+        let mut position = 0;
+        if let Some(mut iter) = tree_store.iter_children(None) {
+            while row_kind(tree_store, &iter) == ROW_ITEM {
+                position += 1;
+                if !tree_store.iter_next(&mut iter) {
+                    break;
+                }
+            }
+        }
+        Some(position)
     }
 
     pub fn remove_item(&self) {
-        let selected = self.imp().tree_selection.selected();
-        let (_, selected_iter) = selected.unwrap();
+        let selected = self.selected_row();
+        let selected_iter = selected.unwrap();
+
+        match row_kind(&self.imp().tree_store, &selected_iter) {
+            ROW_SMART_FOLDER => {
+                // Only the saved search goes, the notes it shows stay where they are
+                self.imp().tree_store.remove(&selected_iter);
+                return;
+            }
+            ROW_SMART_RESULT => return,
+            _ => {}
+        }
 
         if self
             .imp()
@@ -239,11 +327,12 @@ impl GnoteTreeView {
         } else {
             self.remove_note();
         }
+        self.refresh_smart_folders();
     }
 
     fn remove_folder(&self) {
-        let selected = self.imp().tree_selection.selected();
-        let (_, selected_iter) = selected.unwrap();
+        let selected = self.selected_row();
+        let selected_iter = selected.unwrap();
 
         let parent_iter = self.imp().tree_store.iter_parent(&selected_iter);
 
@@ -265,8 +354,8 @@ impl GnoteTreeView {
     }
 
     fn remove_note(&self) {
-        let selected = self.imp().tree_selection.selected();
-        let (_, selected_iter) = selected.unwrap();
+        let selected = self.selected_row();
+        let selected_iter = selected.unwrap();
 
         let parent_iter = self.imp().tree_store.iter_parent(&selected_iter);
 
@@ -287,78 +376,477 @@ impl GnoteTreeView {
         }
     }
 
-    pub fn save(&self) {
-        println!("Saving...");
-        fn build_note_file_item(tree_store: &TreeStore, iter: &TreeIter) -> NoteFileItem {
-            let title = tree_store
-                .get_value(iter, 0)
-                .get::<String>()
-                .unwrap_or_else(|_| "".to_string());
-            let body = tree_store
-                .get_value(iter, 1)
-                .get::<String>()
-                .unwrap_or_else(|_| "".to_string());
-            let is_folder = tree_store.get_value(iter, 2).get::<bool>().unwrap_or(false);
+    // The selected row in terms of the tree store rather than the filtered model on show
+    fn selected_row(&self) -> Option<TreeIter> {
+        let (_, iter) = self.imp().tree_selection.selected()?;
+        Some(self.imp().tree_filter.convert_iter_to_child_iter(&iter))
+    }
+
+    // The selected folder, or the folder holding the selected note
+    fn current_folder(&self) -> Option<TreeIter> {
+        let iter = self.selected_item_row()?;
+        let tree_store = &self.imp().tree_store;
+        if tree_store
+            .get_value(&iter, 2)
+            .get::<bool>()
+            .unwrap_or(false)
+        {
+            Some(iter)
+        } else {
+            tree_store.iter_parent(&iter)
+        }
+    }
+
+    // The selected row when it is a real folder or note rather than part of a smart folder
+    fn selected_item_row(&self) -> Option<TreeIter> {
+        self.selected_row()
+            .filter(|iter| row_kind(&self.imp().tree_store, iter) == ROW_ITEM)
+    }
+
+    // Like selected_row, but a note picked inside a smart folder is its row in the notebook
+    fn selected_iter(&self) -> Option<TreeIter> {
+        let iter = self.selected_row()?;
+        let tree_store = &self.imp().tree_store;
+        if row_kind(tree_store, &iter) != ROW_SMART_RESULT {
+            return Some(iter);
+        }
+
+        let note_path = tree_store.get_value(&iter, 6).get::<String>().ok()?;
+        tree_store.iter(&TreePath::from_string(&note_path)?)
+    }
 
-            let mut children = None;
-            if is_folder {
-                if let Some(mut child_iter) = tree_store.iter_children(Some(iter)) {
-                    let mut child_items = Vec::new();
+    pub fn add_smart_folder(&self, smart_folder: &SmartFolder) {
+        self.insert_smart_folder(smart_folder);
+        self.refresh_smart_folders();
+    }
+
+    fn insert_smart_folder(&self, smart_folder: &SmartFolder) {
+        let query = serde_json::to_string(&smart_folder.query).unwrap_or_else(|e| {
+            log_error!(
+                "Failed to serialize the query of {}: {}",
+                smart_folder.title,
+                e
+            );
+            "{}".to_string()
+        });
+        self.imp().tree_store.insert_with_values(
+            None,
+            None,
+            &[
+                (0, &smart_folder.title),
+                (1, &""),
+                (2, &true),
+                (5, &ROW_SMART_FOLDER),
+                (6, &query),
+                (7, &SMART_FOLDER_ICON),
+            ],
+        );
+    }
+
+    // Brings every smart folder up to date with the notes matching its query. Rows are only
+    // rebuilt when the matches change, so typing in a note doesn't keep losing the selection
+    pub fn refresh_smart_folders(&self) {
+        if let Some(source_id) = self.imp().pending_refresh.take() {
+            source_id.remove();
+        }
+        let tree_store = &self.imp().tree_store;
+        let mut iter = match tree_store.iter_children(None) {
+            Some(iter) => iter,
+            None => return,
+        };
+        let note_file = self.note_file();
+        // The matches shown aren't part of the notebook, so coming and going changes nothing
+        let structure_changed = self.imp().changes.borrow().structure;
+
+        // Where the selection was when it was a note inside a smart folder
+        let selected_result = self.selected_row().and_then(|iter| {
+            if row_kind(tree_store, &iter) != ROW_SMART_RESULT {
+                return None;
+            }
+            let smart_folder_path = tree_store.path(&tree_store.iter_parent(&iter)?);
+            let note_path = tree_store.get_value(&iter, 6).get::<String>().ok()?;
+            Some((smart_folder_path, note_path))
+        });
 
+        loop {
+            if row_kind(tree_store, &iter) == ROW_SMART_FOLDER {
+                let query: SearchQuery = tree_store
+                    .get_value(&iter, 6)
+                    .get::<String>()
+                    .ok()
+                    .and_then(|query| serde_json::from_str(&query).ok())
+                    .unwrap_or_default();
+                let notes = search::matching_notes(&note_file, &query);
+                let note_paths: Vec<String> = notes
+                    .iter()
+                    .map(|note| {
+                        TreePath::from_indices(&note.path)
+                            .to_str()
+                            .map(|path| path.to_string())
+                            .unwrap_or_default()
+                    })
+                    .collect();
+
+                let mut shown_paths = Vec::new();
+                if let Some(mut child_iter) = tree_store.iter_children(Some(&iter)) {
                     loop {
-                        child_items.push(build_note_file_item(&tree_store, &child_iter));
+                        shown_paths.push(
+                            tree_store
+                                .get_value(&child_iter, 6)
+                                .get::<String>()
+                                .unwrap_or_default(),
+                        );
                         if !tree_store.iter_next(&mut child_iter) {
                             break;
                         }
                     }
+                }
 
-                    children = Some(child_items);
+                if shown_paths != note_paths {
+                    while let Some(child_iter) = tree_store.iter_children(Some(&iter)) {
+                        tree_store.remove(&child_iter);
+                    }
+                    for note_path in &note_paths {
+                        tree_store.insert_with_values(
+                            Some(&iter),
+                            None,
+                            &[(5, &ROW_SMART_RESULT), (6, note_path)],
+                        );
+                    }
+                }
+
+                // Titles and bodies can change without the matches changing
+                let mut child_iter = tree_store.iter_children(Some(&iter));
+                for note in &notes {
+                    let child = match child_iter.as_mut() {
+                        Some(child) => child,
+                        None => break,
+                    };
+                    let body = note.item.body.as_deref().unwrap_or("");
+                    tree_store.set(
+                        child,
+                        &[
+                            (0, &note.item.title),
+                            (1, &body),
+                            (2, &false),
+                            (3, &progress_text(body)),
+                        ],
+                    );
+                    if !tree_store.iter_next(child) {
+                        break;
+                    }
                 }
             }
 
-            NoteFileItem {
-                title,
-                body: Some(body),
-                children,
-                is_folder,
+            if !tree_store.iter_next(&mut iter) {
+                break;
             }
         }
 
-        let mut root_iter = self.imp().tree_store.iter_nth_child(None, 0).unwrap();
-        let mut root_items = Vec::new();
+        self.imp().changes.borrow_mut().structure = structure_changed;
 
-        loop {
-            root_items.push(build_note_file_item(&self.imp().tree_store, &root_iter));
-            if !self.imp().tree_store.iter_next(&mut root_iter) {
-                break;
+        if let Some((smart_folder_path, note_path)) = selected_result {
+            if self.imp().tree_selection.selected().is_none() {
+                self.select_smart_result(&smart_folder_path, &note_path);
             }
         }
+    }
 
-        let note_file = NoteFile {
-            children: Some(root_items),
+    // Selects the note inside the smart folder again, or in its own folder once it stops matching
+    fn select_smart_result(&self, smart_folder_path: &TreePath, note_path: &str) {
+        let tree_store = &self.imp().tree_store;
+        if let Some(smart_folder_iter) = tree_store.iter(smart_folder_path) {
+            if let Some(mut child_iter) = tree_store.iter_children(Some(&smart_folder_iter)) {
+                loop {
+                    if tree_store
+                        .get_value(&child_iter, 6)
+                        .get::<String>()
+                        .ok()
+                        .as_deref()
+                        == Some(note_path)
+                    {
+                        let path = self
+                            .imp()
+                            .tree_filter
+                            .convert_child_path_to_path(&tree_store.path(&child_iter));
+                        if let Some(path) = path {
+                            self.expand_to_path(&path);
+                            self.imp().tree_selection.select_path(&path);
+                        }
+                        return;
+                    }
+                    if !tree_store.iter_next(&mut child_iter) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(path) = TreePath::from_string(note_path) {
+            self.select_note_at(&path.indices());
+        }
+    }
+
+    // Shows only the notes carrying every one of the tags, and the folders leading to them
+    pub fn set_tag_filter(&self, tag_names: Vec<String>) {
+        self.imp().tag_filter.replace(tag_names);
+        self.imp().tree_filter.refilter();
+        if !self.imp().tag_filter.borrow().is_empty() {
+            self.expand_all();
+        }
+    }
+
+    pub fn selected_note(&self) -> Option<(String, String)> {
+        let iter = self.selected_iter()?;
+        let tree_store = &self.imp().tree_store;
+
+        if tree_store.get_value(&iter, 2).get::<bool>().unwrap_or(true) {
+            return None;
+        }
+
+        let title = tree_store.get_value(&iter, 0).get::<String>().ok()?;
+        let body = tree_store
+            .get_value(&iter, 1)
+            .get::<String>()
+            .unwrap_or_else(|_| "".to_string());
+        Some((title, body))
+    }
+
+    fn set_note_title(&self, iter: &TreeIter, title: &str) {
+        let tree_store = &self.imp().tree_store;
+        if tree_store
+            .get_value(iter, 0)
+            .get::<String>()
+            .ok()
+            .as_deref()
+            == Some(title)
+        {
+            return;
+        }
+        tree_store.set(iter, &[(0, &title), (4, &now())]);
+        self.note_changed(iter);
+        self.refresh_smart_folders();
+    }
+
+    fn set_note_body(&self, iter: &TreeIter, body: &str) {
+        let tree_store = &self.imp().tree_store;
+        // Opening a note hands its body straight back, which isn't an edit
+        if tree_store
+            .get_value(iter, 1)
+            .get::<String>()
+            .ok()
+            .as_deref()
+            == Some(body)
+        {
+            return;
+        }
+        tree_store.set(iter, &[(1, &body), (3, &progress_text(body)), (4, &now())]);
+        self.note_changed(iter);
+        self.schedule_smart_folder_refresh();
+    }
+
+    fn note_changed(&self, iter: &TreeIter) {
+        let tree_store = &self.imp().tree_store;
+        let mut changes = self.imp().changes.borrow_mut();
+        // Only notes are written on their own, a renamed folder takes the whole notebook
+        if tree_store.get_value(iter, 2).get::<bool>().unwrap_or(true) {
+            changes.structure = true;
+        } else if let Ok(note_id) = tree_store.get_value(iter, 8).get::<String>() {
+            changes.note_ids.insert(note_id);
+        }
+    }
+
+    // What changed since this was last called, which is when the notebook was saved
+    pub fn take_changes(&self) -> Changes {
+        self.imp().changes.take()
+    }
+
+    // Puts back changes that couldn't be saved, so the next save has another go at them
+    pub fn keep_changes(&self, changes: Changes) {
+        let mut current = self.imp().changes.borrow_mut();
+        current.structure |= changes.structure;
+        current.note_ids.extend(changes.note_ids);
+    }
+
+    // Running every query over the whole notebook on each key press would slow typing down
+    fn schedule_smart_folder_refresh(&self) {
+        if let Some(source_id) = self.imp().pending_refresh.take() {
+            source_id.remove();
+        }
+        let source_id = glib::timeout_add_seconds_local_once(
+            SMART_FOLDER_REFRESH_DELAY,
+            clone!(@weak self as tree_view => move || {
+                tree_view.imp().pending_refresh.take();
+                tree_view.refresh_smart_folders();
+            }),
+        );
+        self.imp().pending_refresh.replace(Some(source_id));
+    }
+
+    pub fn find_note(&self, title: &str) -> Option<Vec<i32>> {
+        self.note_file()
+            .notes()
+            .into_iter()
+            .find(|note| note.item.title == title)
+            .map(|note| note.path)
+    }
+
+    pub fn selected_note_id(&self) -> Option<String> {
+        let iter = self.selected_iter()?;
+        self.imp()
+            .tree_store
+            .get_value(&iter, 8)
+            .get::<String>()
+            .ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.imp().tree_store.iter_first().is_none()
+    }
+
+    // Called after any row of the notebook is added, changed or removed
+    pub fn connect_notebook_changed<F: Fn() + 'static>(&self, callback: F) {
+        let callback = Rc::new(callback);
+        let tree_store = &self.imp().tree_store;
+        tree_store.connect_row_changed(clone!(@strong callback => move |_, _, _| callback()));
+        tree_store.connect_row_inserted(clone!(@strong callback => move |_, _, _| callback()));
+        tree_store.connect_row_deleted(move |_, _| callback());
+    }
+
+    pub fn selected_path(&self) -> Option<Vec<i32>> {
+        let iter = self.selected_iter()?;
+        Some(self.imp().tree_store.path(&iter).indices().to_vec())
+    }
+
+    pub fn set_note_body_at(&self, note_path: &[i32], body: &str) {
+        let tree_store = &self.imp().tree_store;
+        if let Some(iter) = tree_store.iter(&TreePath::from_indices(note_path)) {
+            self.set_note_body(&iter, body);
+        }
+    }
+
+    pub fn set_note_title_at(&self, note_path: &[i32], title: &str) {
+        let tree_store = &self.imp().tree_store;
+        if let Some(iter) = tree_store.iter(&TreePath::from_indices(note_path)) {
+            self.set_note_title(&iter, title);
+        }
+    }
+
+    pub fn select_note_at(&self, note_path: &[i32]) {
+        let path = match self
+            .imp()
+            .tree_filter
+            .convert_child_path_to_path(&TreePath::from_indices(note_path))
+        {
+            Some(path) => path,
+            // Hidden by the tag filter
+            None => return,
         };
+        self.expand_to_path(&path);
+        self.imp().tree_selection.select_path(&path);
+        self.scroll_to_cell(Some(&path), None::<&TreeViewColumn>, false, 0.0, 0.0);
+    }
 
-        note_file.save(&io::get_notes_path()).unwrap_or_else(|e| {
-            log_error!("Error: {}", e);
-        });
+    // The notes with these ids, as they are now
+    pub fn notes_by_id(&self, note_ids: &BTreeSet<String>) -> Vec<NoteFileItem> {
+        let tree_store = &self.imp().tree_store;
+        if note_ids.is_empty() {
+            return Vec::new();
+        }
+        note_rows(tree_store)
+            .iter()
+            .filter(|iter| note_ids.contains(&row_id(tree_store, iter)))
+            .map(|iter| build_note_file_item(tree_store, iter))
+            .collect()
     }
 
-    pub fn load(&self) {
-        self.imp().tree_store.clear();
+    // The row of a note in the notebook, wherever it is now
+    fn note_iter(&self, note_id: &str) -> Option<TreeIter> {
+        let tree_store = &self.imp().tree_store;
+        if note_id.is_empty() {
+            return None;
+        }
+        note_rows(tree_store)
+            .into_iter()
+            .find(|iter| row_id(tree_store, iter) == note_id)
+    }
 
-        let note_file = match NoteFile::load(&io::get_notes_path()) {
-            Ok(note_file_load) => note_file_load,
-            Err(e) => {
-                log_error!("Failed to load file - {}", e);
-                return;
+    pub fn note_by_id(&self, note_id: &str) -> Option<(String, String)> {
+        let iter = self.note_iter(note_id)?;
+        let item = build_note_file_item(&self.imp().tree_store, &iter);
+        Some((item.title, item.body.unwrap_or_default()))
+    }
+
+    pub fn set_note_body_by_id(&self, note_id: &str, body: &str) {
+        if let Some(iter) = self.note_iter(note_id) {
+            self.set_note_body(&iter, body);
+        }
+    }
+
+    pub fn set_note_title_by_id(&self, note_id: &str, title: &str) {
+        if let Some(iter) = self.note_iter(note_id) {
+            self.set_note_title(&iter, title);
+        }
+    }
+
+    pub fn note_file(&self) -> NoteFile {
+        let tree_store = &self.imp().tree_store;
+        let mut root_items = Vec::new();
+        let mut smart_folders = Vec::new();
+        // Nothing is shown while the notebook is locked or before it is loaded
+        let mut root_iter = match tree_store.iter_nth_child(None, 0) {
+            Some(root_iter) => root_iter,
+            None => {
+                return NoteFile {
+                    children: Some(root_items),
+                    smart_folders,
+                }
             }
         };
 
+        loop {
+            match row_kind(tree_store, &root_iter) {
+                ROW_SMART_FOLDER => smart_folders.push(SmartFolder {
+                    title: tree_store
+                        .get_value(&root_iter, 0)
+                        .get::<String>()
+                        .unwrap_or_else(|_| "".to_string()),
+                    query: tree_store
+                        .get_value(&root_iter, 6)
+                        .get::<String>()
+                        .ok()
+                        .and_then(|query| serde_json::from_str(&query).ok())
+                        .unwrap_or_default(),
+                }),
+                _ => {
+                    let item = build_note_file_item(tree_store, &root_iter);
+                    if !item.is_placeholder() {
+                        root_items.push(item);
+                    }
+                }
+            }
+            if !tree_store.iter_next(&mut root_iter) {
+                break;
+            }
+        }
+
+        NoteFile {
+            children: Some(root_items),
+            smart_folders,
+        }
+    }
+
+    pub fn load(&self, note_file: &NoteFile) {
+        self.imp().tree_store.clear();
+
         fn insert_note_file_item(
             tree_store: &TreeStore,
             item: &NoteFileItem,
             parent: Option<&TreeIter>,
         ) {
+            if item.is_placeholder() {
+                return;
+            }
             let iter = tree_store.insert_with_values(
                 parent,
                 None,
@@ -366,6 +854,9 @@ impl GnoteTreeView {
                     (0, &item.title),
                     (1, &item.body.as_ref().unwrap_or(&"".to_string())),
                     (2, &item.is_folder),
+                    (3, &progress_text(item.body.as_deref().unwrap_or(""))),
+                    (4, &item.modified.unwrap_or(0)),
+                    (8, &item.id),
                 ],
             );
 
@@ -374,6 +865,9 @@ impl GnoteTreeView {
                     insert_note_file_item(tree_store, child, Some(&iter));
                 }
             }
+            if item.is_folder && tree_store.iter_n_children(Some(&iter)) == 0 {
+                tree_store.insert_with_values(Some(&iter), None, &[(0, &""), (1, &""), (2, &true)]);
+            }
         }
 
         if let Some(root_items) = &note_file.children {
@@ -381,5 +875,141 @@ impl GnoteTreeView {
                 insert_note_file_item(&self.imp().tree_store, item, None);
             }
         }
+
+        for smart_folder in &note_file.smart_folders {
+            self.insert_smart_folder(smart_folder);
+        }
+        self.refresh_smart_folders();
+    }
+}
+
+fn build_note_file_item(tree_store: &TreeStore, iter: &TreeIter) -> NoteFileItem {
+    let title = tree_store
+        .get_value(iter, 0)
+        .get::<String>()
+        .unwrap_or_else(|_| "".to_string());
+    let body = tree_store
+        .get_value(iter, 1)
+        .get::<String>()
+        .unwrap_or_else(|_| "".to_string());
+    let is_folder = tree_store.get_value(iter, 2).get::<bool>().unwrap_or(false);
+    let modified = tree_store
+        .get_value(iter, 4)
+        .get::<i64>()
+        .ok()
+        .filter(|modified| *modified > 0);
+    let id = tree_store
+        .get_value(iter, 8)
+        .get::<String>()
+        .unwrap_or_default();
+
+    let mut children = None;
+    if is_folder {
+        if let Some(mut child_iter) = tree_store.iter_children(Some(iter)) {
+            let mut child_items = Vec::new();
+
+            loop {
+                let child_item = build_note_file_item(tree_store, &child_iter);
+                if !child_item.is_placeholder() {
+                    child_items.push(child_item);
+                }
+                if !tree_store.iter_next(&mut child_iter) {
+                    break;
+                }
+            }
+
+            children = Some(child_items);
+        }
+    }
+
+    NoteFileItem {
+        title,
+        body: Some(body),
+        children,
+        is_folder,
+        modified,
+        id,
+    }
+}
+
+// Every note of the notebook, leaving out folders and smart folders
+fn note_rows(tree_store: &TreeStore) -> Vec<TreeIter> {
+    fn collect(tree_store: &TreeStore, iter: Option<&TreeIter>, rows: &mut Vec<TreeIter>) {
+        let mut child_iter = match tree_store.iter_children(iter) {
+            Some(child_iter) => child_iter,
+            None => return,
+        };
+        loop {
+            if row_kind(tree_store, &child_iter) == ROW_ITEM {
+                if tree_store
+                    .get_value(&child_iter, 2)
+                    .get::<bool>()
+                    .unwrap_or(false)
+                {
+                    collect(tree_store, Some(&child_iter), rows);
+                } else {
+                    rows.push(child_iter.clone());
+                }
+            }
+            if !tree_store.iter_next(&mut child_iter) {
+                break;
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    collect(tree_store, None, &mut rows);
+    rows
+}
+
+fn row_id(tree_store: &TreeStore, iter: &TreeIter) -> String {
+    tree_store
+        .get_value(iter, 8)
+        .get::<String>()
+        .unwrap_or_default()
+}
+
+fn row_kind(tree_store: &TreeStore, iter: &TreeIter) -> i32 {
+    tree_store
+        .get_value(iter, 5)
+        .get::<i32>()
+        .unwrap_or(ROW_ITEM)
+}
+
+// Seconds since the Unix epoch, as kept for when notes were last edited
+fn now() -> i64 {
+    glib::real_time() / 1_000_000
+}
+
+// Shown next to the note title, empty when the note has no check boxes
+fn progress_text(body: &str) -> String {
+    let progress = checklist::progress(body);
+    if progress.total == 0 {
+        String::new()
+    } else {
+        format!("{}/{}", progress.done, progress.total)
+    }
+}
+
+// Whether the note, or any note inside the folder, carries all of the tags
+fn has_tags(model: &gtk::TreeModel, iter: &TreeIter, tag_names: &[String]) -> bool {
+    if !model.get_value(iter, 2).get::<bool>().unwrap_or(false) {
+        let body = model
+            .get_value(iter, 1)
+            .get::<String>()
+            .unwrap_or_else(|_| "".to_string());
+        return tags::has_all(&body, tag_names);
+    }
+
+    if let Some(mut child_iter) = model.iter_children(Some(iter)) {
+        loop {
+            if has_tags(model, &child_iter, tag_names) {
+                return true;
+            }
+            if !model.iter_next(&mut child_iter) {
+                break;
+            }
+        }
     }
+    false
 }
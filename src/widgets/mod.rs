@@ -1,4 +1,13 @@
+pub mod gnote_attachment;
+pub mod gnote_command_palette;
+pub mod gnote_conflicts;
 pub mod gnote_editor;
+pub mod gnote_history;
+pub mod gnote_image;
+pub mod gnote_notebook_replace;
+pub mod gnote_quick_switcher;
+pub mod gnote_tag_list;
+pub mod gnote_task_list;
 pub mod gnote_text_buffer;
 pub mod gnote_tree_view;
 pub mod window;
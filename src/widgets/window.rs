@@ -1,6 +1,75 @@
-use crate::widgets::{gnote_editor::GnoteEditor, gnote_tree_view::GnoteTreeView};
-use adw::subclass::prelude::*;
-use gtk::{gio, glib, prelude::*};
+use crate::{
+    log_error, log_info, log_warning,
+    tools::{
+        attachments::{self, AttachmentStore},
+        checklist, commands,
+        crypto::{CryptoError, NotebookKey, SealedKey},
+        due_date::{self, DueDate},
+        find::{self, Occurrence},
+        fuzzy,
+        git_sync::GitRepo,
+        history::{History, Snapshot},
+        io::{self, NoteFile},
+        keyring,
+        merge::{self, Conflict, Merged},
+        search::{SearchQuery, SmartFolder},
+        settings::Settings,
+        storage::{JsonStorage, Storage, StorageKind},
+        tags, task_export, tasks,
+        webdav_sync::{SyncError, SyncState, Synced, WebDav},
+        wiki_links,
+    },
+    widgets::{
+        gnote_command_palette::GnoteCommandPalette,
+        gnote_conflicts::GnoteConflicts,
+        gnote_editor::GnoteEditor,
+        gnote_history::GnoteHistory,
+        gnote_notebook_replace::GnoteNotebookReplace,
+        gnote_quick_switcher::{GnoteQuickSwitcher, SwitcherRow},
+        gnote_tag_list::GnoteTagList,
+        gnote_task_list::GnoteTaskList,
+        gnote_tree_view::GnoteTreeView,
+    },
+};
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{
+    builders::FileChooserDialogBuilder, gio, glib, glib::clone, prelude::*, FileChooserAction,
+    ResponseType,
+};
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+// Picks the tasks a task list shows out of the notebook
+type ListTasks = fn(&NoteFile) -> Vec<tasks::Task>;
+
+// Seconds between checks for reminders that have come due
+const REMINDER_CHECK_INTERVAL: u32 = 60;
+// Seconds without edits before the notebook is saved
+const SAVE_DELAY: u32 = 2;
+// Seconds without edits before the tag list catches up with what was typed
+const TAG_REFRESH_DELAY: u32 = 1;
+// Seconds between checks for changes on the WebDAV server
+const SYNC_INTERVAL: u32 = 60;
+// Seconds without saves before the edits are uploaded to the WebDAV server
+const SYNC_DELAY: u32 = 15;
+// Seconds for another program to finish writing the notebook before it is read again
+const DISK_CHECK_DELAY: u32 = 1;
+// Seconds without input before an encrypted notebook locks itself, and between checks for it
+const LOCK_AFTER: i64 = 5 * 60;
+const LOCK_CHECK_INTERVAL: u32 = 15;
+
+// A note's title and body before and after a notebook-wide replace
+#[derive(Debug, Clone)]
+pub struct ReplacedNote {
+    note_path: Vec<i32>,
+    before: (String, String),
+    after: (String, String),
+}
 
 mod imp {
     use super::*;
@@ -13,6 +82,45 @@ mod imp {
         pub gnote_tree_view: TemplateChild<GnoteTreeView>,
         #[template_child]
         pub gnote_editor: TemplateChild<GnoteEditor>,
+        #[template_child]
+        pub gnote_tag_list: TemplateChild<GnoteTagList>,
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub sync_status: TemplateChild<gtk::Button>,
+
+        pub reminders_checked_until: Cell<Option<DueDate>>,
+        // The notes changed by the last notebook-wide replace, until it is undone
+        pub replaced_notes: RefCell<Vec<ReplacedNote>>,
+        pub history: RefCell<History>,
+        // The save waiting for edits to settle down
+        pub pending_save: RefCell<Option<glib::SourceId>>,
+        pub pending_tag_refresh: RefCell<Option<glib::SourceId>>,
+        pub settings: RefCell<Settings>,
+        // Notes changed both here and elsewhere by the last pull, until they are reviewed
+        pub conflicts: RefCell<Vec<Conflict>>,
+        // The task lists that are open and the tasks each of them lists
+        pub task_lists: RefCell<Vec<(glib::WeakRef<GnoteTaskList>, ListTasks)>>,
+        pub sync_state: RefCell<SyncState>,
+        pub webdav_syncing: Cell<bool>,
+        // The WebDAV sync waiting for saves to settle down
+        pub pending_webdav_sync: RefCell<Option<glib::SourceId>>,
+        // Whether git is pulling or pushing in the background
+        pub git_syncing: Cell<bool>,
+        // Held by whichever worker thread is running git, so commits and syncs take turns
+        pub git_lock: Arc<Mutex<()>>,
+        // Whether the notebook was saved while a sync was under way
+        pub sync_again: Cell<bool>,
+        // The notebook as last read from or written to disk, to tell what others changed
+        pub disk_note_file: RefCell<Option<NoteFile>>,
+        // The storage last opened and the settings it was opened with, so a database stays open
+        pub storage: RefCell<Option<((StorageKind, Option<String>), Rc<dyn Storage>)>>,
+        pub file_monitors: RefCell<Vec<gio::FileMonitor>>,
+        pub pending_disk_check: RefCell<Option<glib::SourceId>>,
+        // The key of an encrypted notebook while it is unlocked
+        pub notebook_key: RefCell<Option<NotebookKey>>,
+        // Seconds since the Unix epoch of the last key press or click
+        pub last_activity: Cell<i64>,
     }
 
     #[glib::object_subclass]
@@ -24,6 +132,90 @@ mod imp {
         fn class_init(klass: &mut Self::Class) {
             klass.bind_template();
             klass.bind_template_instance_callbacks();
+
+            klass.install_action("notebook.export-ical", None, |window, _, _| {
+                window.export_tasks("tasks.ics", |tasks| {
+                    task_export::to_ical(tasks, &due_date::now_utc())
+                });
+            });
+            klass.install_action("notebook.export-todo-txt", None, |window, _, _| {
+                window.export_tasks("todo.txt", task_export::to_todo_txt);
+            });
+            klass.install_action("notebook.export-json", None, |window, _, _| {
+                window.export_json();
+            });
+            klass.install_action("notebook.import-todo-txt", None, |window, _, _| {
+                window.import_todo_txt();
+            });
+            klass.install_action("notebook.quick-switch", None, |window, _, _| {
+                window.show_quick_switcher();
+            });
+            klass.install_action("notebook.command-palette", None, |window, _, _| {
+                window.show_command_palette();
+            });
+            klass.install_action("notebook.add-note", None, |window, _, _| {
+                let note_path = window.imp().gnote_tree_view.add_note("New Note");
+                window.open_note_at(&note_path);
+            });
+            klass.install_action("notebook.add-folder", None, |window, _, _| {
+                window.imp().gnote_tree_view.add_folder("New Folder");
+            });
+            klass.install_action("notebook.remove-item", None, |window, _, _| {
+                // Top level folders stay, as the buttons show
+                let tree_view = &window.imp().gnote_tree_view;
+                if tree_view.property::<bool>("remove-item-visible") {
+                    tree_view.remove_item();
+                    let note_ids = tree_view
+                        .note_file()
+                        .notes()
+                        .into_iter()
+                        .map(|note| note.item.id)
+                        .collect();
+                    window.imp().gnote_editor.retain_note_buffers(&note_ids);
+                }
+            });
+            klass.install_action("notebook.new-smart-folder", None, |window, _, _| {
+                window.show_new_smart_folder_dialog();
+            });
+            klass.install_action("notebook.show-tasks", None, |window, _, _| {
+                window.show_tasks();
+            });
+            klass.install_action("notebook.replace", None, |window, _, _| {
+                window.show_notebook_replace();
+            });
+            klass.install_action("notebook.undo-replace", None, |window, _, _| {
+                window.undo_notebook_replace();
+            });
+            klass.install_action("notebook.show-history", None, |window, _, _| {
+                window.show_history();
+            });
+            klass.install_action("notebook.set-up-git-sync", None, |window, _, _| {
+                window.show_git_sync_dialog();
+            });
+            klass.install_action("notebook.git-pull", None, |window, _, _| {
+                window.git_pull();
+            });
+            klass.install_action("notebook.git-push", None, |window, _, _| {
+                window.git_push();
+            });
+            klass.install_action("notebook.show-conflicts", None, |window, _, _| {
+                window.show_conflicts();
+            });
+            klass.install_action("notebook.set-up-webdav-sync", None, |window, _, _| {
+                window.show_webdav_sync_dialog();
+            });
+            klass.install_action("notebook.webdav-sync", None, |window, _, _| {
+                window.webdav_sync();
+            });
+            klass.install_action("notebook.choose-storage", None, |window, _, _| {
+                window.show_storage_dialog();
+            });
+            klass.install_action("notebook.set-up-encryption", None, |window, _, _| {
+                window.show_encryption_dialog();
+            });
+            klass.install_action("notebook.lock", None, |window, _, _| {
+                window.lock();
+            });
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -33,7 +225,14 @@ mod imp {
 
     impl ObjectImpl for GnoteWindow {}
     impl WidgetImpl for GnoteWindow {}
-    impl WindowImpl for GnoteWindow {}
+    impl WindowImpl for GnoteWindow {
+        fn close_request(&self) -> gtk::Inhibit {
+            if self.pending_save.borrow().is_some() {
+                self.instance().save_notes();
+            }
+            self.parent_close_request()
+        }
+    }
     impl ApplicationWindowImpl for GnoteWindow {}
     impl AdwApplicationWindowImpl for GnoteWindow {}
 }
@@ -47,25 +246,2269 @@ glib::wrapper! {
 impl GnoteWindow {
     pub fn new<P: glib::IsA<gtk::Application>>(application: &P) -> Self {
         let window: GnoteWindow = glib::Object::new(&[("application", application)]);
-        window.imp().gnote_tree_view.add_folder("My Notes");
+        window.load_notes();
+        window.setup_signals();
+        window.setup_reminders();
+        window.setup_webdav_sync();
+        window.setup_auto_lock();
+        window.refresh_tags();
+        window.action_set_enabled("notebook.undo-replace", false);
+        window.action_set_enabled("notebook.show-conflicts", false);
+        window.update_git_actions();
+        window.update_encryption_actions();
         window
     }
 
-    #[template_callback]
-    fn handle_add_folder_clicked(&self) {
-        println!("Add folder clicked");
-        self.imp().gnote_tree_view.add_folder("New Folder");
+    fn setup_signals(&self) {
+        self.imp().gnote_tree_view.connect_notebook_changed(
+            clone!(@weak self as window => move || {
+                window.schedule_save();
+            }),
+        );
+
+        self.imp().gnote_tree_view.connect_row_activated(
+            clone!(@weak self as window => move |_, _, _| {
+                window.open_selected_note();
+            }),
+        );
+
+        self.imp().gnote_editor.connect_local(
+            "note-changed",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let note = values[1].get::<String>().unwrap();
+                // The selection may have moved on without the note being opened
+                if let Some(note_id) = window.imp().gnote_editor.note_id() {
+                    window.imp().gnote_tree_view.set_note_body_by_id(&note_id, &note);
+                }
+                window.schedule_tag_refresh();
+                None
+            }),
+        );
+
+        self.imp().gnote_tag_list.connect_local(
+            "selection-changed",
+            false,
+            clone!(@weak self as window => @default-return None, move |_| {
+                let selected_tags = window.imp().gnote_tag_list.selected_tags();
+                window.imp().gnote_tree_view.set_tag_filter(selected_tags);
+                None
+            }),
+        );
+
+        self.imp().gnote_tag_list.connect_local(
+            "rename-requested",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let tag_name = values[1].get::<String>().unwrap();
+                window.show_rename_tag_dialog(&tag_name);
+                None
+            }),
+        );
+
+        self.imp().gnote_editor.connect_local(
+            "title-changed",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let title = values[1].get::<String>().unwrap();
+                if let Some(note_id) = window.imp().gnote_editor.note_id() {
+                    window.imp().gnote_tree_view.set_note_title_by_id(&note_id, &title);
+                }
+                None
+            }),
+        );
+
+        self.imp().gnote_editor.connect_local(
+            "title-renamed",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let old_title = values[1].get::<String>().unwrap();
+                let new_title = values[2].get::<String>().unwrap();
+                window.rename_wiki_links(&old_title, &new_title);
+                None
+            }),
+        );
+
+        self.imp().gnote_editor.connect_local(
+            "wiki-link-activated",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let title = values[1].get::<String>().unwrap();
+                match window.imp().gnote_tree_view.find_note(&title) {
+                    Some(note_path) => window.open_note_at(&note_path),
+                    None => log_warning!("No note called {} to link to", title),
+                }
+                None
+            }),
+        );
+
+        self.imp().gnote_editor.connect_local(
+            "error",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let message = values[1].get::<String>().unwrap();
+                log_warning!("{}", message);
+                window.imp().toast_overlay.add_toast(&adw::Toast::new(&message));
+                None
+            }),
+        );
+
+        self.imp().gnote_editor.connect_local(
+            "backlink-activated",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let index = values[1].get::<u32>().unwrap();
+                if let Some(note_path) = window.imp().gnote_editor.backlink_path(index as usize) {
+                    window.open_note_at(&note_path);
+                }
+                None
+            }),
+        );
+    }
+
+    fn setup_reminders(&self) {
+        self.imp()
+            .reminders_checked_until
+            .set(Some(due_date::now_local()));
+
+        glib::timeout_add_seconds_local(
+            REMINDER_CHECK_INTERVAL,
+            clone!(@weak self as window => @default-return glib::Continue(false), move || {
+                window.send_due_reminders();
+                window.imp().gnote_editor.refresh_due_dates();
+                glib::Continue(true)
+            }),
+        );
     }
 
-    #[template_callback]
-    fn handle_add_note_clicked(&self) {
-        println!("Add note clicked");
-        self.imp().gnote_tree_view.add_note("New Note");
+    // Notifies about every open task whose reminder came due since the last check
+    fn send_due_reminders(&self) {
+        // Reminders that come due while locked are sent once the notebook is unlocked
+        if self.is_locked() {
+            return;
+        }
+        let now = due_date::now_local();
+        let checked_until = self.imp().reminders_checked_until.replace(Some(now));
+        let application = match self.application() {
+            Some(application) => application,
+            None => return,
+        };
+
+        for task in tasks::upcoming(&self.imp().gnote_tree_view.note_file()) {
+            let reminder = task.due.unwrap().reminder();
+            if reminder > now || Some(reminder) <= checked_until {
+                continue;
+            }
+
+            let notification = gio::Notification::new(&task.text);
+            notification.set_body(Some(
+                format!("Due {} in {}", task.due.unwrap(), task.location()).as_str(),
+            ));
+            application.send_notification(
+                Some(&format!("reminder-{:?}-{}", task.note_path, task.line)),
+                &notification,
+            );
+        }
+    }
+
+    fn open_selected_note(&self) {
+        let editor = &self.imp().gnote_editor;
+        editor.commit_title();
+
+        let tree_view = &self.imp().gnote_tree_view;
+        if let (Some(note_id), Some((title, body))) =
+            (tree_view.selected_note_id(), tree_view.selected_note())
+        {
+            let note_file = tree_view.note_file();
+            editor.set_note(&note_id, &title, &body);
+            editor.set_backlinks(wiki_links::backlinks(&note_file, &title));
+            editor.set_note_titles(
+                note_file
+                    .notes()
+                    .iter()
+                    .map(|note| note.item.title.clone())
+                    .collect(),
+            );
+        }
+    }
+
+    fn open_note_at(&self, note_path: &[i32]) {
+        self.imp().gnote_tree_view.select_note_at(note_path);
+        self.open_selected_note();
+    }
+
+    // Applies the rewrite to the body of every note, keeping the open note in step
+    fn rewrite_notes<F: Fn(&str) -> Option<String>>(&self, rewrite: F) {
+        let tree_view = &self.imp().gnote_tree_view;
+        let editor = &self.imp().gnote_editor;
+        let open_note_id = editor.note_id();
+
+        for note in tree_view.note_file().notes() {
+            // Edited in place, so the cursor and scroll position stay put while typing
+            if open_note_id.as_deref() == Some(note.item.id.as_str()) {
+                editor.note_buffer().rewrite_text(&rewrite);
+                continue;
+            }
+            let body = note.item.body.as_deref().unwrap_or("");
+            if let Some(rewritten) = rewrite(body) {
+                tree_view.set_note_body_at(&note.path, &rewritten);
+            }
+        }
+    }
+
+    fn load_notes(&self) {
+        let mut settings = Settings::load(&io::get_settings_path()).unwrap_or_else(|e| {
+            log_error!("Failed to load settings - {}", e);
+            Settings::default()
+        });
+        load_webdav_password(&mut settings);
+        self.imp().settings.replace(settings);
+
+        let sync_state = SyncState::load(&io::get_sync_state_path()).unwrap_or_else(|e| {
+            log_error!("Failed to load sync state - {}", e);
+            SyncState::default()
+        });
+        self.imp().sync_state.replace(sync_state);
+
+        if self.is_encrypted() {
+            self.set_locked(true);
+            // Shown once the window is, so it has something to sit on
+            glib::idle_add_local_once(clone!(@weak self as window => move || {
+                window.show_unlock_dialog();
+            }));
+        } else {
+            self.open_notebook();
+        }
+    }
+
+    // Reads the notebook and its history, once they can be decrypted
+    fn open_notebook(&self) {
+        let tree_view = &self.imp().gnote_tree_view;
+        let storage = self.storage();
+        if storage.exists() {
+            match storage.load() {
+                Ok(note_file) => {
+                    tree_view.load(&note_file);
+                    self.imp().disk_note_file.replace(Some(note_file));
+                }
+                Err(e) => log_error!("Failed to load notes - {}", e),
+            }
+        }
+        if tree_view.is_empty() {
+            tree_view.add_folder("My Notes");
+        }
+        // Loading isn't an edit
+        tree_view.take_changes();
+
+        let key = self.imp().notebook_key.borrow().clone();
+        let mut history =
+            History::load(&io::get_history_path(), key.as_ref()).unwrap_or_else(|e| {
+                log_error!("Failed to load history - {}", e);
+                History::default()
+            });
+        // Notes edited outside of Gnote get their version kept before they are edited here
+        let note_file = tree_view.note_file();
+        if history.record_notes(&note_file, glib::real_time() / 1_000_000) {
+            self.save_history(&history);
+        }
+
+        // Attachments only go once neither the notes nor their history refer to them
+        let mut referenced = attachments::referenced_by_notes(&note_file);
+        referenced.extend(attachments::referenced(
+            history
+                .notes
+                .values()
+                .flatten()
+                .map(|snapshot| snapshot.body.as_str()),
+        ));
+        match AttachmentStore::new(io::get_attachments_dir()).collect_garbage(&referenced) {
+            Ok(removed) if removed > 0 => log_info!("Removed {} unused attachments", removed),
+            Ok(_) => {}
+            Err(e) => log_error!("Failed to clean up attachments - {}", e),
+        }
+        self.imp().history.replace(history);
+        self.watch_notebook();
+    }
+
+    fn schedule_save(&self) {
+        if let Some(source_id) = self.imp().pending_save.take() {
+            source_id.remove();
+        }
+        let source_id = glib::timeout_add_seconds_local_once(
+            SAVE_DELAY,
+            clone!(@weak self as window => move || {
+                window.imp().pending_save.take();
+                window.save_notes();
+            }),
+        );
+        self.imp().pending_save.replace(Some(source_id));
+    }
+
+    // Saves the notebook straight away, keeping a snapshot of every note that changed
+    fn save_notes(&self) {
+        // Nothing of a locked notebook is shown, so there is nothing to save
+        if self.is_locked() {
+            return;
+        }
+        // Git is working on the notebook directory, so edits wait until it is done
+        if self.imp().git_syncing.get() {
+            self.schedule_save();
+            return;
+        }
+        // Changes made elsewhere that the monitor hasn't caught up with yet aren't overwritten
+        self.check_disk();
+        if let Some(source_id) = self.imp().pending_save.take() {
+            source_id.remove();
+        }
+        let tree_view = &self.imp().gnote_tree_view;
+        let changes = tree_view.take_changes();
+        if !changes.structure && self.git_repo().is_none() {
+            let notes = tree_view.notes_by_id(&changes.note_ids);
+            // Storage that can't write notes on its own gets the whole notebook below
+            match self.storage().save_notes(&notes) {
+                Ok(true) => {
+                    if let Some(disk_note_file) = &mut *self.imp().disk_note_file.borrow_mut() {
+                        disk_note_file.update_notes(&notes);
+                    }
+                    let edited = NoteFile {
+                        children: Some(notes),
+                        smart_folders: Vec::new(),
+                    };
+                    let mut history = self.imp().history.borrow_mut();
+                    if history.record_notes(&edited, glib::real_time() / 1_000_000) {
+                        self.save_history(&history);
+                    }
+                    drop(history);
+                    self.schedule_webdav_sync();
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => log_warning!("Failed to save the edited notes - {}", e),
+            }
+        }
+        let note_file = tree_view.note_file();
+        match self.storage().save(&note_file) {
+            Ok(()) => {
+                self.imp().disk_note_file.replace(Some(note_file.clone()));
+            }
+            Err(e) => {
+                log_error!("Failed to save notes - {}", e);
+                tree_view.keep_changes(changes);
+            }
+        }
+
+        let mut history = self.imp().history.borrow_mut();
+        if history.record_notes(&note_file, glib::real_time() / 1_000_000) {
+            self.save_history(&history);
+        }
+
+        self.commit_notes();
+        self.schedule_webdav_sync();
+    }
+
+    // Git takes too long to run on every save while typing, so it commits in the background
+    fn commit_notes(&self) {
+        let repo = match self.git_repo() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let git_lock = self.imp().git_lock.clone();
+        std::thread::spawn(move || {
+            let _git_lock = git_lock.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = repo.commit_notes() {
+                log_error!("Failed to commit notes - {}", e);
+            }
+        });
+    }
+
+    // Where the notebook is read from and written to, as chosen in the settings
+    fn storage(&self) -> Rc<dyn Storage> {
+        let settings = self.imp().settings.borrow();
+        let opened_with = (settings.storage, settings.notes_dir.clone());
+        if let Some((cached_with, storage)) = &*self.imp().storage.borrow() {
+            if *cached_with == opened_with {
+                return storage.clone();
+            }
+        }
+        let storage: Rc<dyn Storage> = match (settings.storage, &*self.imp().notebook_key.borrow())
+        {
+            (StorageKind::Json, Some(key)) => {
+                Rc::new(JsonStorage::encrypted(&io::get_notes_path(), key.clone()))
+            }
+            _ => {
+                let notes_dir = settings
+                    .notes_dir
+                    .as_ref()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(io::get_notes_dir);
+                Rc::from(settings.storage.open(
+                    &io::get_notes_path(),
+                    &notes_dir,
+                    &io::get_database_path(),
+                ))
+            }
+        };
+        self.imp()
+            .storage
+            .replace(Some((opened_with, storage.clone())));
+        storage
+    }
+
+    // The storage opened with the old key, or without one, can't read the notebook any more
+    fn set_notebook_key(&self, key: Option<NotebookKey>) {
+        self.imp().notebook_key.replace(key);
+        self.imp().storage.replace(None);
+    }
+
+    fn show_storage_dialog(&self) {
+        if self.is_encrypted() {
+            self.imp().toast_overlay.add_toast(&adw::Toast::new(
+                "Encrypted notebooks are kept as a JSON file",
+            ));
+            return;
+        }
+        let settings = self.imp().settings.borrow().clone();
+        let single_file = gtk::CheckButton::builder()
+            .label("A JSON file")
+            .active(settings.storage == StorageKind::Json)
+            .build();
+        let database = gtk::CheckButton::builder()
+            .label("An SQLite database")
+            .group(&single_file)
+            .active(settings.storage == StorageKind::Sqlite)
+            .build();
+        let folder = gtk::CheckButton::builder()
+            .label("A folder of Markdown files")
+            .group(&single_file)
+            .active(settings.storage == StorageKind::Folder)
+            .build();
+
+        let notes_dir = settings
+            .notes_dir
+            .clone()
+            .unwrap_or_else(|| io::get_notes_dir().to_string_lossy().to_string());
+        let dir_button = gtk::Button::builder()
+            .label(&notes_dir)
+            .tooltip_text("Choose the folder")
+            .margin_start(25)
+            .sensitive(folder.is_active())
+            .build();
+        folder.connect_toggled(clone!(@weak dir_button => move |folder| {
+            dir_button.set_sensitive(folder.is_active());
+        }));
+        dir_button.connect_clicked(clone!(@weak self as window => move |dir_button| {
+            let file_chooser = FileChooserDialogBuilder::new()
+                .title("Keep notes in")
+                .action(FileChooserAction::SelectFolder)
+                .transient_for(&window)
+                .modal(true)
+                .build();
+            file_chooser.add_buttons(&[("Select", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+            file_chooser.connect_response(clone!(@weak dir_button => move |dialog, response| {
+                if response == ResponseType::Ok {
+                    if let Some(dir) = dialog.file().and_then(|file| file.path()) {
+                        dir_button.set_label(&dir.to_string_lossy());
+                    }
+                }
+                dialog.destroy();
+            }));
+            file_chooser.present();
+        }));
+
+        let fields = gtk::Box::new(gtk::Orientation::Vertical, 5);
+        fields.append(&single_file);
+        fields.append(&database);
+        fields.append(&folder);
+        fields.append(&dir_button);
+
+        let dialog = adw::MessageDialog::new(
+            Some(self),
+            Some("Notebook Storage"),
+            Some("A database only writes the notes that changed, for notebooks with thousands of notes. Kept as a folder, every note is a Markdown file that other editors, grep and file syncing tools can work with. The notebook is copied over and the old copy is kept."),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("switch", "_Switch")]);
+        dialog.set_response_appearance("switch", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("switch"));
+        dialog.set_extra_child(Some(&fields));
+
+        dialog.connect_response(
+            Some("switch"),
+            clone!(@weak self as window, @weak database, @weak folder, @weak dir_button => move |_, _| {
+                let storage = if database.is_active() {
+                    StorageKind::Sqlite
+                } else if folder.is_active() {
+                    StorageKind::Folder
+                } else {
+                    StorageKind::Json
+                };
+                let notes_dir = dir_button.label().map(|label| label.to_string());
+                window.set_storage(storage, notes_dir);
+            }),
+        );
+
+        dialog.present();
+    }
+
+    // Copies the notebook to the new storage, leaving the old copy where it was
+    fn set_storage(&self, storage: StorageKind, notes_dir: Option<String>) {
+        self.save_notes();
+        let mut settings = self.imp().settings.borrow().clone();
+        // The default directory is left out, so it follows the notebook directory
+        settings.notes_dir =
+            notes_dir.filter(|notes_dir| *notes_dir != io::get_notes_dir().to_string_lossy());
+        settings.storage = storage;
+        if settings == *self.imp().settings.borrow() {
+            return;
+        }
+
+        let previous = self.imp().settings.replace(settings.clone());
+        let note_file = self.imp().gnote_tree_view.note_file();
+        match self.storage().save(&note_file) {
+            Ok(()) => {
+                if let Err(e) = settings.save(&io::get_settings_path()) {
+                    log_error!("Failed to save settings - {}", e);
+                }
+                self.imp().disk_note_file.replace(Some(note_file));
+                self.watch_notebook();
+                self.update_git_actions();
+                self.imp()
+                    .toast_overlay
+                    .add_toast(&adw::Toast::new("Moved the notebook"));
+            }
+            Err(e) => {
+                self.imp().settings.replace(previous);
+                log_error!("Failed to switch storage - {}", e);
+                self.imp()
+                    .toast_overlay
+                    .add_toast(&adw::Toast::new("Couldn't move the notebook"));
+            }
+        }
+    }
+
+    // Watches the notebook for changes made by other programs, such as file syncing tools or
+    // another window
+    fn watch_notebook(&self) {
+        let mut file_monitors = Vec::new();
+        for path in self.storage().watched() {
+            let file_monitor = match gio::File::for_path(&path)
+                .monitor(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE)
+            {
+                Ok(file_monitor) => file_monitor,
+                Err(e) => {
+                    log_warning!("Failed to watch {} - {}", path.display(), e);
+                    continue;
+                }
+            };
+            file_monitor.connect_changed(clone!(@weak self as window => move |_, _, _, _| {
+                window.schedule_disk_check();
+            }));
+            file_monitors.push(file_monitor);
+        }
+        for file_monitor in self.imp().file_monitors.replace(file_monitors) {
+            file_monitor.cancel();
+        }
+    }
+
+    fn schedule_disk_check(&self) {
+        if let Some(source_id) = self.imp().pending_disk_check.take() {
+            source_id.remove();
+        }
+        let source_id = glib::timeout_add_seconds_local_once(
+            DISK_CHECK_DELAY,
+            clone!(@weak self as window => move || {
+                window.imp().pending_disk_check.take();
+                window.check_disk();
+            }),
+        );
+        self.imp().pending_disk_check.replace(Some(source_id));
+    }
+
+    // Takes in what other programs changed on disk. Notes only changed there are reloaded, and
+    // notes changed both there and here are offered for review.
+    fn check_disk(&self) {
+        // What a pull writes is taken in once it is done
+        if self.is_locked() || self.imp().git_syncing.get() {
+            return;
+        }
+        let storage = self.storage();
+        // A database can tell whether anything was written since, without reading it all
+        if !storage.exists() || !storage.may_have_changed() {
+            return;
+        }
+        let on_disk = match storage.load() {
+            Ok(on_disk) => on_disk,
+            // Likely still being written, the monitor will say when it's done
+            Err(e) => {
+                log_warning!("Failed to read notes changed on disk - {}", e);
+                return;
+            }
+        };
+        let current = self.imp().gnote_tree_view.note_file();
+        let known = self.imp().disk_note_file.borrow().clone();
+        let merged = match merge::changes_on_disk(known.as_ref(), &current, &on_disk) {
+            Some(merged) => merged,
+            None => return,
+        };
+        log_info!("Notes changed on disk, reloading");
+
+        if !merge::same_notes(&merged.note_file, &current) {
+            self.show_notes(&merged.note_file);
+        }
+        // Edits made here go back on disk with the next save
+        if !merge::same_notes(&merged.note_file, &on_disk) {
+            self.schedule_save();
+        }
+        self.imp().disk_note_file.replace(Some(on_disk));
+        // Folders may have been added or removed
+        self.watch_notebook();
+        if !merged.conflicts.is_empty() {
+            self.add_conflicts(merged.conflicts);
+            self.show_conflicts();
+        }
+    }
+
+    // Whether the notebook is encrypted, whether or not it is unlocked right now
+    fn is_encrypted(&self) -> bool {
+        std::path::Path::new(&io::get_key_path()).exists()
+    }
+
+    fn is_locked(&self) -> bool {
+        self.imp().notebook_key.borrow().is_none() && self.is_encrypted()
+    }
+
+    fn set_locked(&self, locked: bool) {
+        let imp = self.imp();
+        imp.gnote_tree_view.set_sensitive(!locked);
+        imp.gnote_editor.set_sensitive(!locked);
+        imp.gnote_tag_list.set_sensitive(!locked);
+        self.update_encryption_actions();
+    }
+
+    fn update_encryption_actions(&self) {
+        self.action_set_enabled("notebook.lock", self.is_encrypted() && !self.is_locked());
+    }
+
+    // Locks an encrypted notebook once there has been no input for a while
+    fn setup_auto_lock(&self) {
+        self.imp().last_activity.set(glib::real_time() / 1_000_000);
+        self.watch_activity(self);
+
+        glib::timeout_add_seconds_local(
+            LOCK_CHECK_INTERVAL,
+            clone!(@weak self as window => @default-return glib::Continue(false), move || {
+                if glib::real_time() / 1_000_000 - window.imp().last_activity.get() >= LOCK_AFTER {
+                    window.lock();
+                }
+                glib::Continue(true)
+            }),
+        );
+    }
+
+    // Counts input in this window, or in one opened from it, as use of the notebook
+    fn watch_activity(&self, watched: &impl IsA<gtk::Window>) {
+        let controller = gtk::EventControllerLegacy::new();
+        controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        controller.connect_event(
+            clone!(@weak self as window => @default-return gtk::Inhibit(false), move |_, _| {
+                window.imp().last_activity.set(glib::real_time() / 1_000_000);
+                gtk::Inhibit(false)
+            }),
+        );
+        watched.add_controller(&controller);
+    }
+
+    // Saves an encrypted notebook and forgets its key and everything decrypted with it
+    fn lock(&self) {
+        if !self.is_encrypted() || self.is_locked() {
+            return;
+        }
+        self.save_notes();
+
+        // Windows opened from this one show notes too
+        for toplevel in gtk::Window::list_toplevels() {
+            if let Ok(window) = toplevel.downcast::<gtk::Window>() {
+                if window.transient_for().as_ref() == Some(self.upcast_ref()) {
+                    window.close();
+                }
+            }
+        }
+
+        let imp = self.imp();
+        self.set_notebook_key(None);
+        imp.history.replace(History::default());
+        imp.disk_note_file.replace(None);
+        imp.replaced_notes.replace(Vec::new());
+        self.set_conflicts(Vec::new());
+        for file_monitor in imp.file_monitors.take() {
+            file_monitor.cancel();
+        }
+        imp.gnote_tree_view.load(&NoteFile {
+            children: Some(Vec::new()),
+            smart_folders: Vec::new(),
+        });
+        imp.gnote_editor.clear();
+        self.refresh_tags();
+        self.set_locked(true);
+        self.show_unlock_dialog();
+    }
+
+    fn show_unlock_dialog(&self) {
+        let passphrase_entry = gtk::PasswordEntry::builder()
+            .placeholder_text("Passphrase")
+            .show_peek_icon(true)
+            .activates_default(true)
+            .build();
+
+        let dialog = adw::MessageDialog::new(
+            Some(self),
+            Some("Unlock Notebook"),
+            Some("The notebook is encrypted. Enter its passphrase to open it."),
+        );
+        dialog.add_responses(&[("quit", "_Quit"), ("unlock", "_Unlock")]);
+        dialog.set_response_appearance("unlock", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("unlock"));
+        dialog.set_close_response("quit");
+        dialog.set_extra_child(Some(&passphrase_entry));
+
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window, @weak passphrase_entry => move |_, response| {
+                match response {
+                    "unlock" => window.unlock(&passphrase_entry.text()),
+                    _ => window.close(),
+                }
+            }),
+        );
+
+        dialog.present();
+    }
+
+    fn unlock(&self, passphrase: &str) {
+        let key = match self.unseal_key(passphrase) {
+            Some(key) => key,
+            None => {
+                self.show_unlock_dialog();
+                return;
+            }
+        };
+        self.set_notebook_key(Some(key));
+        self.imp().last_activity.set(glib::real_time() / 1_000_000);
+        self.set_locked(false);
+        self.open_notebook();
+        self.refresh_tags();
+    }
+
+    // The notebook key, if the passphrase is the one it was sealed with
+    fn unseal_key(&self, passphrase: &str) -> Option<NotebookKey> {
+        let key = match SealedKey::load(&io::get_key_path()) {
+            Ok(sealed_key) => sealed_key.unseal(passphrase),
+            Err(e) => Err(CryptoError::Failed(e.to_string())),
+        };
+        match key {
+            Ok(key) => Some(key),
+            Err(CryptoError::WrongPassphrase) => {
+                self.show_encryption_error("The passphrase is wrong");
+                None
+            }
+            Err(e) => {
+                self.show_encryption_error(&e.to_string());
+                None
+            }
+        }
+    }
+
+    fn show_encryption_error(&self, message: &str) {
+        log_error!("{}", message);
+        self.imp()
+            .toast_overlay
+            .add_toast(&adw::Toast::new(message));
+    }
+
+    fn show_encryption_dialog(&self) {
+        if self.is_locked() {
+            return;
+        }
+        let settings = self.imp().settings.borrow().clone();
+        let encrypted = self.is_encrypted();
+        if !encrypted && settings.storage != StorageKind::Json {
+            self.show_encryption_error("Only notebooks kept as a JSON file can be encrypted");
+            return;
+        }
+        if !encrypted && (settings.git_remote.is_some() || settings.webdav_url.is_some()) {
+            self.show_encryption_error("Turn off Git and WebDAV sync to encrypt the notebook");
+            return;
+        }
+
+        let passphrase_entry = |placeholder: &str| {
+            gtk::PasswordEntry::builder()
+                .placeholder_text(placeholder)
+                .show_peek_icon(true)
+                .build()
+        };
+        let current_entry = passphrase_entry("Current passphrase");
+        let new_entry = passphrase_entry("New passphrase");
+        let confirm_entry = passphrase_entry("New passphrase again");
+        confirm_entry.set_activates_default(true);
+
+        let fields = gtk::Box::new(gtk::Orientation::Vertical, 5);
+        if encrypted {
+            fields.append(&current_entry);
+        }
+        fields.append(&new_entry);
+        fields.append(&confirm_entry);
+
+        let dialog = if encrypted {
+            let dialog = adw::MessageDialog::new(
+                Some(self),
+                Some("Change Passphrase"),
+                Some("The notes stay encrypted with the same key, only the key is encrypted again with the new passphrase. Decrypting the notebook needs just the current passphrase."),
+            );
+            dialog.add_responses(&[
+                ("cancel", "_Cancel"),
+                ("decrypt", "_Decrypt"),
+                ("change", "_Change"),
+            ]);
+            dialog.set_response_appearance("decrypt", adw::ResponseAppearance::Destructive);
+            dialog.set_response_appearance("change", adw::ResponseAppearance::Suggested);
+            dialog.set_default_response(Some("change"));
+            dialog
+        } else {
+            let dialog = adw::MessageDialog::new(
+                Some(self),
+                Some("Encrypt Notebook"),
+                Some("Notes and their history are encrypted with the passphrase, and the notebook locks itself after 5 minutes without use. Attachments and copies made before, such as in a git repository, stay readable. There is no way back into the notes without the passphrase."),
+            );
+            dialog.add_responses(&[("cancel", "_Cancel"), ("encrypt", "_Encrypt")]);
+            dialog.set_response_appearance("encrypt", adw::ResponseAppearance::Suggested);
+            dialog.set_default_response(Some("encrypt"));
+            dialog
+        };
+        dialog.set_extra_child(Some(&fields));
+
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window, @weak current_entry, @weak new_entry, @weak confirm_entry
+                => move |_, response| {
+                let current = current_entry.text().to_string();
+                let new = new_entry.text().to_string();
+                if matches!(response, "encrypt" | "change") {
+                    if new.is_empty() {
+                        window.show_encryption_error("The passphrase can't be empty");
+                        return;
+                    }
+                    if new != confirm_entry.text() {
+                        window.show_encryption_error("The passphrases don't match");
+                        return;
+                    }
+                }
+                match response {
+                    "encrypt" => window.encrypt(&new),
+                    "change" => window.change_passphrase(&current, &new),
+                    "decrypt" => window.decrypt(&current),
+                    _ => {}
+                }
+            }),
+        );
+
+        dialog.present();
+    }
+
+    // The key file goes first, as files still in plain text can be read with the key
+    fn encrypt(&self, passphrase: &str) {
+        let key = NotebookKey::generate();
+        let sealed_key = match key.seal(passphrase) {
+            Ok(sealed_key) => sealed_key,
+            Err(e) => {
+                self.show_encryption_error(&e.to_string());
+                return;
+            }
+        };
+        if let Err(e) = sealed_key.save(&io::get_key_path()) {
+            self.show_encryption_error(&e.to_string());
+            return;
+        }
+        self.set_notebook_key(Some(key));
+        self.save_notes();
+        self.save_history(&self.imp().history.borrow());
+
+        // What was last agreed with a WebDAV server is a copy of the notes in plain text
+        if let Err(e) = fs::remove_file(io::get_sync_state_path()) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log_error!("Failed to remove sync state - {}", e);
+            }
+        }
+        self.imp().sync_state.replace(SyncState::default());
+
+        self.imp().last_activity.set(glib::real_time() / 1_000_000);
+        self.update_encryption_actions();
+        self.imp()
+            .toast_overlay
+            .add_toast(&adw::Toast::new("Encrypted the notebook"));
+    }
+
+    // Only the key file is written, moved over the old one in one step
+    fn change_passphrase(&self, current: &str, new: &str) {
+        let key = match self.unseal_key(current) {
+            Some(key) => key,
+            None => return,
+        };
+        let result = key
+            .seal(new)
+            .map_err(|e| e.to_string())
+            .and_then(|sealed_key| {
+                sealed_key
+                    .save(&io::get_key_path())
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => self
+                .imp()
+                .toast_overlay
+                .add_toast(&adw::Toast::new("Changed the passphrase")),
+            Err(e) => self.show_encryption_error(&e),
+        }
+    }
+
+    // The key file goes last, so the notes are never left without a way to read them
+    fn decrypt(&self, current: &str) {
+        if self.unseal_key(current).is_none() {
+            return;
+        }
+        let note_file = self.imp().gnote_tree_view.note_file();
+        if let Err(e) = JsonStorage::new(&io::get_notes_path()).save(&note_file) {
+            self.show_encryption_error(&e.to_string());
+            return;
+        }
+        let history = self.imp().history.borrow();
+        if let Err(e) = history.save(&io::get_history_path(), None) {
+            self.show_encryption_error(&e.to_string());
+            return;
+        }
+        if let Err(e) = fs::remove_file(io::get_key_path()) {
+            self.show_encryption_error(&format!("Failed to remove key - {}", e));
+            return;
+        }
+        self.set_notebook_key(None);
+        self.update_encryption_actions();
+        self.imp()
+            .toast_overlay
+            .add_toast(&adw::Toast::new("Decrypted the notebook"));
+    }
+
+    fn save_history(&self, history: &History) {
+        history
+            .save(
+                &io::get_history_path(),
+                self.imp().notebook_key.borrow().as_ref(),
+            )
+            .unwrap_or_else(|e| log_error!("Failed to save history - {}", e));
+    }
+
+    fn show_history(&self) {
+        let tree_view = &self.imp().gnote_tree_view;
+        let note_id = match self.imp().gnote_editor.note_id() {
+            Some(note_id) => note_id,
+            None => return,
+        };
+        let (title, body) = match tree_view.note_by_id(&note_id) {
+            Some(note) => note,
+            None => return,
+        };
+
+        let history = GnoteHistory::new();
+        history.set_snapshots(
+            Snapshot {
+                time: glib::real_time() / 1_000_000,
+                title: title.clone(),
+                body,
+            },
+            self.imp().history.borrow().snapshots(&note_id),
+        );
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        content.append(&adw::HeaderBar::new());
+        content.append(&history);
+
+        let history_window = adw::Window::builder()
+            .transient_for(self)
+            .modal(true)
+            .title(&format!("History of {}", title))
+            .default_width(800)
+            .default_height(500)
+            .content(&content)
+            .build();
+
+        history.connect_local(
+            "restore-requested",
+            false,
+            clone!(@weak self as window, @weak history_window => @default-return None, move |values| {
+                let title = values[1].get::<String>().unwrap();
+                let body = values[2].get::<String>().unwrap();
+                window.imp().gnote_editor.restore_note(&title, &body);
+                history_window.close();
+                None
+            }),
+        );
+
+        self.watch_activity(&history_window);
+        history_window.present();
+    }
+
+    fn show_notebook_replace(&self) {
+        let notebook_replace = GnoteNotebookReplace::new();
+        notebook_replace.set_note_file(self.imp().gnote_tree_view.note_file());
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        content.append(&adw::HeaderBar::new());
+        content.append(&notebook_replace);
+
+        let replace_window = adw::Window::builder()
+            .transient_for(self)
+            .modal(true)
+            .title("Replace in Notebook")
+            .default_width(600)
+            .default_height(500)
+            .content(&content)
+            .build();
+
+        notebook_replace.connect_local(
+            "apply-requested",
+            false,
+            clone!(@weak self as window, @weak replace_window => @default-return None, move |values| {
+                let notebook_replace = values[0].get::<GnoteNotebookReplace>().unwrap();
+                window.apply_notebook_replace(&notebook_replace.included_occurrences());
+                replace_window.close();
+                None
+            }),
+        );
+
+        self.watch_activity(&replace_window);
+        replace_window.present();
+    }
+
+    // Replaces every occurrence in one go and saves once, leaving a toast to undo it all
+    fn apply_notebook_replace(&self, occurrences: &[Occurrence]) {
+        let mut replaced_notes = Vec::new();
+        let mut occurrence_count = 0;
+        for note in self.imp().gnote_tree_view.note_file().notes() {
+            let (in_title, in_body): (Vec<&Occurrence>, Vec<&Occurrence>) = occurrences
+                .iter()
+                .filter(|occurrence| occurrence.note_path == note.path)
+                .partition(|occurrence| occurrence.in_title);
+            if in_title.is_empty() && in_body.is_empty() {
+                continue;
+            }
+            occurrence_count += in_title.len() + in_body.len();
+
+            let title = note.item.title.clone();
+            let body = note.item.body.clone().unwrap_or_default();
+            let after = (
+                find::apply_occurrences(&title, &in_title),
+                find::apply_occurrences(&body, &in_body),
+            );
+            replaced_notes.push(ReplacedNote {
+                note_path: note.path,
+                before: (title, body),
+                after,
+            });
+        }
+        if replaced_notes.is_empty() {
+            return;
+        }
+
+        let changes: Vec<(Vec<i32>, (String, String))> = replaced_notes
+            .iter()
+            .map(|note| (note.note_path.clone(), note.after.clone()))
+            .collect();
+        self.write_notes(&changes);
+
+        // Links to renamed notes were rewritten too, so undo checks against the saved text
+        for note in self.imp().gnote_tree_view.note_file().notes() {
+            if let Some(replaced_note) = replaced_notes
+                .iter_mut()
+                .find(|replaced_note| replaced_note.note_path == note.path)
+            {
+                replaced_note.after = (note.item.title, note.item.body.unwrap_or_default());
+            }
+        }
+
+        let toast = adw::Toast::new(&format!(
+            "Replaced {} occurrences in {} notes",
+            occurrence_count,
+            replaced_notes.len()
+        ));
+        toast.set_button_label(Some("Undo"));
+        toast.set_action_name(Some("notebook.undo-replace"));
+        self.imp().toast_overlay.add_toast(&toast);
+
+        self.imp().replaced_notes.replace(replaced_notes);
+        self.action_set_enabled("notebook.undo-replace", true);
+    }
+
+    fn undo_notebook_replace(&self) {
+        let replaced_notes = self.imp().replaced_notes.take();
+        self.action_set_enabled("notebook.undo-replace", false);
+
+        let note_file = self.imp().gnote_tree_view.note_file();
+        let notes = note_file.notes();
+        let mut changes = Vec::new();
+        for replaced_note in replaced_notes {
+            // Notes moved or edited since are left as they are
+            let unchanged = notes.iter().any(|note| {
+                note.path == replaced_note.note_path
+                    && note.item.title == replaced_note.after.0
+                    && note.item.body.as_deref().unwrap_or("") == replaced_note.after.1
+            });
+            if unchanged {
+                changes.push((replaced_note.note_path, replaced_note.before));
+            } else {
+                log_warning!(
+                    "{} changed after the replace and was left as it is",
+                    replaced_note.after.0
+                );
+            }
+        }
+        self.write_notes(&changes);
     }
 
-    #[template_callback]
-    fn handle_remove_item_clicked(&self) {
-        println!("Remove item clicked");
-        self.imp().gnote_tree_view.remove_item()
+    // Sets the titles and bodies of notes, then saves the notebook
+    fn write_notes(&self, changes: &[(Vec<i32>, (String, String))]) {
+        let tree_view = &self.imp().gnote_tree_view;
+        let editor = &self.imp().gnote_editor;
+        let open_note_id = editor.note_id();
+        let notes = tree_view.note_file().notes();
+
+        let mut renames = Vec::new();
+        for (note_path, (title, body)) in changes {
+            tree_view.set_note_title_at(note_path, title);
+            tree_view.set_note_body_at(note_path, body);
+            if let Some(note) = notes.iter().find(|note| &note.path == note_path) {
+                if &note.item.title != title {
+                    renames.push((note.item.title.clone(), title.clone()));
+                }
+                if open_note_id.as_deref() == Some(note.item.id.as_str()) {
+                    editor.set_note(&note.item.id, title, body);
+                }
+            }
+        }
+        for (old_title, new_title) in renames {
+            self.rename_wiki_links(&old_title, &new_title);
+        }
+        self.refresh_tags();
+        self.save_notes();
+    }
+
+    // Keeps [[links]] pointing at a note after it has been renamed
+    fn rename_wiki_links(&self, old_title: &str, new_title: &str) {
+        self.rewrite_notes(|body| wiki_links::rename(body, old_title, new_title));
+    }
+
+    // Indexing the whole notebook on each key press would slow typing down
+    fn schedule_tag_refresh(&self) {
+        if let Some(source_id) = self.imp().pending_tag_refresh.take() {
+            source_id.remove();
+        }
+        let source_id = glib::timeout_add_seconds_local_once(
+            TAG_REFRESH_DELAY,
+            clone!(@weak self as window => move || {
+                window.imp().pending_tag_refresh.take();
+                window.refresh_tags();
+            }),
+        );
+        self.imp().pending_tag_refresh.replace(Some(source_id));
+    }
+
+    fn refresh_tags(&self) {
+        if let Some(source_id) = self.imp().pending_tag_refresh.take() {
+            source_id.remove();
+        }
+        // The tags of a locked notebook are hidden along with its notes
+        if self.is_locked() {
+            self.imp().gnote_tag_list.set_index(&Default::default());
+            return;
+        }
+        let note_file = self.imp().gnote_tree_view.note_file();
+        self.imp()
+            .gnote_tag_list
+            .set_index(&tags::index(&note_file));
+    }
+
+    // Renaming a tag to one that already exists merges the two
+    fn show_rename_tag_dialog(&self, tag_name: &str) {
+        let entry = gtk::Entry::builder()
+            .text(tag_name)
+            .activates_default(true)
+            .build();
+
+        let dialog = adw::MessageDialog::new(
+            Some(self),
+            Some(format!("Rename #{}", tag_name).as_str()),
+            Some("Renaming to an existing tag merges the two."),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("rename", "_Rename")]);
+        dialog.set_response_appearance("rename", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("rename"));
+        dialog.set_extra_child(Some(&entry));
+
+        let old_name = tag_name.to_string();
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window, @weak entry => move |_, response| {
+                let new_name = entry.text().trim().trim_start_matches(tags::TAG_PREFIX).to_string();
+                if response != "rename" || new_name.is_empty() || new_name == old_name {
+                    return;
+                }
+                if !tags::is_valid_name(&new_name) {
+                    log_warning!("#{} isn't a valid tag", new_name);
+                    return;
+                }
+
+                window.rewrite_notes(|body| tags::rename(body, &old_name, &new_name));
+                window.refresh_tags();
+            }),
+        );
+
+        dialog.present();
+    }
+
+    fn toggle_task(&self, task: &tasks::Task) {
+        let tree_view = &self.imp().gnote_tree_view;
+        let (title, body) = match tree_view.note_by_id(&task.note_id) {
+            Some(note) => note,
+            None => return,
+        };
+
+        // The note may have been edited since the task list was built
+        let still_matches = body
+            .lines()
+            .nth(task.line)
+            .and_then(checklist::parse_check_box_line)
+            .map_or(false, |check_box| check_box.text == task.text);
+        if !still_matches {
+            return;
+        }
+
+        if let Some(toggled) = tasks::toggle_line(&body, task.line) {
+            tree_view.set_note_body_by_id(&task.note_id, &toggled);
+            let editor = &self.imp().gnote_editor;
+            if editor.note_id().as_deref() == Some(task.note_id.as_str()) {
+                editor.set_note(&task.note_id, &title, &toggled);
+            }
+        }
+    }
+
+    // Lists the tasks as they are now, after one was toggled or changed in the meantime
+    fn refresh_task_lists(&self) {
+        let note_file = self.imp().gnote_tree_view.note_file();
+        self.imp()
+            .task_lists
+            .borrow_mut()
+            .retain(|(task_list, list_tasks)| match task_list.upgrade() {
+                Some(task_list) => {
+                    task_list.set_tasks(list_tasks(&note_file));
+                    true
+                }
+                None => false,
+            });
+    }
+
+    fn connect_task_list(&self, task_list: &GnoteTaskList, list_tasks: ListTasks) {
+        task_list.set_tasks(list_tasks(&self.imp().gnote_tree_view.note_file()));
+        self.imp()
+            .task_lists
+            .borrow_mut()
+            .push((task_list.downgrade(), list_tasks));
+        task_list.connect_local(
+            "task-toggled",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let task_list = values[0].get::<GnoteTaskList>().unwrap();
+                let index = values[1].get::<u32>().unwrap();
+                if let Some(task) = task_list.task(index as usize) {
+                    window.toggle_task(&task);
+                    // Not straight away, as the check box that was clicked is among the rows
+                    glib::idle_add_local_once(clone!(@weak window => move || {
+                        window.refresh_task_lists();
+                    }));
+                }
+                None
+            }),
+        );
+
+        task_list.connect_local(
+            "task-activated",
+            false,
+            clone!(@weak self as window => @default-return None, move |values| {
+                let task_list = values[0].get::<GnoteTaskList>().unwrap();
+                let index = values[1].get::<u32>().unwrap();
+                if let Some(task) = task_list.task(index as usize) {
+                    window.open_note_at(&task.note_path);
+                    window.imp().gnote_editor.goto_line(task.line as i32);
+                }
+                None
+            }),
+        );
+    }
+
+    fn show_tasks(&self) {
+        let open_list = GnoteTaskList::new();
+        self.connect_task_list(&open_list, tasks::open_tasks);
+
+        let upcoming_list = GnoteTaskList::new();
+        self.connect_task_list(&upcoming_list, tasks::upcoming);
+
+        let view_stack = adw::ViewStack::new();
+        view_stack
+            .add_titled(&open_list, Some("open"), "Open")
+            .set_icon_name(Some("checkbox-checked-symbolic"));
+        view_stack
+            .add_titled(&upcoming_list, Some("upcoming"), "Upcoming")
+            .set_icon_name(Some("alarm-symbolic"));
+
+        let header_bar = adw::HeaderBar::new();
+        header_bar.set_title_widget(Some(
+            &adw::ViewSwitcherTitle::builder()
+                .stack(&view_stack)
+                .title("Tasks")
+                .build(),
+        ));
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        content.append(&header_bar);
+        content.append(&view_stack);
+
+        let tasks_window = adw::Window::builder()
+            .transient_for(self)
+            .title("Tasks")
+            .default_width(400)
+            .default_height(500)
+            .content(&content)
+            .build();
+        self.watch_activity(&tasks_window);
+        tasks_window.present();
+    }
+
+    fn export_tasks(&self, file_name: &str, export: fn(&[tasks::Task]) -> String) {
+        let file_chooser = FileChooserDialogBuilder::new()
+            .title("Export tasks")
+            .action(FileChooserAction::Save)
+            .transient_for(self)
+            .modal(true)
+            .build();
+        file_chooser.set_current_name(file_name);
+        file_chooser.add_buttons(&[("Save", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+
+        file_chooser.connect_response(clone!(@weak self as window => move |dialog, response| {
+            if response == ResponseType::Ok {
+                if let Some(file_path) = dialog.file().and_then(|file| file.path()) {
+                    let tasks = tasks::collect(&window.imp().gnote_tree_view.note_file());
+                    fs::write(&file_path, export(&tasks)).unwrap_or_else(|e| {
+                        log_error!("Failed to export tasks to {}: {}", file_path.display(), e);
+                    });
+                }
+            }
+            dialog.destroy();
+        }));
+
+        file_chooser.present();
+    }
+
+    // Writes the whole notebook as one notes.json, whatever it is kept in
+    fn export_json(&self) {
+        let file_chooser = FileChooserDialogBuilder::new()
+            .title("Export notebook")
+            .action(FileChooserAction::Save)
+            .transient_for(self)
+            .modal(true)
+            .build();
+        file_chooser.set_current_name("notes.json");
+        file_chooser.add_buttons(&[("Save", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+
+        file_chooser.connect_response(clone!(@weak self as window => move |dialog, response| {
+            if response == ResponseType::Ok {
+                if let Some(file_path) = dialog.file().and_then(|file| file.path()) {
+                    window
+                        .imp()
+                        .gnote_tree_view
+                        .note_file()
+                        .save(&file_path.to_string_lossy())
+                        .unwrap_or_else(|e| log_error!("Failed to export notebook - {}", e));
+                }
+            }
+            dialog.destroy();
+        }));
+
+        file_chooser.present();
+    }
+
+    fn import_todo_txt(&self) {
+        let file_chooser = FileChooserDialogBuilder::new()
+            .title("Import todo.txt")
+            .action(FileChooserAction::Open)
+            .transient_for(self)
+            .modal(true)
+            .build();
+        file_chooser.add_buttons(&[("Open", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+
+        file_chooser.connect_response(clone!(@weak self as window => move |dialog, response| {
+            if response == ResponseType::Ok {
+                if let Some(file_path) = dialog.file().and_then(|file| file.path()) {
+                    match fs::read_to_string(&file_path) {
+                        Ok(todo_txt) => {
+                            let title = file_path
+                                .file_stem()
+                                .map(|stem| stem.to_string_lossy().to_string())
+                                .unwrap_or_else(|| "todo".to_string());
+                            let tree_view = &window.imp().gnote_tree_view;
+                            let note_path = tree_view.add_note(&title);
+                            tree_view.set_note_body_at(
+                                &note_path,
+                                &task_export::from_todo_txt(&todo_txt),
+                            );
+                            window.open_note_at(&note_path);
+                        }
+                        Err(e) => {
+                            log_error!("Failed to read {}: {}", file_path.display(), e);
+                        }
+                    }
+                }
+            }
+            dialog.destroy();
+        }));
+
+        file_chooser.present();
+    }
+
+    fn show_quick_switcher(&self) {
+        let switcher = GnoteQuickSwitcher::new();
+        switcher.set_candidates(
+            fuzzy::candidates(&self.imp().gnote_tree_view.note_file()),
+            glib::real_time() / 1_000_000,
+        );
+
+        let switcher_window = adw::Window::builder()
+            .transient_for(self)
+            .modal(true)
+            .title("Go to Note")
+            .default_width(450)
+            .default_height(400)
+            .content(&switcher)
+            .build();
+
+        switcher.connect_local(
+            "row-chosen",
+            false,
+            clone!(@weak self as window, @weak switcher_window => @default-return None, move |values| {
+                let switcher = values[0].get::<GnoteQuickSwitcher>().unwrap();
+                let index = values[1].get::<u32>().unwrap();
+                switcher_window.close();
+
+                match switcher.row(index as usize) {
+                    Some(SwitcherRow::Note(note_path)) => window.open_note_at(&note_path),
+                    // New notes go in the folder of whatever is selected
+                    Some(SwitcherRow::Create(title)) => {
+                        let note_path = window.imp().gnote_tree_view.add_note(&title);
+                        window.open_note_at(&note_path);
+                    }
+                    None => {}
+                }
+                None
+            }),
+        );
+        switcher.connect_local(
+            "cancelled",
+            false,
+            clone!(@weak switcher_window => @default-return None, move |_| {
+                switcher_window.close();
+                None
+            }),
+        );
+
+        self.watch_activity(&switcher_window);
+        switcher_window.present();
+    }
+
+    fn show_command_palette(&self) {
+        let palette = GnoteCommandPalette::new();
+
+        let palette_window = adw::Window::builder()
+            .transient_for(self)
+            .modal(true)
+            .title("Commands")
+            .default_width(450)
+            .default_height(400)
+            .content(&palette)
+            .build();
+
+        palette.connect_local(
+            "command-chosen",
+            false,
+            clone!(@weak self as window, @weak palette_window => @default-return None, move |values| {
+                let action = values[1].get::<String>().unwrap();
+                palette_window.close();
+
+                window.run_command(&action);
+                None
+            }),
+        );
+        palette.connect_local(
+            "cancelled",
+            false,
+            clone!(@weak palette_window => @default-return None, move |_| {
+                palette_window.close();
+                None
+            }),
+        );
+
+        self.watch_activity(&palette_window);
+        palette_window.present();
+    }
+
+    fn run_command(&self, detailed_action: &str) {
+        let (action, parameter) = match gio::Action::parse_detailed_name(detailed_action) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                log_error!("Failed to run {}: {}", detailed_action, e);
+                return;
+            }
+        };
+        let editor = &self.imp().gnote_editor;
+        // Images and attachments each have their own actions, so the one at the cursor runs them
+        let widget = if action.starts_with("image.") || action.starts_with("attachment.") {
+            match editor.attachment_at_cursor() {
+                Some(widget) => widget,
+                None => {
+                    self.imp().toast_overlay.add_toast(&adw::Toast::new(
+                        "Put the cursor next to an image or attachment first",
+                    ));
+                    return;
+                }
+            }
+        } else {
+            // Run from the editor so its own actions are found along with the window's
+            editor.clone().upcast()
+        };
+        if let Err(e) = widget.activate_action(&action, parameter.as_ref()) {
+            log_error!("Failed to run {}: {}", detailed_action, e);
+        }
+    }
+
+    // A smart folder holds whichever notes match every field that is filled in
+    fn show_new_smart_folder_dialog(&self) {
+        if self.is_locked() {
+            return;
+        }
+        let title_entry = gtk::Entry::builder()
+            .placeholder_text("Name")
+            .activates_default(true)
+            .build();
+        let text_entry = gtk::Entry::builder()
+            .placeholder_text("Containing text")
+            .build();
+        let regex_entry = gtk::Entry::builder()
+            .placeholder_text("Matching regular expression")
+            .build();
+        let tag_entry = gtk::Entry::builder().placeholder_text("#tag").build();
+        if let Some(tag_name) = self.imp().gnote_tag_list.selected_tags().first() {
+            tag_entry.set_text(&format!("{}{}", tags::TAG_PREFIX, tag_name));
+        }
+        let modified_since_entry = gtk::Entry::builder()
+            .placeholder_text("Modified since YYYY-MM-DD")
+            .build();
+        let unchecked_tasks_check = gtk::CheckButton::builder()
+            .label("Has unchecked tasks")
+            .build();
+
+        let fields = gtk::Box::new(gtk::Orientation::Vertical, 5);
+        fields.append(&title_entry);
+        fields.append(&text_entry);
+        fields.append(&regex_entry);
+        fields.append(&tag_entry);
+        fields.append(&modified_since_entry);
+        fields.append(&unchecked_tasks_check);
+
+        let dialog = adw::MessageDialog::new(
+            Some(self),
+            Some("New Smart Folder"),
+            Some("Notes matching all of the filled in fields show up in it as they change."),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("create", "C_reate")]);
+        dialog.set_response_appearance("create", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("create"));
+        dialog.set_extra_child(Some(&fields));
+
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window, @weak title_entry, @weak text_entry, @weak regex_entry,
+                @weak tag_entry, @weak modified_since_entry, @weak unchecked_tasks_check
+                => move |_, response| {
+                if response != "create" {
+                    return;
+                }
+
+                let field = |entry: &gtk::Entry| {
+                    Some(entry.text().trim().to_string()).filter(|text| !text.is_empty())
+                };
+                let modified_since = match field(&modified_since_entry) {
+                    Some(date) => match parse_date(&date) {
+                        Some(modified_since) => Some(modified_since),
+                        None => {
+                            log_warning!("{} isn't a date like 2024-01-31", date);
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let query = SearchQuery {
+                    text: field(&text_entry),
+                    regex: field(&regex_entry),
+                    tag: field(&tag_entry)
+                        .map(|tag| tag.trim_start_matches(tags::TAG_PREFIX).to_string()),
+                    modified_since,
+                    has_unchecked_tasks: unchecked_tasks_check.is_active(),
+                };
+                if query.is_empty() {
+                    log_warning!("A smart folder needs something to search for");
+                    return;
+                }
+
+                window.imp().gnote_tree_view.add_smart_folder(&SmartFolder {
+                    title: field(&title_entry).unwrap_or_else(|| "Smart Folder".to_string()),
+                    query,
+                });
+            }),
+        );
+
+        dialog.present();
+    }
+
+    // The notebook directory as a git repository, once syncing has been set up
+    fn git_repo(&self) -> Option<GitRepo> {
+        let settings = self.imp().settings.borrow();
+        settings.git_remote.as_ref()?;
+        // Merging works on the notebook file, so a folder of notes isn't committed
+        if settings.storage != StorageKind::Json {
+            return None;
+        }
+        Some(notebook_repo())
+    }
+
+    fn update_git_actions(&self) {
+        let enabled = self.git_repo().is_some();
+        self.action_set_enabled("notebook.git-pull", enabled);
+        self.action_set_enabled("notebook.git-push", enabled);
+    }
+
+    fn show_git_sync_dialog(&self) {
+        if self.is_encrypted() {
+            self.imp().toast_overlay.add_toast(&adw::Toast::new(
+                "Git sync can't merge an encrypted notebook",
+            ));
+            return;
+        }
+        if self.imp().settings.borrow().storage != StorageKind::Json {
+            self.imp().toast_overlay.add_toast(&adw::Toast::new(
+                "Git sync needs the notebook kept as a JSON file",
+            ));
+            return;
+        }
+        let remote = self.imp().settings.borrow().git_remote.clone();
+        let remote_entry = gtk::Entry::builder()
+            .placeholder_text("Remote, e.g. git@example.com:notes.git")
+            .text(remote.as_deref().unwrap_or(""))
+            .activates_default(true)
+            .build();
+
+        let dialog = adw::MessageDialog::new(
+            Some(self),
+            Some("Sync with Git"),
+            Some("Every save is committed, with pull and push to keep the notebook in step with the remote repository."),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("save", "_Save")]);
+        if remote.is_some() {
+            dialog.add_response("turn-off", "_Turn Off");
+            dialog.set_response_appearance("turn-off", adw::ResponseAppearance::Destructive);
+        }
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_extra_child(Some(&remote_entry));
+
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window, @weak remote_entry => move |_, response| {
+                match response {
+                    "save" => {
+                        let remote = remote_entry.text().trim().to_string();
+                        if remote.is_empty() {
+                            log_warning!("Git sync needs a remote to pull from and push to");
+                            return;
+                        }
+                        window.set_git_remote(Some(remote));
+                    }
+                    // The repository stays, it just isn't committed to any more
+                    "turn-off" => window.set_git_remote(None),
+                    _ => {}
+                }
+            }),
+        );
+
+        dialog.present();
+    }
+
+    fn set_git_remote(&self, remote: Option<String>) {
+        if let Some(remote) = &remote {
+            self.save_notes();
+            if let Err(e) = notebook_repo().init(remote) {
+                self.show_git_error(&e.to_string());
+                return;
+            }
+        }
+
+        let mut settings = self.imp().settings.borrow().clone();
+        settings.git_remote = remote;
+        if let Err(e) = settings.save(&io::get_settings_path()) {
+            log_error!("Failed to save settings - {}", e);
+        }
+        self.imp().settings.replace(settings);
+        self.update_git_actions();
+
+        // Whatever is there so far becomes the first commit
+        if let Some(repo) = self.git_repo() {
+            if let Err(e) = repo.commit("Add notebook") {
+                self.show_git_error(&e.to_string());
+            }
+        }
+    }
+
+    fn show_git_error(&self, message: &str) {
+        log_error!("{}", message);
+        self.imp()
+            .toast_overlay
+            .add_toast(&adw::Toast::new(message.lines().last().unwrap_or(message)));
+    }
+
+    // Keeps the notebook from being edited while git works on it in the background
+    fn set_syncing(&self, syncing: bool) {
+        let imp = self.imp();
+        imp.git_syncing.set(syncing);
+        imp.gnote_tree_view.set_sensitive(!syncing);
+        imp.gnote_editor.set_sensitive(!syncing);
+        imp.gnote_tag_list.set_sensitive(!syncing);
+        if syncing {
+            self.action_set_enabled("notebook.git-pull", false);
+            self.action_set_enabled("notebook.git-push", false);
+        } else {
+            self.update_git_actions();
+        }
+    }
+
+    fn git_pull(&self) {
+        if self.is_locked() {
+            return;
+        }
+        let repo = match self.git_repo() {
+            Some(repo) => repo,
+            None => return,
+        };
+        self.save_notes();
+        let sent = self.imp().gnote_tree_view.note_file();
+        self.set_syncing(true);
+
+        let git_lock = self.imp().git_lock.clone();
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        std::thread::spawn(move || {
+            let _git_lock = git_lock.lock().unwrap_or_else(|e| e.into_inner());
+            // The save above may not have been committed yet
+            let result = repo.commit_notes().and_then(|_| repo.pull());
+            let _ = sender.send(result.map_err(|e| e.to_string()));
+        });
+        receiver.attach(
+            None,
+            clone!(@weak self as window => @default-return glib::Continue(false), move |result| {
+                window.set_syncing(false);
+                match result {
+                    Ok(Some(merged)) => window.apply_pull(&sent, merged),
+                    Ok(None) => window
+                        .imp()
+                        .toast_overlay
+                        .add_toast(&adw::Toast::new("Notes are up to date")),
+                    Err(e) => window.show_git_error(&e),
+                }
+                glib::Continue(false)
+            }),
+        );
+    }
+
+    // Edits made while pulling go on top of what was pulled, and are committed with the next save
+    fn apply_pull(&self, sent: &NoteFile, merged: Merged) {
+        let current = self.imp().gnote_tree_view.note_file();
+        let edited = merge::merge(Some(sent), &current, &merged.note_file);
+        if !merge::same_notes(&edited.note_file, &current) {
+            self.show_notes(&edited.note_file);
+        }
+        if !merge::same_notes(&edited.note_file, &merged.note_file) {
+            self.schedule_save();
+        }
+        self.imp()
+            .disk_note_file
+            .replace(Some(merged.note_file.clone()));
+        self.add_conflicts(edited.conflicts);
+        if merged.conflicts.is_empty() {
+            self.imp()
+                .toast_overlay
+                .add_toast(&adw::Toast::new("Pulled notes"));
+        }
+        self.add_conflicts(merged.conflicts);
+    }
+
+    // Shows the notebook as saved by a sync
+    fn reload_notes(&self) {
+        match self.storage().load() {
+            Ok(note_file) => {
+                self.show_notes(&note_file);
+                self.imp().disk_note_file.replace(Some(note_file));
+            }
+            Err(e) => log_error!("Failed to load notes - {}", e),
+        }
+    }
+
+    // Shows another version of the notebook, keeping the same note selected. The editor only
+    // gets the note again if it changed, so the cursor stays put otherwise.
+    fn show_notes(&self, note_file: &NoteFile) {
+        let tree_view = &self.imp().gnote_tree_view;
+        let selected_id = tree_view.selected_note_id();
+        let selected_note = tree_view.selected_note();
+        tree_view.load(note_file);
+        if let Some(note_path) = selected_id.and_then(|note_id| self.note_path_by_id(&note_id)) {
+            tree_view.select_note_at(&note_path);
+            if tree_view.selected_note() != selected_note {
+                self.open_selected_note();
+            }
+        }
+        self.refresh_tags();
+
+        let mut history = self.imp().history.borrow_mut();
+        if history.record_notes(&tree_view.note_file(), glib::real_time() / 1_000_000) {
+            self.save_history(&history);
+        }
+    }
+
+    fn git_push(&self) {
+        if self.is_locked() {
+            return;
+        }
+        let repo = match self.git_repo() {
+            Some(repo) => repo,
+            None => return,
+        };
+        self.save_notes();
+        self.set_syncing(true);
+
+        let git_lock = self.imp().git_lock.clone();
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        std::thread::spawn(move || {
+            let _git_lock = git_lock.lock().unwrap_or_else(|e| e.into_inner());
+            // The save above may not have been committed yet
+            let result = repo.commit_notes().and_then(|_| repo.push());
+            let _ = sender.send(result.map_err(|e| e.to_string()));
+        });
+        receiver.attach(
+            None,
+            clone!(@weak self as window => @default-return glib::Continue(false), move |result| {
+                window.set_syncing(false);
+                match result {
+                    Ok(()) => window
+                        .imp()
+                        .toast_overlay
+                        .add_toast(&adw::Toast::new("Pushed notes")),
+                    Err(e) => window.show_git_error(&e),
+                }
+                glib::Continue(false)
+            }),
+        );
+    }
+
+    fn webdav(&self) -> Option<WebDav> {
+        let settings = self.imp().settings.borrow();
+        Some(WebDav::new(
+            settings.webdav_url.as_deref()?,
+            settings.webdav_username.as_deref(),
+            settings.webdav_password.as_deref(),
+        ))
+    }
+
+    fn setup_webdav_sync(&self) {
+        glib::timeout_add_seconds_local(
+            SYNC_INTERVAL,
+            clone!(@weak self as window => @default-return glib::Continue(false), move || {
+                window.webdav_sync();
+                glib::Continue(true)
+            }),
+        );
+        self.webdav_sync();
+    }
+
+    fn show_webdav_sync_dialog(&self) {
+        if self.is_encrypted() {
+            self.imp().toast_overlay.add_toast(&adw::Toast::new(
+                "WebDAV sync can't merge an encrypted notebook",
+            ));
+            return;
+        }
+        let settings = self.imp().settings.borrow().clone();
+        let url_entry = gtk::Entry::builder()
+            .placeholder_text("Folder URL, e.g. https://example.com/dav/notes")
+            .text(settings.webdav_url.as_deref().unwrap_or(""))
+            .build();
+        let username_entry = gtk::Entry::builder()
+            .placeholder_text("User name")
+            .text(settings.webdav_username.as_deref().unwrap_or(""))
+            .build();
+        let password_entry = gtk::PasswordEntry::builder()
+            .placeholder_text("Password")
+            .text(settings.webdav_password.as_deref().unwrap_or(""))
+            .show_peek_icon(true)
+            .activates_default(true)
+            .build();
+
+        let fields = gtk::Box::new(gtk::Orientation::Vertical, 5);
+        fields.append(&url_entry);
+        fields.append(&username_entry);
+        fields.append(&password_entry);
+
+        let dialog = adw::MessageDialog::new(
+            Some(self),
+            Some("Sync with WebDAV"),
+            Some("The notebook and its attachments are kept on the server, and changes made elsewhere are merged in every minute and shortly after edits are saved."),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("save", "_Save")]);
+        if settings.webdav_url.is_some() {
+            dialog.add_response("turn-off", "_Turn Off");
+            dialog.set_response_appearance("turn-off", adw::ResponseAppearance::Destructive);
+        }
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_extra_child(Some(&fields));
+
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window, @weak url_entry, @weak username_entry, @weak password_entry
+                => move |_, response| {
+                let field = |text: glib::GString| {
+                    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+                };
+                match response {
+                    "save" => {
+                        let url = field(url_entry.text());
+                        if url.is_none() {
+                            log_warning!("WebDAV sync needs the URL of a folder on the server");
+                            return;
+                        }
+                        window.set_webdav(url, field(username_entry.text()), field(password_entry.text()));
+                    }
+                    "turn-off" => window.set_webdav(None, None, None),
+                    _ => {}
+                }
+            }),
+        );
+
+        dialog.present();
+    }
+
+    fn set_webdav(&self, url: Option<String>, username: Option<String>, password: Option<String>) {
+        let mut settings = self.imp().settings.borrow().clone();
+        // What was agreed with one server means nothing to another
+        if settings.webdav_url != url {
+            self.imp().sync_state.replace(SyncState::default());
+            self.save_sync_state();
+        }
+        if let Some(old_url) = &settings.webdav_url {
+            if let Err(e) =
+                keyring::set_webdav_password(old_url, settings.webdav_username.as_deref(), None)
+            {
+                log_warning!(
+                    "Failed to remove the WebDAV password from the keyring - {}",
+                    e
+                );
+            }
+        }
+        if let Some(url) = &url {
+            if let Err(e) =
+                keyring::set_webdav_password(url, username.as_deref(), password.as_deref())
+            {
+                log_error!("Failed to keep the WebDAV password in the keyring - {}", e);
+            }
+        }
+        settings.webdav_url = url;
+        settings.webdav_username = username;
+        settings.webdav_password = password;
+        if let Err(e) = settings.save(&io::get_settings_path()) {
+            log_error!("Failed to save settings - {}", e);
+        }
+        self.imp().settings.replace(settings);
+        self.webdav_sync();
+    }
+
+    fn save_sync_state(&self) {
+        self.imp()
+            .sync_state
+            .borrow()
+            .save(&io::get_sync_state_path())
+            .unwrap_or_else(|e| log_error!("Failed to save sync state - {}", e));
+    }
+
+    fn set_sync_status(&self, icon_name: &str, tooltip: &str) {
+        let sync_status = &self.imp().sync_status;
+        sync_status.set_icon_name(icon_name);
+        sync_status.set_tooltip_text(Some(tooltip));
+        sync_status.set_visible(true);
+    }
+
+    // Each sync sends the whole notebook, which is too much for every save while typing
+    fn schedule_webdav_sync(&self) {
+        if let Some(source_id) = self.imp().pending_webdav_sync.take() {
+            source_id.remove();
+        }
+        if self.webdav().is_none() {
+            return;
+        }
+        let source_id = glib::timeout_add_seconds_local_once(
+            SYNC_DELAY,
+            clone!(@weak self as window => move || {
+                window.imp().pending_webdav_sync.take();
+                window.webdav_sync();
+            }),
+        );
+        self.imp().pending_webdav_sync.replace(Some(source_id));
+    }
+
+    // Syncs in the background, or once more after the sync that is already under way
+    fn webdav_sync(&self) {
+        let imp = self.imp();
+        if let Some(source_id) = imp.pending_webdav_sync.take() {
+            source_id.remove();
+        }
+        // Nothing is synced while locked, as only the key can read the notebook
+        if self.is_locked() {
+            return;
+        }
+        let webdav = match self.webdav() {
+            Some(webdav) => webdav,
+            None => {
+                imp.sync_status.set_visible(false);
+                return;
+            }
+        };
+        if imp.webdav_syncing.get() {
+            imp.sync_again.set(true);
+            return;
+        }
+        imp.webdav_syncing.set(true);
+        self.set_sync_status("emblem-synchronizing-symbolic", "Syncing…");
+
+        let note_file = imp.gnote_tree_view.note_file();
+        let state = imp.sync_state.borrow().clone();
+        let store = AttachmentStore::new(io::get_attachments_dir());
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        std::thread::spawn(move || {
+            let result = webdav.sync(&note_file, &state, &store);
+            let _ = sender.send((note_file, result));
+        });
+        receiver.attach(
+            None,
+            clone!(@weak self as window => @default-return glib::Continue(false), move |(note_file, result)| {
+                window.finish_webdav_sync(&note_file, result);
+                glib::Continue(false)
+            }),
+        );
+    }
+
+    fn finish_webdav_sync(&self, sent: &NoteFile, result: Result<Synced, SyncError>) {
+        let imp = self.imp();
+        imp.webdav_syncing.set(false);
+        match result {
+            Ok(synced) => {
+                if !merge::same_notes(&synced.note_file, sent) {
+                    // Edits made while syncing go on top of what came back
+                    let current = imp.gnote_tree_view.note_file();
+                    let merged = merge::merge(Some(sent), &current, &synced.note_file);
+                    match self.storage().save(&merged.note_file) {
+                        Ok(()) => self.reload_notes(),
+                        Err(e) => log_error!("Failed to save synced notes - {}", e),
+                    }
+                    self.add_conflicts(merged.conflicts);
+                }
+                self.add_conflicts(synced.conflicts);
+                imp.sync_state.replace(synced.state);
+                self.save_sync_state();
+                let time = glib::DateTime::now_local()
+                    .and_then(|now| now.format("%H:%M"))
+                    .map(|time| time.to_string())
+                    .unwrap_or_default();
+                self.set_sync_status("emblem-ok-symbolic", &format!("Synced at {}", time));
+            }
+            Err(SyncError::Offline(e)) => {
+                log_warning!("{}", e);
+                let pending = imp
+                    .sync_state
+                    .borrow()
+                    .has_pending(&imp.gnote_tree_view.note_file());
+                self.set_sync_status(
+                    "network-offline-symbolic",
+                    if pending {
+                        "Offline, changes will be uploaded once the server can be reached"
+                    } else {
+                        "Offline"
+                    },
+                );
+            }
+            Err(e) => {
+                log_error!("{}", e);
+                self.set_sync_status("dialog-warning-symbolic", &e.to_string());
+            }
+        }
+
+        if imp.sync_again.take() {
+            self.webdav_sync();
+        }
+    }
+
+    fn note_path_by_id(&self, note_id: &str) -> Option<Vec<i32>> {
+        self.imp()
+            .gnote_tree_view
+            .note_file()
+            .notes()
+            .into_iter()
+            .find(|note| note.item.id == note_id)
+            .map(|note| note.path)
+    }
+
+    fn set_conflicts(&self, conflicts: Vec<Conflict>) {
+        self.action_set_enabled("notebook.show-conflicts", !conflicts.is_empty());
+        self.imp().conflicts.replace(conflicts);
+    }
+
+    // Adds conflicts from a sync to those not yet reviewed, offering to review them
+    fn add_conflicts(&self, new_conflicts: Vec<Conflict>) {
+        if new_conflicts.is_empty() {
+            return;
+        }
+        let toast = adw::Toast::new(&format!(
+            "{} notes were changed both here and elsewhere",
+            new_conflicts.len()
+        ));
+        toast.set_button_label(Some("Review"));
+        toast.set_action_name(Some("notebook.show-conflicts"));
+        self.imp().toast_overlay.add_toast(&toast);
+
+        let mut conflicts = self.imp().conflicts.take();
+        for conflict in new_conflicts {
+            conflicts.retain(|known| known.id != conflict.id);
+            conflicts.push(conflict);
+        }
+        self.set_conflicts(conflicts);
+    }
+
+    // Gives the note the chosen version, unless the merge already went with it
+    fn resolve_conflict(&self, note_id: &str, title: String, body: String) {
+        let note_file = self.imp().gnote_tree_view.note_file();
+        match note_file
+            .notes()
+            .into_iter()
+            .find(|note| note.item.id == note_id)
+        {
+            Some(note) => {
+                if note.item.title != title || note.item.body.as_deref() != Some(body.as_str()) {
+                    self.write_notes(&[(note.path, (title, body))]);
+                }
+            }
+            None => log_warning!("{} is no longer in the notebook", title),
+        }
+
+        let mut conflicts = self.imp().conflicts.take();
+        conflicts.retain(|conflict| conflict.id != note_id);
+        self.set_conflicts(conflicts);
+    }
+
+    fn show_conflicts(&self) {
+        let conflicts = GnoteConflicts::new();
+        conflicts.set_conflicts(self.imp().conflicts.borrow().clone());
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        content.append(&adw::HeaderBar::new());
+        content.append(&conflicts);
+
+        let conflicts_window = adw::Window::builder()
+            .transient_for(self)
+            .modal(true)
+            .title("Changed Here and Elsewhere")
+            .default_width(800)
+            .default_height(500)
+            .content(&content)
+            .build();
+
+        conflicts.connect_local(
+            "resolved",
+            false,
+            clone!(@weak self as window, @weak conflicts_window => @default-return None, move |values| {
+                let conflicts = values[0].get::<GnoteConflicts>().unwrap();
+                let note_id = values[1].get::<String>().unwrap();
+                let title = values[2].get::<String>().unwrap();
+                let body = values[3].get::<String>().unwrap();
+                window.resolve_conflict(&note_id, title, body);
+                if conflicts.is_empty() {
+                    conflicts_window.close();
+                }
+                None
+            }),
+        );
+
+        self.watch_activity(&conflicts_window);
+        conflicts_window.present();
+    }
+}
+
+fn notebook_repo() -> GitRepo {
+    let file_name = |path: &std::path::Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    GitRepo::new(
+        io::get_notebook_dir(),
+        &file_name(std::path::Path::new(&io::get_notes_path())),
+        &file_name(&io::get_attachments_dir()),
+    )
+}
+
+// Midnight local time at the start of a YYYY-MM-DD date, in seconds since the Unix epoch
+fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.splitn(3, '-').map(|part| part.parse::<i32>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    glib::DateTime::from_local(year, month, day, 0, 0, 0.0)
+        .ok()
+        .map(|date_time| date_time.to_unix())
+}
+
+// Looks the WebDAV password up in the keyring, first moving it there from older settings files
+fn load_webdav_password(settings: &mut Settings) {
+    let url = match settings.webdav_url.clone() {
+        Some(url) => url,
+        None => return,
+    };
+    let username = settings.webdav_username.clone();
+    if let Some(password) = &settings.webdav_password {
+        match keyring::set_webdav_password(&url, username.as_deref(), Some(password)) {
+            // Saving leaves the password out
+            Ok(()) => settings
+                .save(&io::get_settings_path())
+                .unwrap_or_else(|e| log_error!("Failed to save settings - {}", e)),
+            Err(e) => log_error!("Failed to move the WebDAV password to the keyring - {}", e),
+        }
+        return;
+    }
+    match keyring::webdav_password(&url, username.as_deref()) {
+        Ok(password) => settings.webdav_password = password,
+        Err(e) => log_warning!("Failed to look up the WebDAV password - {}", e),
     }
 }